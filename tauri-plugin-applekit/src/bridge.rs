@@ -1,6 +1,12 @@
+#[cfg(native_bridge)]
 use std::ffi::c_char;
 
+#[cfg(native_bridge)]
 extern "C" {
     pub(crate) fn set_user_default(key: *const c_char, value: *const c_char) -> *const c_char;
+    /// The value comes back as a `strdup` copy (see `UserDefaultUtil.swift`), freed by the caller.
     pub(crate) fn get_user_default(key: *const c_char) -> *const c_char;
 }
+
+#[cfg(not(native_bridge))]
+pub(crate) use crate::stub::{get_user_default, set_user_default};
@@ -37,10 +37,12 @@ impl<R: Runtime> Applekit<R> {
     }
 }
 
+/// Copy a string returned by the bridge and free it, both sides allocate it with `strdup`.
 unsafe fn take_string(ptr: *const c_char) -> String {
     if ptr.is_null() {
         return String::new();
     }
-    // 只读取，不接管所有权
-    CStr::from_ptr(ptr).to_string_lossy().into_owned()
+    let value = CStr::from_ptr(ptr).to_string_lossy().into_owned();
+    libc::free(ptr as *mut libc::c_void);
+    value
 }
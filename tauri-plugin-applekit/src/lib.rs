@@ -14,6 +14,8 @@ mod bridge;
 mod commands;
 mod error;
 mod models;
+#[cfg(not(native_bridge))]
+mod stub;
 
 pub use error::{Error, Result};
 
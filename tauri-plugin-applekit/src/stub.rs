@@ -0,0 +1,34 @@
+//! In-memory stand-in for the Swift bridge, used when the crate is built without the native
+//! library (`native-macos` disabled or a target other than macOS).
+
+use std::collections::HashMap;
+use std::ffi::{c_char, CStr, CString};
+use std::sync::{Mutex, MutexGuard, OnceLock};
+
+static USER_DEFAULTS: OnceLock<Mutex<HashMap<String, String>>> = OnceLock::new();
+
+fn user_defaults() -> MutexGuard<'static, HashMap<String, String>> {
+    USER_DEFAULTS
+        .get_or_init(Default::default)
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+}
+
+pub(crate) unsafe fn set_user_default(key: *const c_char, value: *const c_char) -> *const c_char {
+    let key = CStr::from_ptr(key).to_string_lossy().into_owned();
+    let value = CStr::from_ptr(value).to_string_lossy().into_owned();
+    user_defaults().insert(key, value);
+    std::ptr::null()
+}
+
+/// Same contract as the Swift side: the returned string is a `strdup` copy the caller releases
+/// with `free`, an unknown key yields an empty string.
+pub(crate) unsafe fn get_user_default(key: *const c_char) -> *const c_char {
+    let key = CStr::from_ptr(key).to_string_lossy();
+    let value = user_defaults()
+        .get(key.as_ref())
+        .cloned()
+        .unwrap_or_default();
+    let value = CString::new(value).unwrap_or_default();
+    libc::strdup(value.as_ptr())
+}
@@ -1,4 +1,203 @@
-import {invoke} from '@tauri-apps/api/core'
+import {Channel, invoke} from '@tauri-apps/api/core'
+import {listen, UnlistenFn} from '@tauri-apps/api/event'
+
+/**
+ * Advertisement data merged over the packets of a peripheral, byte strings are hex encoded.
+ */
+export interface Advertisement {
+    localName: string | null
+    /** Payload keyed by Bluetooth SIG company identifier, without the identifier. */
+    manufacturerData: Record<number, string>
+    serviceUuids: string[]
+    serviceData: Record<string, string>
+    txPowerLevel: number | null
+    isConnectable: boolean | null
+    solicitedServiceUuids: string[]
+    overflowServiceUuids: string[]
+}
+
+export type BeaconFrame =
+    | { type: 'iBeacon', uuid: string, major: number, minor: number, measuredPower: number }
+    | { type: 'altBeacon', manufacturerId: number, id1: string, id2: number, id3: number, referenceRssi: number, reserved: number }
+    | { type: 'eddystoneUid', namespace: string, instance: string, txPower: number }
+    | { type: 'eddystoneUrl', url: string, txPower: number }
+    | { type: 'eddystoneTlm', version: number, batteryVoltage: number, temperature: number | null, advertisingCount: number, uptime: number }
+    | { type: 'eddystoneEid', eid: string, txPower: number }
+
+/**
+ * A decoded beacon frame, `distance` is in meters and null for frames without TX power.
+ */
+export type Beacon = BeaconFrame & { distance: number | null }
+
+export interface BeaconSighting {
+    identifier: string
+    rssi: number
+    beacon: Beacon
+}
+
+export interface BeaconRegion {
+    uuid?: string
+    major?: number
+    minor?: number
+    namespace?: string
+    instance?: string
+}
+
+export interface Device {
+    uuid: string
+    manufacture: string | null
+    model: string | null
+    advertisement: Advertisement
+    rssi: number
+    mac_addr: string | null
+    bl_name: string | null
+    name: string | null
+    state: string | null
+    beacons: Beacon[]
+}
+
+export interface DistanceEstimate {
+    distance: number
+    min: number
+    max: number
+    calibrated: boolean
+}
+
+export interface RssiUpdate {
+    identifier: string
+    rssi: number
+    estimatedRssi: number
+    filteredRssi: number
+    variance: number
+    rejected: boolean
+    active: boolean
+    distance: DistanceEstimate
+}
+
+/**
+ * A value pushed by a subscribed characteristic, `timestamp` is in milliseconds since the epoch.
+ */
+export interface Notification<V = number[]> {
+    identifier: string
+    service: string
+    characteristic: string
+    value: V
+    timestamp: number
+    /** The decoded value, for the characteristics `read_profile` supports. */
+    profile?: ProfileValue
+}
+
+export interface PresenceUpdate {
+    presence: boolean
+    reason: string
+    /** The trusted device whose presence changed, null for the monitoring of the Swift side. */
+    identifier: string | null
+}
+
+/**
+ * How the presence of the trusted devices adds up: present while one of them is, all of them
+ * are, or at least `quorum` of them are.
+ */
+export type PresencePolicy = 'any' | 'all' | { quorum: number }
+
+export interface AggregatePresence {
+    presence: boolean
+    policy: PresencePolicy
+    /** The trusted devices present, sorted. */
+    present: string[]
+    monitored: number
+    /** The device whose change decided it, null when the policy or the trusted devices changed. */
+    identifier: string | null
+}
+
+export type BluetoothEvent =
+    | { event: 'deviceDiscovered', data: Device }
+    | { event: 'deviceUpdated', data: Device }
+    | { event: 'deviceLost', data: Device }
+    | { event: 'beacon', data: BeaconSighting }
+    | { event: 'rssi', data: RssiUpdate }
+    | { event: 'presence', data: PresenceUpdate }
+    | { event: 'powerWarning' }
+    | { event: 'notification', data: Notification }
+    | { event: 'scanSummary', data: ScanSummary }
+    | { event: 'reconnect', data: ReconnectUpdate }
+    | { event: 'adapterState', data: AdapterState }
+    | { event: 'aggregatePresence', data: AggregatePresence }
+
+export async function onDeviceDiscovered(handler: (device: Device) => void): Promise<UnlistenFn> {
+    return await listen<Device>('bluetooth://device-discovered', (e) => handler(e.payload))
+}
+
+export async function onDeviceUpdated(handler: (device: Device) => void): Promise<UnlistenFn> {
+    return await listen<Device>('bluetooth://device-updated', (e) => handler(e.payload))
+}
+
+export async function onDeviceLost(handler: (device: Device) => void): Promise<UnlistenFn> {
+    return await listen<Device>('bluetooth://device-lost', (e) => handler(e.payload))
+}
+
+export async function onBeacon(handler: (sighting: BeaconSighting) => void): Promise<UnlistenFn> {
+    return await listen<BeaconSighting>('bluetooth://beacon', (e) => handler(e.payload))
+}
+
+export async function onRssi(handler: (update: RssiUpdate) => void): Promise<UnlistenFn> {
+    return await listen<RssiUpdate>('bluetooth://rssi', (e) => handler(e.payload))
+}
+
+export async function onPresence(handler: (update: PresenceUpdate) => void): Promise<UnlistenFn> {
+    return await listen<PresenceUpdate>('bluetooth://presence', (e) => handler(e.payload))
+}
+
+export async function onAggregatePresence(handler: (aggregate: AggregatePresence) => void): Promise<UnlistenFn> {
+    return await listen<AggregatePresence>('bluetooth://aggregate-presence', (e) => handler(e.payload))
+}
+
+export async function onPowerWarning(handler: () => void): Promise<UnlistenFn> {
+    return await listen('bluetooth://power-warning', () => handler())
+}
+
+export async function onNotification(handler: (notification: Notification) => void): Promise<UnlistenFn> {
+    return await listen<Notification>('bluetooth://notification', (e) => handler(e.payload))
+}
+
+export async function onReconnect(handler: (update: ReconnectUpdate) => void): Promise<UnlistenFn> {
+    return await listen<ReconnectUpdate>('bluetooth://reconnect', (e) => handler(e.payload))
+}
+
+export async function onScanSummary(handler: (summary: ScanSummary) => void): Promise<UnlistenFn> {
+    return await listen<ScanSummary>('bluetooth://scan-summary', (e) => handler(e.payload))
+}
+
+export async function onAdapterState(handler: (state: AdapterState) => void): Promise<UnlistenFn> {
+    return await listen<AdapterState>('bluetooth://adapter-state', (e) => handler(e.payload))
+}
+
+export type BluetoothErrorCode =
+    | 'io'
+    | 'json'
+    | 'adapterPoweredOff'
+    | 'adapterNotReady'
+    | 'unauthorized'
+    | 'deviceNotFound'
+    | 'invalidIdentifier'
+    | 'notConnected'
+    | 'connection'
+    | 'timeout'
+    | 'gattError'
+    | 'gatt'
+    | 'unsupported'
+    | 'calibration'
+    | 'invalidValue'
+    | 'presenceAction'
+    | 'pluginInvoke'
+
+/**
+ * What the commands reject with, `code` tells the errors apart and `message` describes them.
+ */
+export interface BluetoothError {
+    code: BluetoothErrorCode
+    message: string
+}
 
 export async function echo(value: string): Promise<string | null> {
     return await invoke<{ value?: string }>('plugin:bluetooth|echo', {
@@ -8,16 +207,153 @@ export async function echo(value: string): Promise<string | null> {
     }).then((r) => (r.value ? r.value : null));
 }
 
-export async function start_scanning(): Promise<boolean> {
+/**
+ * State of the bluetooth adapter. Scans and connections fail with the matching `BluetoothError`
+ * unless it is `poweredOn`, `unknown` and `resetting` are reported as `adapterNotReady`.
+ */
+export type AdapterState =
+    | 'unknown'
+    | 'resetting'
+    | 'unsupported'
+    | 'unauthorized'
+    | 'poweredOff'
+    | 'poweredOn'
+
+export async function get_adapter_state(): Promise<AdapterState> {
+    return await invoke<AdapterState>('plugin:bluetooth|get_adapter_state')
+}
+
+/**
+ * Resolves once the adapter is powered on, rejects with the reason it isn't after `timeout`
+ * seconds, 10 by default.
+ */
+export async function wait_for_powered_on(timeout?: number): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|wait_for_powered_on', {
+        timeout,
+    }).then((r) => r.success)
+}
+
+/**
+ * What a scan reports, every criterion that is set must match.
+ */
+export interface ScanOptions {
+    /** Service UUIDs the devices advertise. */
+    services?: string[]
+    /** Case insensitive prefix of the name. */
+    namePrefix?: string
+    /** Regular expression, in the syntax of the Rust `regex` crate, the name matches. */
+    namePattern?: string
+    /** Company identifiers of the manufacturer data. */
+    companyIds?: number[]
+    minRssi?: number
+    /** Report every advertisement of a device rather than only the first, defaults to true. */
+    allowDuplicates?: boolean
+}
+
+/**
+ * Start scanning, `onEvent` receives the device events of this scan until it is stopped.
+ */
+export async function start_scanning(onEvent?: (event: BluetoothEvent) => void, options?: ScanOptions): Promise<boolean> {
+    let channel: Channel<BluetoothEvent> | undefined
+    if (onEvent) {
+        channel = new Channel<BluetoothEvent>()
+        channel.onmessage = onEvent
+    }
     return await invoke<{ success: boolean }>('plugin:bluetooth|start_scanning', {
-        data: {}
+        options,
+        channel,
     }).then((r) => r.success)
 }
 
+/**
+ * Open a scan session and return its ID, `onBeacon` receives the beacons of `region` until the
+ * session is stopped with `stop_scan_session`. Every beacon is reported when `region` is omitted.
+ */
+export async function scan_beacons(onBeacon: (sighting: BeaconSighting) => void, region?: BeaconRegion): Promise<number> {
+    const channel = new Channel<BluetoothEvent>()
+    channel.onmessage = (event) => {
+        if (event.event === 'beacon') {
+            onBeacon(event.data)
+        }
+    }
+    return await invoke<number>('plugin:bluetooth|scan_beacons', {
+        region,
+        channel,
+    })
+}
+
+/**
+ * Stop the scan of `start_scanning`, the scan sessions keep running.
+ */
 export async function stop_scanning(): Promise<boolean> {
-    return await invoke<{ success: boolean }>('plugin:bluetooth|stop_scanning', {
-        data: {}
-    }).then((r) => r.success)
+    return await invoke<{ success: boolean }>('plugin:bluetooth|stop_scanning').then((r) => r.success)
+}
+
+export interface DeviceScanSummary {
+    identifier: string
+    name: string | null
+    sightings: number
+    maxRssi: number
+    averageRssi: number
+    /** Milliseconds from the start of the session. */
+    timeToFirstSighting: number
+}
+
+export interface ScanSummary {
+    sessionId: number
+    /** Milliseconds since the Unix epoch. */
+    startedAt: number
+    /** Milliseconds. */
+    duration: number
+    devices: DeviceScanSummary[]
+}
+
+/**
+ * Scan for `duration` seconds alongside the other scans and return the session ID,
+ * `onSummary` receives what the session saw once it ends.
+ */
+export async function start_scan_session(
+    duration: number,
+    options?: ScanOptions,
+    onSummary?: (summary: ScanSummary) => void,
+): Promise<number> {
+    // listen first, a short session may end before its ID is known.
+    let sessionId: number | undefined
+    const early: ScanSummary[] = []
+    const unlisten = onSummary
+        ? await onScanSummary((summary) => {
+            if (sessionId === undefined) {
+                early.push(summary)
+            } else if (summary.sessionId === sessionId) {
+                unlisten?.()
+                onSummary(summary)
+            }
+        })
+        : undefined
+    try {
+        sessionId = await invoke<number>('plugin:bluetooth|start_scan_session', {
+            options,
+            duration,
+        })
+    } catch (e) {
+        unlisten?.()
+        throw e
+    }
+    const summary = early.find((summary) => summary.sessionId === sessionId)
+    if (summary && onSummary) {
+        unlisten?.()
+        onSummary(summary)
+    }
+    return sessionId
+}
+
+/**
+ * End a scan session early, returns its summary or null if it already ended.
+ */
+export async function stop_scan_session(sessionId: number): Promise<ScanSummary | null> {
+    return await invoke<ScanSummary | null>('plugin:bluetooth|stop_scan_session', {
+        sessionId,
+    })
 }
 
 export async function set_passive_mode(passiveMode: boolean): Promise<boolean> {
@@ -38,8 +374,510 @@ export async function disconnect_device(identifier: string): Promise<boolean> {
     }).then((r) => r.success)
 }
 
+/**
+ * Connect to a device, resolves once it is connected and rejects with a `BluetoothError` if the
+ * connection fails or takes longer than `timeout` seconds, 10 by default.
+ */
+export async function connect(identifier: string, timeout?: number): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|connect', {
+        identifier,
+        timeout,
+    }).then((r) => r.success)
+}
+
+/**
+ * Disconnect from a device, resolves once the connection is closed.
+ */
+export async function disconnect(identifier: string, timeout?: number): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|disconnect', {
+        identifier,
+        timeout,
+    }).then((r) => r.success)
+}
+
+/**
+ * How a lost connection is retried, delays grow from `initialDelay` by `multiplier` up to
+ * `maxDelay`. Durations are in seconds.
+ */
+export interface ReconnectPolicy {
+    /** Defaults to 1. */
+    initialDelay?: number
+    /** Defaults to 2. */
+    multiplier?: number
+    /** Also how long an attempt may take, defaults to 60. */
+    maxDelay?: number
+    /** Unlimited if omitted. */
+    maxAttempts?: number
+    /** Fraction of each delay added or removed at random, defaults to 0.1. */
+    jitter?: number
+}
+
+export type ReconnectStatus =
+    | { status: 'scheduled', delay: number }
+    | { status: 'attempting' }
+    | { status: 'failed' }
+    | { status: 'connected' }
+    | { status: 'gaveUp' }
+    | { status: 'paused' }
+
+export type ReconnectUpdate = ReconnectStatus & {
+    identifier: string
+    /** The attempt the update is about, 0 before the first one. */
+    attempt: number
+}
+
+/**
+ * Reconnect `identifier` whenever its connection drops, omit `policy` to stop. Attempts pause
+ * while the adapter is off and `disconnect_device` cancels them.
+ */
+export async function set_reconnect_policy(identifier: string, policy?: ReconnectPolicy): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|set_reconnect_policy', {
+        identifier,
+        policy,
+    }).then((r) => r.success)
+}
+
 export async function read_rssi(identifier: string): Promise<boolean> {
     return await invoke<{ success: boolean }>('plugin:bluetooth|read_rssi', {
         identifier,
     }).then((r) => r.success)
 }
+
+export type RssiFilterStage =
+    | { type: 'ema', alpha: number }
+    | { type: 'kalman', processNoise: number, measurementNoise: number }
+    | { type: 'median', window: number }
+    | { type: 'zScore', window: number, threshold: number }
+
+export interface RssiFilterConfig {
+    stages: RssiFilterStage[]
+}
+
+/**
+ * Configure the RSSI filter pipeline of a device, or the default one when `identifier` is null.
+ */
+export async function set_rssi_filter(identifier: string | null, config: RssiFilterConfig): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|set_rssi_filter', {
+        identifier,
+        config,
+    }).then((r) => r.success)
+}
+
+export interface PathLossModel {
+    measuredPower: number
+    pathLossExponent: number
+    residualStd: number
+    calibrated: boolean
+}
+
+/**
+ * Start collecting RSSI readings of a device held at `referenceDistance` meters. Call it again
+ * with another distance to also fit the path loss exponent, then `finish_calibration`.
+ */
+export async function start_calibration(identifier: string, referenceDistance: number): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|start_calibration', {
+        identifier,
+        referenceDistance,
+    }).then((r) => r.success)
+}
+
+export async function finish_calibration(identifier: string): Promise<PathLossModel> {
+    return await invoke<PathLossModel>('plugin:bluetooth|finish_calibration', {
+        identifier,
+    })
+}
+
+export async function cancel_calibration(identifier: string): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|cancel_calibration', {
+        identifier,
+    }).then((r) => r.success)
+}
+
+export async function get_calibration(identifier: string): Promise<PathLossModel | null> {
+    return await invoke<PathLossModel | null>('plugin:bluetooth|get_calibration', {
+        identifier,
+    })
+}
+
+export interface RssiPoint {
+    at: number
+    rssi: number
+}
+
+/**
+ * A device known to the registry, times are in milliseconds since the Unix epoch.
+ */
+export interface DeviceSnapshot {
+    device: Device
+    firstSeen: number
+    lastSeen: number
+    rssiHistory: RssiPoint[]
+    /** Last result of `read_device_info`. */
+    deviceInfo: DeviceInformation | null
+}
+
+export interface DeviceFilter {
+    name?: string
+    minRssi?: number
+    state?: string
+}
+
+/**
+ * Devices seen recently, most recently seen first. Devices not heard from within `deviceTtl`
+ * seconds of the plugin configuration are left out.
+ */
+export async function list_devices(filter?: DeviceFilter): Promise<DeviceSnapshot[]> {
+    return await invoke<DeviceSnapshot[]>('plugin:bluetooth|list_devices', {
+        filter,
+    })
+}
+
+export async function get_device(identifier: string): Promise<DeviceSnapshot | null> {
+    return await invoke<DeviceSnapshot | null>('plugin:bluetooth|get_device', {
+        identifier,
+    })
+}
+
+export interface PresenceConfig {
+    lockRssi: number
+    unlockRssi: number
+    /** Seconds the signal may stay below `lockRssi` before the user is considered away. */
+    proximityTimeout: number
+    /** Seconds without any reading before the device is considered lost. */
+    signalTimeout: number
+    thresholdRssi: number
+    /** Seconds between RSSI reads in active mode. */
+    activeReadInterval: number
+}
+
+export interface TrustedDevice {
+    identifier: string
+    alias?: string | null
+    macAddr?: string | null
+    calibration?: PathLossModel | null
+    presence?: Partial<PresenceConfig>
+}
+
+/**
+ * Trust a device and monitor its presence, also after the app restarts. The stored entry is
+ * returned with the calibration and MAC address filled in when known.
+ */
+export async function add_trusted_device(device: TrustedDevice): Promise<TrustedDevice> {
+    return await invoke<TrustedDevice>('plugin:bluetooth|add_trusted_device', {
+        device,
+    })
+}
+
+export async function remove_trusted_device(identifier: string): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|remove_trusted_device', {
+        identifier,
+    }).then((r) => r.success)
+}
+
+export async function list_trusted_devices(): Promise<TrustedDevice[]> {
+    return await invoke<TrustedDevice[]>('plugin:bluetooth|list_trusted_devices')
+}
+
+export async function set_presence_policy(policy: PresencePolicy): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|set_presence_policy', {
+        policy,
+    }).then((r) => r.success)
+}
+
+export async function get_presence(): Promise<AggregatePresence> {
+    return await invoke<AggregatePresence>('plugin:bluetooth|get_presence')
+}
+
+export interface RecordingSummary {
+    path: string
+    events: number
+    /** Seconds between the start and the end of the recording. */
+    duration: number
+}
+
+/**
+ * Record the events reported by the bluetooth stack to the JSONL file `name`, a new file by
+ * default, in the recordings directory of the app data dir. Resolves with the path recorded to.
+ * Not in the default permissions.
+ */
+export async function start_recording(name?: string): Promise<string> {
+    return await invoke<string>('plugin:bluetooth|start_recording', {
+        name,
+    })
+}
+
+export async function stop_recording(): Promise<RecordingSummary | null> {
+    return await invoke<RecordingSummary | null>('plugin:bluetooth|stop_recording')
+}
+
+/**
+ * Feed the recording `name` of the recordings directory back through the plugin, `speed` times
+ * faster than recorded (1 by default, 0 for no pauses). Resolves with the number of events
+ * replayed once done. Not in the default permissions.
+ */
+export async function replay_recording(name: string, speed?: number): Promise<number> {
+    return await invoke<number>('plugin:bluetooth|replay_recording', {
+        name,
+        speed,
+    })
+}
+
+export interface CharacteristicProperties {
+    broadcast: boolean
+    read: boolean
+    writeWithoutResponse: boolean
+    write: boolean
+    notify: boolean
+    indicate: boolean
+    authenticatedSignedWrites: boolean
+    extendedProperties: boolean
+}
+
+export interface GattDescriptor {
+    uuid: string
+    name: string | null
+}
+
+export interface GattCharacteristic {
+    uuid: string
+    name: string | null
+    properties: CharacteristicProperties
+    descriptors: GattDescriptor[]
+}
+
+export interface GattService {
+    uuid: string
+    name: string | null
+    primary: boolean
+    characteristics: GattCharacteristic[]
+}
+
+/**
+ * Services of a connected device, their characteristics are left empty.
+ */
+export async function discover_services(identifier: string): Promise<GattService[]> {
+    return await invoke<GattService[]>('plugin:bluetooth|discover_services', {
+        identifier,
+    })
+}
+
+/**
+ * Characteristics and descriptors of a service, discover the services first.
+ */
+export async function discover_characteristics(identifier: string, service: string): Promise<GattCharacteristic[]> {
+    return await invoke<GattCharacteristic[]>('plugin:bluetooth|discover_characteristics', {
+        identifier,
+        service,
+    })
+}
+
+/**
+ * How characteristic values are passed, `bytes` is an array of numbers, the others a string.
+ */
+export type ValueEncoding = 'bytes' | 'hex' | 'base64'
+
+export async function read_characteristic(identifier: string, service: string, characteristic: string): Promise<number[]>
+export async function read_characteristic(identifier: string, service: string, characteristic: string, encoding: 'bytes'): Promise<number[]>
+export async function read_characteristic(identifier: string, service: string, characteristic: string, encoding: 'hex' | 'base64'): Promise<string>
+export async function read_characteristic(identifier: string, service: string, characteristic: string, encoding?: ValueEncoding): Promise<number[] | string> {
+    return await invoke<number[] | string>('plugin:bluetooth|read_characteristic', {
+        identifier,
+        service,
+        characteristic,
+        encoding,
+    })
+}
+
+/**
+ * Write a characteristic, a string `value` is decoded with `encoding`. Writes wait for the
+ * acknowledgement of the device unless `withResponse` is false.
+ */
+export async function write_characteristic(
+    identifier: string,
+    service: string,
+    characteristic: string,
+    value: number[] | Uint8Array | string,
+    encoding?: ValueEncoding,
+    withResponse: boolean = true,
+): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|write_characteristic', {
+        identifier,
+        service,
+        characteristic,
+        value: value instanceof Uint8Array ? Array.from(value) : value,
+        encoding,
+        withResponse,
+    }).then((r) => r.success)
+}
+
+/**
+ * Enable the notifications of a characteristic, `onValue` receives its values until
+ * `unsubscribe` or `disconnect_device`. The subscription is restored after a lost connection.
+ */
+export async function subscribe(
+    identifier: string,
+    service: string,
+    characteristic: string,
+    onValue: (notification: Notification<number[] | string>) => void,
+    encoding?: ValueEncoding,
+): Promise<boolean> {
+    const channel = new Channel<Notification<number[] | string>>()
+    channel.onmessage = onValue
+    return await invoke<{ success: boolean }>('plugin:bluetooth|subscribe', {
+        identifier,
+        service,
+        characteristic,
+        encoding,
+        channel,
+    }).then((r) => r.success)
+}
+
+export async function unsubscribe(identifier: string, service: string, characteristic: string): Promise<boolean> {
+    return await invoke<{ success: boolean }>('plugin:bluetooth|unsubscribe', {
+        identifier,
+        service,
+        characteristic,
+    }).then((r) => r.success)
+}
+
+export interface DateTime {
+    year: number
+    month: number
+    day: number
+    hours: number
+    minutes: number
+    seconds: number
+}
+
+export interface HeartRateMeasurement {
+    bpm: number
+    sensorContact: boolean | null
+    energyExpended: number | null
+    /** Milliseconds. */
+    rrIntervals: number[]
+}
+
+export type TemperatureType =
+    | 'armpit' | 'body' | 'ear' | 'finger' | 'gastroIntestinalTract' | 'mouth' | 'rectum' | 'toe' | 'tympanum'
+    | { other: number }
+
+export interface TemperatureMeasurement {
+    temperature: number | null
+    unit: 'celsius' | 'fahrenheit'
+    timestamp: DateTime | null
+    temperatureType: TemperatureType | null
+}
+
+export interface WeightMeasurement {
+    weight: number | null
+    unit: 'kilogram' | 'pound'
+    timestamp: DateTime | null
+    userId: number | null
+    bmi: number | null
+    /** Meters for kilograms, inches for pounds. */
+    height: number | null
+}
+
+export interface RevolutionData {
+    revolutions: number
+    /** Seconds. */
+    lastEventTime: number
+}
+
+export interface Extremes {
+    max: number
+    min: number
+}
+
+export interface CyclingPowerMeasurement {
+    instantaneousPower: number
+    pedalPowerBalance: number | null
+    pedalPowerBalanceLeft: boolean
+    accumulatedTorque: number | null
+    torqueFromCrank: boolean
+    wheelRevolutions: RevolutionData | null
+    crankRevolutions: RevolutionData | null
+    extremeForce: Extremes | null
+    extremeTorque: Extremes | null
+    extremeAngles: Extremes | null
+    topDeadSpotAngle: number | null
+    bottomDeadSpotAngle: number | null
+    accumulatedEnergy: number | null
+    offsetCompensation: boolean
+}
+
+export interface RscMeasurement {
+    /** Meters per second. */
+    speed: number
+    cadence: number
+    strideLength: number | null
+    totalDistance: number | null
+    running: boolean
+}
+
+export interface CurrentTime {
+    dateTime: DateTime
+    dayOfWeek: number | null
+    /** Seconds. */
+    fraction: number
+    manualUpdate: boolean
+    externalReferenceUpdate: boolean
+    timeZoneChange: boolean
+    dstChange: boolean
+}
+
+export type ProfileValue =
+    | { type: 'heartRate', value: HeartRateMeasurement }
+    | { type: 'batteryLevel', value: number }
+    | { type: 'temperature', value: TemperatureMeasurement }
+    | { type: 'weight', value: WeightMeasurement }
+    | { type: 'cyclingPower', value: CyclingPowerMeasurement }
+    | { type: 'runningSpeedCadence', value: RscMeasurement }
+    | { type: 'currentTime', value: CurrentTime }
+
+/**
+ * Read a standard characteristic (2A19, 2A1C, 2A2B, 2A37, 2A53, 2A63, 2A9D) and decode it,
+ * `service` defaults to the service defining the characteristic.
+ */
+export async function read_profile(identifier: string, characteristic: string, service?: string): Promise<ProfileValue> {
+    return await invoke<ProfileValue>('plugin:bluetooth|read_profile', {
+        identifier,
+        service,
+        characteristic,
+    })
+}
+
+export interface PnpId {
+    vendorIdSource: 'bluetooth' | 'usb' | { other: number }
+    vendorId: number
+    productId: number
+    /** Binary coded decimal `0xJJMN` for version JJ.M.N. */
+    productVersion: number
+}
+
+export interface SystemId {
+    manufacturerIdentifier: string
+    organizationallyUniqueIdentifier: string
+}
+
+export interface DeviceInformation {
+    manufacturerName: string | null
+    modelNumber: string | null
+    serialNumber: string | null
+    hardwareRevision: string | null
+    firmwareRevision: string | null
+    softwareRevision: string | null
+    systemId: SystemId | null
+    pnpId: PnpId | null
+}
+
+/**
+ * Read the Device Information service of a connected device, the result is also kept in its
+ * `DeviceSnapshot`. The characteristics that can't be read are `null`, it fails only when the
+ * device lacks the service.
+ */
+export async function read_device_info(identifier: string): Promise<DeviceInformation> {
+    return await invoke<DeviceInformation>('plugin:bluetooth|read_device_info', {
+        identifier,
+    })
+}
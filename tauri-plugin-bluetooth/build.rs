@@ -1,51 +1,108 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::{env, fs};
 
 const LIB_NAME: &str = "CLibBluKitBridge";
 const SWIFT_CODE_DIR: &str = "native_bluetooth";
 const COMMANDS: &[&str] = &[
+    "echo",
+    "get_adapter_state",
+    "wait_for_powered_on",
     "start_scanning",
+    "scan_beacons",
     "stop_scanning",
+    "start_scan_session",
+    "stop_scan_session",
     "set_passive_mode",
     "connect_device",
     "disconnect_device",
+    "connect",
+    "disconnect",
+    "set_reconnect_policy",
     "read_rssi",
+    "discover_services",
+    "discover_characteristics",
+    "read_characteristic",
+    "read_profile",
+    "read_device_info",
+    "write_characteristic",
+    "subscribe",
+    "unsubscribe",
+    "set_rssi_filter",
+    "start_calibration",
+    "finish_calibration",
+    "cancel_calibration",
+    "get_calibration",
+    "list_devices",
+    "get_device",
+    "add_trusted_device",
+    "remove_trusted_device",
+    "list_trusted_devices",
+    "set_presence_policy",
+    "get_presence",
+    "start_recording",
+    "stop_recording",
+    "replay_recording",
 ];
+/// What to do when the Swift toolchain is missing.
+const NO_TOOLCHAIN: &str = "install Xcode or disable the `native-macos` feature";
 
 fn main() {
+    println!("cargo:rustc-check-cfg=cfg(native_bridge)");
+
+    // The Swift bridge is only built for macOS targets with the `native-macos` feature enabled,
+    // everywhere else the crate falls back to its stub implementation.
+    let native = env::var_os("CARGO_FEATURE_NATIVE_MACOS").is_some()
+        && env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos");
+    if native {
+        build_native_bridge();
+        println!("cargo:rustc-cfg=native_bridge");
+    }
+
+    tauri_plugin::Builder::new(COMMANDS)
+        .android_path("android")
+        .ios_path("ios")
+        .build();
+}
+
+fn build_native_bridge() {
     let static_lib_name = format!("lib{}.a", LIB_NAME);
     let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").unwrap());
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+    let package_path = manifest_dir.join(SWIFT_CODE_DIR);
+    let scratch_path = out_dir.join("swift-build");
+    let triple = swift_triple();
 
     // 1. 编译 Swift 库
     let swift_build = Command::new("swift")
-        .args(["build", "-c", "release", "--package-path", &SWIFT_CODE_DIR])
+        .args(swift_build_args(&package_path, &scratch_path, &triple))
         .status()
-        .expect("Failed to start Swift build");
+        .unwrap_or_else(|e| panic!("Failed to start Swift build ({e}), {NO_TOOLCHAIN}"));
 
     if !swift_build.success() {
         panic!("Swift build failed");
     }
 
     // 2. 定位生成的静态库
-    let swift_lib_path = manifest_dir
-        .join(&SWIFT_CODE_DIR)
-        .join(".build/release")
-        .join(&static_lib_name);
+    let bin_path = Command::new("swift")
+        .args(swift_build_args(&package_path, &scratch_path, &triple))
+        .arg("--show-bin-path")
+        .output()
+        .expect("Failed to locate the Swift build output")
+        .stdout;
+    let bin_path = String::from_utf8(bin_path).unwrap();
+    let swift_lib_path = PathBuf::from(bin_path.trim()).join(&static_lib_name);
 
     println!("cargo:warning=Swift static lib path: {:?}", swift_lib_path);
 
-    // 3. 复制到 target/debug
-    let target_dir = manifest_dir.join("target/debug");
-    let target_lib = target_dir.join(&static_lib_name);
-
-    fs::create_dir_all(&target_dir).unwrap();
+    // 3. 复制到 OUT_DIR
+    let target_lib = out_dir.join(&static_lib_name);
     fs::copy(&swift_lib_path, &target_lib).expect("Failed to copy the Swift library");
 
     let swift_path = Command::new("xcrun")
         .args(["--find", "swift"])
         .output()
-        .expect("Failed to find swift")
+        .unwrap_or_else(|e| panic!("Failed to find swift ({e}), {NO_TOOLCHAIN}"))
         .stdout;
     let swift_path = String::from_utf8(swift_path).unwrap();
     let swift_path = swift_path.trim();
@@ -67,13 +124,32 @@ fn main() {
     println!("cargo:rustc-link-lib=swiftCompatibilityDynamicReplacements");
     println!("cargo:rustc-link-lib=swiftCompatibilityPacks");
 
-    println!("cargo:rustc-link-search={}", target_dir.display());
+    println!("cargo:rustc-link-search=native={}", out_dir.display());
     println!("cargo:rustc-link-search={}", swift_lib_path.display());
     println!("cargo:rustc-link-lib=static={}", LIB_NAME);
-    println!("cargo:rerun-if-changed={}", &SWIFT_CODE_DIR);
+    println!("cargo:rerun-if-changed={}", SWIFT_CODE_DIR);
+}
 
-    tauri_plugin::Builder::new(COMMANDS)
-        .android_path("android")
-        .ios_path("ios")
-        .build();
+fn swift_build_args(package_path: &Path, scratch_path: &Path, triple: &str) -> Vec<String> {
+    vec![
+        "build".to_string(),
+        "-c".to_string(),
+        "release".to_string(),
+        "--package-path".to_string(),
+        package_path.display().to_string(),
+        "--scratch-path".to_string(),
+        scratch_path.display().to_string(),
+        "--triple".to_string(),
+        triple.to_string(),
+    ]
+}
+
+/// Map the cargo target onto the Swift triple, so cross builds between arm64 and x86_64 link
+/// the right architecture.
+fn swift_triple() -> String {
+    let arch = match env::var("CARGO_CFG_TARGET_ARCH").unwrap().as_str() {
+        "aarch64" => "arm64".to_string(),
+        arch => arch.to_string(),
+    };
+    format!("{}-apple-macosx12.0", arch)
 }
@@ -123,12 +123,93 @@ func getNameFromMAC(_ mac: String) -> String? {
     return nil
 }
 
+func jsonString(_ value: Any) -> String {
+    guard let data = try? JSONSerialization.data(withJSONObject: value) else { return "" }
+    return String(data: data, encoding: .utf8) ?? ""
+}
+
+extension Data {
+    var hexString: String {
+        map { String(format: "%02hhX", $0) }.joined()
+    }
+}
+
+/// 广播数据，多个广播包（含 scan response）合并后的结果，以 JSON 传给 Rust 侧的 `Advertisement`
+struct Advertisement: Encodable {
+    var localName: String?
+    /// company id（十进制字符串） → 去掉 company id 后的 payload（hex）
+    var manufacturerData: [String: String] = [:]
+    var serviceUuids: [String] = []
+    /// service UUID → service data（hex）
+    var serviceData: [String: String] = [:]
+    var txPowerLevel: Int?
+    var isConnectable: Bool?
+    var solicitedServiceUuids: [String] = []
+    var overflowServiceUuids: [String] = []
+
+    mutating func merge(_ advertisementData: [String: Any]) {
+        if let localName = advertisementData[CBAdvertisementDataLocalNameKey] as? String {
+            self.localName = localName
+        }
+        // 前两个字节是小端序的 Company Identifier
+        if let data = advertisementData[CBAdvertisementDataManufacturerDataKey] as? Data, data.count >= 2 {
+            let companyId = UInt16(data[data.startIndex]) | UInt16(data[data.startIndex + 1]) << 8
+            manufacturerData[String(companyId)] = data.dropFirst(2).hexString
+        }
+        if let uuids = advertisementData[CBAdvertisementDataServiceUUIDsKey] as? [CBUUID] {
+            Advertisement.join(&serviceUuids, uuids)
+        }
+        if let data = advertisementData[CBAdvertisementDataServiceDataKey] as? [CBUUID: Data] {
+            for (uuid, value) in data {
+                serviceData[uuid.uuidString] = value.hexString
+            }
+        }
+        if let txPower = advertisementData[CBAdvertisementDataTxPowerLevelKey] as? NSNumber {
+            txPowerLevel = txPower.intValue
+        }
+        if let connectable = advertisementData[CBAdvertisementDataIsConnectable] as? NSNumber {
+            isConnectable = connectable.boolValue
+        }
+        if let uuids = advertisementData[CBAdvertisementDataSolicitedServiceUUIDsKey] as? [CBUUID] {
+            Advertisement.join(&solicitedServiceUuids, uuids)
+        }
+        if let uuids = advertisementData[CBAdvertisementDataOverflowServiceUUIDsKey] as? [CBUUID] {
+            Advertisement.join(&overflowServiceUuids, uuids)
+        }
+    }
+
+    /// `companyId` 的 manufacturer payload（不含 company id）
+    func manufacturerPayload(_ companyId: UInt16) -> Data? {
+        guard let hex = manufacturerData[String(companyId)] else { return nil }
+        var bytes: [UInt8] = []
+        var index = hex.startIndex
+        while index < hex.endIndex {
+            let next = hex.index(index, offsetBy: 2, limitedBy: hex.endIndex) ?? hex.endIndex
+            guard let byte = UInt8(hex[index..<next], radix: 16) else { return nil }
+            bytes.append(byte)
+            index = next
+        }
+        return Data(bytes)
+    }
+
+    func json() -> String {
+        guard let data = try? JSONEncoder().encode(self) else { return "" }
+        return String(data: data, encoding: .utf8) ?? ""
+    }
+
+    private static func join(_ uuids: inout [String], _ other: [CBUUID]) {
+        for uuid in other where !uuids.contains(uuid.uuidString) {
+            uuids.append(uuid.uuidString)
+        }
+    }
+}
+
 class Device: NSObject {
     let uuid : UUID!
     var peripheral : CBPeripheral?
     var manufacture : String?
     var model : String?
-    var advData: Data?
+    var advertisement = Advertisement()
     var rssi: Int = 0
     var scanTimer: Timer?
     var macAddr: String?
@@ -174,14 +255,13 @@ class Device: NSObject {
         if let mod = model {
             return mod
         }
-        // iBeacon
-        if let adv = advData {
-            if adv.count >= 25 {
-                var iBeaconPrefix : [uint16] = [0x004c, 0x01502]
-                if adv[0...3] == Data(bytes: &iBeaconPrefix, count: 4) {
-                    let major = uint16(adv[20]) << 8 | uint16(adv[21])
-                    let minor = uint16(adv[22]) << 8 | uint16(adv[23])
-                    let tx = Int8(bitPattern: adv[24])
+        // iBeacon，Apple 的 payload 不含 company id
+        if let adv = advertisement.manufacturerPayload(0x004C) {
+            if adv.count >= 23 {
+                if adv[0] == 0x02 && adv[1] == 0x15 {
+                    let major = uint16(adv[18]) << 8 | uint16(adv[19])
+                    let minor = uint16(adv[20]) << 8 | uint16(adv[21])
+                    let tx = Int8(bitPattern: adv[22])
                     let distance = pow(10, Double(Int(tx) - rssi)/20.0)
                     let d = String(format:"%.1f", distance)
                     return "iBeacon [\(major), \(minor)] \(d)m"
@@ -207,9 +287,45 @@ protocol BLEDelegate {
     func newDevice(device: Device)
     func updateDevice(device: Device)
     func removeDevice(device: Device)
-    func updateRSSI(rssi: Int?, estimatedRSSI: Int?, active: Bool)
+    func updateRSSI(identifier: UUID, rssi: Int?, estimatedRSSI: Int?, active: Bool)
     func updatePresence(presence: Bool, reason: String)
     func bluetoothPowerWarn()
+    /// 蓝牙状态变化，state 为 CBManagerState 的 rawValue
+    func adapterState(state: Int32)
+    /// 连接建立、连接失败或断开，error 为失败或意外断开的原因
+    func connectionState(identifier: UUID, connected: Bool, error: String?)
+    /// GATT 发现的结果，service 为空表示服务发现，成功时 error 为 nil
+    func gattResult(identifier: UUID, service: String, error: String?, result: String)
+    /// 特征读取的结果，value 为 hex 编码
+    func characteristicRead(identifier: UUID, service: String, characteristic: String, error: String?, value: String)
+    /// 特征写入（with response）的结果
+    func characteristicWritten(identifier: UUID, service: String, characteristic: String, error: String?)
+    /// 开启或关闭通知的结果
+    func notificationState(identifier: UUID, service: String, characteristic: String, error: String?)
+    /// 已订阅特征的新值，value 为 hex 编码
+    func characteristicChanged(identifier: UUID, service: String, characteristic: String, value: String)
+}
+
+/// 桥接函数的状态码，和 Rust 侧 `backend/native.rs` 的 `STATUS_*` 保持一致
+let STATUS_STARTED: Int32 = 0
+let STATUS_NOT_CONNECTED: Int32 = 1
+let STATUS_SERVICE_NOT_FOUND: Int32 = 2
+let STATUS_CHARACTERISTIC_NOT_FOUND: Int32 = 3
+let STATUS_NOT_PERMITTED: Int32 = 4
+let STATUS_DONE: Int32 = 5
+let STATUS_POWERED_OFF: Int32 = 6
+let STATUS_UNAUTHORIZED: Int32 = 7
+let STATUS_UNSUPPORTED: Int32 = 8
+let STATUS_DEVICE_NOT_FOUND: Int32 = 9
+let STATUS_INVALID_IDENTIFIER: Int32 = 10
+
+/// 回调中的错误信息，ATT 错误带上错误码，格式为 "att:<code>:<description>"
+func errorMessage(_ error: Error?) -> String? {
+    guard let error = error else { return nil }
+    if let att = error as? CBATTError {
+        return "att:\(att.code.rawValue):\(error.localizedDescription)"
+    }
+    return error.localizedDescription
 }
 
 class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
@@ -236,6 +352,19 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
     var latestN: Int = 5
     var activeModeTimer : Timer? = nil
     var connectionTimer : Timer? = nil
+    // 等待结果的 GATT 发现
+    var pendingServiceDiscoveries: Set<UUID> = []
+    var pendingCharacteristicDiscoveries: [UUID: Set<CBUUID>] = [:]
+    var pendingDescriptorDiscoveries: [String: Int] = [:]
+    var pendingReads: Set<String> = []
+    // 扫描选项，只在 scanMode 下生效，监控设备时不过滤服务
+    var scanServices: [CBUUID]? = nil
+    var scanAllowDuplicates = true
+    var pendingNotifyStates: Set<String> = []
+    // 已开启通知的特征，连接断开后重连时重新开启，主动断开时清除
+    var subscriptions: [UUID: [CBUUID: Set<CBUUID>]] = [:]
+    // 应用主动连接的设备，读取完设备信息后不自动断开
+    var userConnections: Set<UUID> = []
     // RSSI 样本
     private var rssiSamples: [Int] = []
     private var filteredRSSI: Double?
@@ -249,14 +378,50 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
     
     func scanForPeripherals() {
         guard !centralMgr.isScanning else { return }
-        centralMgr.scanForPeripherals(withServices: nil, options: [CBCentralManagerScanOptionAllowDuplicatesKey: true])
+        let filtered = scanMode && monitoredUUID == nil
+        centralMgr.scanForPeripherals(withServices: filtered ? scanServices : nil,
+                                      options: [CBCentralManagerScanOptionAllowDuplicatesKey: filtered ? scanAllowDuplicates : true])
         print("Start scanning")
     }
     
+    /// 蓝牙不可用时的状态码，状态未知时（启动中）视为可用
+    func adapterStatus() -> Int32? {
+        switch centralMgr.state {
+        case .poweredOff: return STATUS_POWERED_OFF
+        case .unauthorized: return STATUS_UNAUTHORIZED
+        case .unsupported: return STATUS_UNSUPPORTED
+        default: return nil
+        }
+    }
+    
+    /// 查找已连接的设备，找不到或未连接时返回对应的状态码
+    func connectedPeripheral(identifier: UUID) -> (CBPeripheral?, Int32) {
+        if let status = adapterStatus() {
+            return (nil, status)
+        }
+        guard let peripheral = devices[identifier]?.peripheral else {
+            return (nil, STATUS_DEVICE_NOT_FOUND)
+        }
+        guard peripheral.state == .connected else {
+            return (nil, STATUS_NOT_CONNECTED)
+        }
+        return (peripheral, STATUS_STARTED)
+    }
+    
     /// 开始进行设备扫描，每次调用后，scanMode 设置为scanMode = true
-    func startScanning() {
+    func startScanning(services: [CBUUID] = [], allowDuplicates: Bool = true) -> Int32 {
+        if let status = adapterStatus() {
+            return status
+        }
         scanMode = true
+        scanServices = services.isEmpty ? nil : services
+        scanAllowDuplicates = allowDuplicates
+        // 重新开始扫描以应用新的选项
+        if centralMgr.isScanning {
+            centralMgr.stopScan()
+        }
         scanForPeripherals()
+        return STATUS_STARTED
     }
     
     /// 停止扫描
@@ -333,6 +498,7 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
         default:
             break
         }
+        delegate?.adapterState(state: Int32(central.state.rawValue))
     }
     
     func getEstimatedRSSI(rssi: Int) -> Int {
@@ -348,6 +514,11 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
     
     func updatePeripheral(_ peripheral: CBPeripheral, _ rssi: NSNumber) {
         print("peripheral: \(peripheral.identifier) \(peripheral.state) \(rssi)")
+        let value = rssi.intValue > 0 ? 0 : rssi.intValue
+        delegate?.updateRSSI(identifier: peripheral.identifier,
+                             rssi: value,
+                             estimatedRSSI: getEstimatedRSSI(rssi: value),
+                             active: !passiveMode)
     }
 
     func connectMonitoredPeripheral() {
@@ -370,53 +541,165 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
         RunLoop.main.add(connectionTimer!, forMode: .common)
     }
     
-    func connectDevice(identifier: UUID) -> Bool {
+    func connectDevice(identifier: UUID) -> Int32 {
+        if let status = adapterStatus() {
+            return status
+        }
         guard let device = devices[identifier] else {
             print("Device not found.")
-            return false
+            return STATUS_DEVICE_NOT_FOUND
         }
         guard let peripheral = device.peripheral else {
             print("Device peripheral invalidate.")
-            return false
+            return STATUS_DEVICE_NOT_FOUND
         }
         
         // Idk why but this works like a charm when 'didConnect' won't get called.
         // However, this generates warnings in the log.
         peripheral.readRSSI()
-        guard peripheral.state == .disconnected else {
-            print("Peripheral state is not disconnected.")
-            return false
+        userConnections.insert(identifier)
+        switch peripheral.state {
+        case .connected:
+            delegate?.connectionState(identifier: identifier, connected: true, error: nil)
+            return STATUS_DONE
+        case .connecting:
+            return STATUS_STARTED
+        default:
+            centralMgr.connect(peripheral, options: nil)
+            return STATUS_STARTED
         }
-        centralMgr.connect(peripheral, options: nil)
-        print("Peripheral connected.")
-        return true
     }
     
-    func disconnectDevice(identifier: UUID) -> Bool {
-        guard let device = devices[identifier] else {
-            print("Device not found.")
-            return false
-        }
-        guard let peripheral = device.peripheral else {
-            print("Device peripheral invalidate.")
-            return false
-        }
-        guard peripheral.state == .connected else {
-            print("Peripheral state is not connected.")
-            return false
+    func disconnectDevice(identifier: UUID) -> Int32 {
+        let (connected, status) = connectedPeripheral(identifier: identifier)
+        // 正在连接的设备也可以取消
+        let connecting = devices[identifier]?.peripheral.flatMap { $0.state == .connecting ? $0 : nil }
+        guard let peripheral = connected ?? connecting else {
+            print("Peripheral is not connected.")
+            return status
         }
+        userConnections.remove(identifier)
+        subscriptions.removeValue(forKey: identifier)
         centralMgr.cancelPeripheralConnection(peripheral)
         print("Peripheral disconnected.")
-        return true
+        return STATUS_STARTED
+    }
+    
+    /// 发现已连接设备的所有服务，结果通过 delegate 的 gattResult 返回
+    func discoverServices(identifier: UUID) -> Int32 {
+        let (peripheral, status) = connectedPeripheral(identifier: identifier)
+        guard let peripheral = peripheral else {
+            print("Peripheral is not connected.")
+            return status
+        }
+        peripheral.delegate = self
+        pendingServiceDiscoveries.insert(identifier)
+        peripheral.discoverServices(nil)
+        return STATUS_STARTED
     }
     
-    func readRssi(identifier: UUID) -> Void {
+    /// 发现某个已发现服务的特征及其描述符，结果通过 delegate 的 gattResult 返回
+    func discoverCharacteristics(identifier: UUID, service serviceUUID: CBUUID) -> Int32 {
+        let (peripheral, status) = connectedPeripheral(identifier: identifier)
+        guard let peripheral = peripheral else {
+            print("Peripheral is not connected.")
+            return status
+        }
+        guard let service = peripheral.services?.first(where: { $0.uuid == serviceUUID }) else {
+            print("Service \(serviceUUID) not discovered.")
+            return STATUS_SERVICE_NOT_FOUND
+        }
+        peripheral.delegate = self
+        pendingCharacteristicDiscoveries[identifier, default: []].insert(serviceUUID)
+        peripheral.discoverCharacteristics(nil, for: service)
+        return STATUS_STARTED
+    }
+    
+    func descriptorDiscoveryKey(_ peripheral: CBPeripheral, _ service: CBService) -> String {
+        return "\(peripheral.identifier.uuidString)/\(service.uuid.uuidString)"
+    }
+    
+    func finishCharacteristicDiscovery(_ peripheral: CBPeripheral, _ service: CBService, error: Error?) {
+        pendingCharacteristicDiscoveries[peripheral.identifier]?.remove(service.uuid)
+        pendingDescriptorDiscoveries.removeValue(forKey: descriptorDiscoveryKey(peripheral, service))
+        if let error = error {
+            delegate?.gattResult(identifier: peripheral.identifier, service: service.uuid.uuidString, error: errorMessage(error), result: "")
+            return
+        }
+        let characteristics: [[String: Any]] = (service.characteristics ?? []).map { chara in
+            [
+                "uuid": chara.uuid.uuidString,
+                "properties": chara.properties.rawValue,
+                "descriptors": (chara.descriptors ?? []).map { $0.uuid.uuidString },
+            ]
+        }
+        delegate?.gattResult(identifier: peripheral.identifier, service: service.uuid.uuidString, error: nil, result: jsonString(characteristics))
+    }
+    
+    func characteristicKey(_ peripheral: CBPeripheral, _ service: CBUUID, _ characteristic: CBUUID) -> String {
+        return "\(peripheral.identifier.uuidString)/\(service.uuidString)/\(characteristic.uuidString)"
+    }
+    
+    /// 查找已发现的特征，找不到时返回对应的状态码
+    func findCharacteristic(identifier: UUID, service: CBUUID, characteristic: CBUUID) -> (CBPeripheral?, CBCharacteristic?, Int32) {
+        let (peripheral, status) = connectedPeripheral(identifier: identifier)
+        guard let peripheral = peripheral else {
+            return (nil, nil, status)
+        }
+        guard let cbService = peripheral.services?.first(where: { $0.uuid == service }) else {
+            return (peripheral, nil, STATUS_SERVICE_NOT_FOUND)
+        }
+        guard let chara = cbService.characteristics?.first(where: { $0.uuid == characteristic }) else {
+            return (peripheral, nil, STATUS_CHARACTERISTIC_NOT_FOUND)
+        }
+        return (peripheral, chara, STATUS_STARTED)
+    }
+    
+    /// 读取特征值，结果通过 delegate 的 characteristicRead 返回
+    func readCharacteristic(identifier: UUID, service: CBUUID, characteristic: CBUUID) -> Int32 {
+        let (peripheral, chara, status) = findCharacteristic(identifier: identifier, service: service, characteristic: characteristic)
+        guard let peripheral = peripheral, let chara = chara else { return status }
+        guard chara.properties.contains(.read) else { return STATUS_NOT_PERMITTED }
+        peripheral.delegate = self
+        pendingReads.insert(characteristicKey(peripheral, service, characteristic))
+        peripheral.readValue(for: chara)
+        return STATUS_STARTED
+    }
+    
+    /// 写入特征值，with response 时结果通过 delegate 的 characteristicWritten 返回
+    func writeCharacteristic(identifier: UUID, service: CBUUID, characteristic: CBUUID, value: Data, withResponse: Bool) -> Int32 {
+        let (peripheral, chara, status) = findCharacteristic(identifier: identifier, service: service, characteristic: characteristic)
+        guard let peripheral = peripheral, let chara = chara else { return status }
+        let property: CBCharacteristicProperties = withResponse ? .write : .writeWithoutResponse
+        guard chara.properties.contains(property) else { return STATUS_NOT_PERMITTED }
+        peripheral.delegate = self
+        peripheral.writeValue(value, for: chara, type: withResponse ? .withResponse : .withoutResponse)
+        return withResponse ? STATUS_STARTED : STATUS_DONE
+    }
+    
+    /// 开启或关闭特征的通知，结果通过 delegate 的 notificationState 返回
+    func setNotify(identifier: UUID, service: CBUUID, characteristic: CBUUID, enabled: Bool) -> Int32 {
+        let (peripheral, chara, status) = findCharacteristic(identifier: identifier, service: service, characteristic: characteristic)
+        guard let peripheral = peripheral, let chara = chara else { return status }
+        guard chara.properties.contains(.notify) || chara.properties.contains(.indicate) else { return STATUS_NOT_PERMITTED }
+        if enabled {
+            subscriptions[identifier, default: [:]][service, default: []].insert(characteristic)
+        } else {
+            subscriptions[identifier]?[service]?.remove(characteristic)
+        }
+        peripheral.delegate = self
+        pendingNotifyStates.insert(characteristicKey(peripheral, service, characteristic))
+        peripheral.setNotifyValue(enabled, for: chara)
+        return STATUS_STARTED
+    }
+    
+    func readRssi(identifier: UUID) -> Int32 {
         print("readRssi \(identifier.uuidString)")
         
-        guard let device = devices[identifier] else {return}
-        guard let peripheral = device.peripheral else {return}
-        guard peripheral.state == .connected else {return}
+        let (peripheral, status) = connectedPeripheral(identifier: identifier)
+        guard let peripheral = peripheral else {return status}
         peripheral.readRSSI();
+        return STATUS_STARTED
     }
     
     //MARK:- CBCentralManagerDelegate start
@@ -454,9 +737,8 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
                 if (rssi >= thresholdRSSI) {
                     device.peripheral = peripheral
                     device.rssi = rssi
-                    device.advData = advertisementData["kCBAdvDataManufacturerData"] as? Data
+                    device.advertisement.merge(advertisementData)
                     
-                    let parsed = parseAdvertisementData(advertisementData)
                     devices[peripheral.identifier] = device
                     delegate?.newDevice(device: device)
                     
@@ -465,6 +747,7 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
             } else {
                 device = dev!
                 device.rssi = rssi
+                device.advertisement.merge(advertisementData)
                 delegate?.updateDevice(device: device)
                 
                 let desc = device.getDescription()
@@ -607,12 +890,41 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
         if scanMode {
             peripheral.discoverServices([DeviceInformation])
         }
+        // 重连后重新发现订阅的服务，在 didDiscoverCharacteristicsFor 中重新开启通知
+        if let subscribed = subscriptions[peripheral.identifier], !subscribed.isEmpty {
+            peripheral.discoverServices(Array(subscribed.keys))
+        }
         if !passiveMode {
             print("Connected")
             connectionTimer?.invalidate()
             connectionTimer = nil
             peripheral.readRSSI()
         }
+        // 连接状态变化通知 Rust 侧，用于重连策略
+        if let device = devices[peripheral.identifier] {
+            delegate?.updateDevice(device: device)
+        }
+        delegate?.connectionState(identifier: peripheral.identifier, connected: true, error: nil)
+    }
+    
+    func centralManager(_ central: CBCentralManager,
+                        didDisconnectPeripheral peripheral: CBPeripheral,
+                        error: Error?)
+    {
+        if let device = devices[peripheral.identifier] {
+            delegate?.updateDevice(device: device)
+        }
+        delegate?.connectionState(identifier: peripheral.identifier, connected: false, error: errorMessage(error))
+    }
+    
+    func centralManager(_ central: CBCentralManager,
+                        didFailToConnect peripheral: CBPeripheral,
+                        error: Error?)
+    {
+        if let device = devices[peripheral.identifier] {
+            delegate?.updateDevice(device: device)
+        }
+        delegate?.connectionState(identifier: peripheral.identifier, connected: false, error: errorMessage(error) ?? "connection failed")
     }
     
     // MARK: CBCentralManagerDelegate end
@@ -625,11 +937,27 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
     
     func peripheral(_ peripheral: CBPeripheral,
                     didDiscoverServices error: Error?) {
+        let requested = pendingServiceDiscoveries.remove(peripheral.identifier) != nil
+        if requested {
+            if let error = error {
+                delegate?.gattResult(identifier: peripheral.identifier, service: "", error: errorMessage(error), result: "")
+            } else {
+                let services: [[String: Any]] = (peripheral.services ?? []).map { service in
+                    ["uuid": service.uuid.uuidString, "primary": service.isPrimary]
+                }
+                delegate?.gattResult(identifier: peripheral.identifier, service: "", error: nil, result: jsonString(services))
+            }
+        }
         if let services = peripheral.services {
             for service in services {
-                if service.uuid == DeviceInformation {
+                // 应用发起的发现会自行发现特征，避免以部分特征回应它
+                if service.uuid == DeviceInformation && !requested {
                     peripheral.discoverCharacteristics([ManufacturerName, ModelName], for: service)
                 }
+                if let subscribed = subscriptions[peripheral.identifier]?[service.uuid], !subscribed.isEmpty,
+                   service.characteristics == nil {
+                    peripheral.discoverCharacteristics(Array(subscribed), for: service)
+                }
             }
         }
     }
@@ -643,14 +971,56 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
                 if chara.uuid == ManufacturerName || chara.uuid == ModelName {
                     peripheral.readValue(for:chara)
                 }
+                if subscriptions[peripheral.identifier]?[service.uuid]?.contains(chara.uuid) == true && !chara.isNotifying {
+                    peripheral.setNotifyValue(true, for: chara)
+                }
             }
         }
+        guard pendingCharacteristicDiscoveries[peripheral.identifier]?.contains(service.uuid) == true else { return }
+        let chars = service.characteristics ?? []
+        if error != nil || chars.isEmpty {
+            finishCharacteristicDiscovery(peripheral, service, error: error)
+            return
+        }
+        pendingDescriptorDiscoveries[descriptorDiscoveryKey(peripheral, service)] = chars.count
+        for chara in chars {
+            peripheral.discoverDescriptors(for: chara)
+        }
+    }
+    
+    func peripheral(_ peripheral: CBPeripheral,
+                    didDiscoverDescriptorsFor characteristic: CBCharacteristic,
+                    error: Error?)
+    {
+        guard let service = characteristic.service else { return }
+        let key = descriptorDiscoveryKey(peripheral, service)
+        guard let remaining = pendingDescriptorDiscoveries[key] else { return }
+        if remaining <= 1 || error != nil {
+            finishCharacteristicDiscovery(peripheral, service, error: error)
+        } else {
+            pendingDescriptorDiscoveries[key] = remaining - 1
+        }
     }
     
     func peripheral(_ peripheral: CBPeripheral,
                     didUpdateValueFor characteristic: CBCharacteristic,
                     error: Error?)
     {
+        if let service = characteristic.service {
+            let key = characteristicKey(peripheral, service.uuid, characteristic.uuid)
+            if pendingReads.remove(key) != nil {
+                delegate?.characteristicRead(identifier: peripheral.identifier,
+                                             service: service.uuid.uuidString,
+                                             characteristic: characteristic.uuid.uuidString,
+                                             error: errorMessage(error),
+                                             value: characteristic.value?.hexString ?? "")
+            } else if characteristic.isNotifying, error == nil, let value = characteristic.value {
+                delegate?.characteristicChanged(identifier: peripheral.identifier,
+                                                service: service.uuid.uuidString,
+                                                characteristic: characteristic.uuid.uuidString,
+                                                value: value.hexString)
+            }
+        }
         if let value = characteristic.value {
             let str: String? = String(data: value, encoding: .utf8)
             if let s = str {
@@ -663,7 +1033,8 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
                         device.model = s
                         delegate?.updateDevice(device: device)
                     }
-                    if device.model != nil && device.model != nil && device.peripheral != monitoredPeripheral {
+                    if device.model != nil && device.model != nil && device.peripheral != monitoredPeripheral
+                        && !userConnections.contains(peripheral.identifier) {
                         centralMgr.cancelPeripheralConnection(peripheral)
                     }
                 }
@@ -671,6 +1042,33 @@ class BLE: NSObject, CBCentralManagerDelegate, CBPeripheralDelegate {
         }
     }
     
+    func peripheral(_ peripheral: CBPeripheral,
+                    didWriteValueFor characteristic: CBCharacteristic,
+                    error: Error?)
+    {
+        guard let service = characteristic.service else { return }
+        delegate?.characteristicWritten(identifier: peripheral.identifier,
+                                        service: service.uuid.uuidString,
+                                        characteristic: characteristic.uuid.uuidString,
+                                        error: errorMessage(error))
+    }
+    
+    func peripheral(_ peripheral: CBPeripheral,
+                    didUpdateNotificationStateFor characteristic: CBCharacteristic,
+                    error: Error?)
+    {
+        guard let service = characteristic.service else { return }
+        if error != nil && !characteristic.isNotifying {
+            subscriptions[peripheral.identifier]?[service.uuid]?.remove(characteristic.uuid)
+        }
+        let key = characteristicKey(peripheral, service.uuid, characteristic.uuid)
+        guard pendingNotifyStates.remove(key) != nil else { return }
+        delegate?.notificationState(identifier: peripheral.identifier,
+                                    service: service.uuid.uuidString,
+                                    characteristic: characteristic.uuid.uuidString,
+                                    error: errorMessage(error))
+    }
+    
     func peripheral(_ peripheral: CBPeripheral,
                     didModifyServices invalidatedServices: [CBService])
     {
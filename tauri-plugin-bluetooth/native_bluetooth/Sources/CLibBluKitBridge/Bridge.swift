@@ -15,6 +15,13 @@ class BLEDelegateImpl: BLEDelegate {
     var onRssiUpdated: RssiUpdateCallback;
     var presenceUpdated: PresenceUpdateCallback;
     var blePowerWarn: BlePowerWarnCallback;
+    var onGattResult: GattResultCallback = { _,_,_,_ in };
+    var onCharacteristicRead: CharacteristicResultCallback = { _,_,_,_,_ in };
+    var onCharacteristicWrite: CharacteristicResultCallback = { _,_,_,_,_ in };
+    var onNotificationState: CharacteristicResultCallback = { _,_,_,_,_ in };
+    var onNotification: CharacteristicResultCallback = { _,_,_,_,_ in };
+    var onAdapterState: AdapterStateCallback = { _ in };
+    var onConnectionState: ConnectionStateCallback = { _,_,_ in };
     
     func newDevice(device: Device) {
         callDeviceCallback(device, callback: self.onDeviceNew)
@@ -28,10 +35,10 @@ class BLEDelegateImpl: BLEDelegate {
         callDeviceCallback(device, callback: self.onDeviceRemoved)
     }
     
-    func updateRSSI(rssi: Int?, estimatedRSSI: Int?, active: Bool) {
+    func updateRSSI(identifier: UUID, rssi: Int?, estimatedRSSI: Int?, active: Bool) {
         let rssiValue: Int32 = rssi.map { Int32($0) } ?? 0
         let estimatedRSSIValue: Int32 = estimatedRSSI.map { Int32($0) } ?? 0
-        self.onRssiUpdated(rssiValue, estimatedRSSIValue, active)
+        self.onRssiUpdated(identifier.uuidString, rssiValue, estimatedRSSIValue, active)
         
     }
     
@@ -43,10 +50,38 @@ class BLEDelegateImpl: BLEDelegate {
         
     }
     
+    func adapterState(state: Int32) {
+        self.onAdapterState(state)
+    }
+    
+    func connectionState(identifier: UUID, connected: Bool, error: String?) {
+        self.onConnectionState(identifier.uuidString, connected, error ?? "")
+    }
+    
+    func gattResult(identifier: UUID, service: String, error: String?, result: String) {
+        self.onGattResult(identifier.uuidString, service, error ?? "", result)
+    }
+    
+    func characteristicRead(identifier: UUID, service: String, characteristic: String, error: String?, value: String) {
+        self.onCharacteristicRead(identifier.uuidString, service, characteristic, error ?? "", value)
+    }
+    
+    func characteristicWritten(identifier: UUID, service: String, characteristic: String, error: String?) {
+        self.onCharacteristicWrite(identifier.uuidString, service, characteristic, error ?? "", "")
+    }
+    
+    func notificationState(identifier: UUID, service: String, characteristic: String, error: String?) {
+        self.onNotificationState(identifier.uuidString, service, characteristic, error ?? "", "")
+    }
+    
+    func characteristicChanged(identifier: UUID, service: String, characteristic: String, value: String) {
+        self.onNotification(identifier.uuidString, service, characteristic, "", value)
+    }
+    
     init(onDeviceNew: @escaping DeviceCallback = { _,_,_,_,_,__,_,_,_ in },
          onDeviceUpdate: @escaping DeviceCallback = { _,_,_,_,_,_,_,_,_ in },
          onDeviceRemoved: @escaping DeviceCallback = { _,_,_,_,_,_,_,_,_ in },
-         onRssiUpdated: @escaping RssiUpdateCallback = { _,_,_ in },
+         onRssiUpdated: @escaping RssiUpdateCallback = { _,_,_,_ in },
          presenceUpdated: @escaping PresenceUpdateCallback = { _,_ in },
          bluetoothPowerWarn: @escaping BlePowerWarnCallback = { }
     ) {
@@ -63,7 +98,7 @@ public typealias DeviceCallback = @convention(c) @Sendable (
     UnsafePointer<CChar>, // uuid
     UnsafePointer<CChar>, // manufacture
     UnsafePointer<CChar>, // model
-    Int64,                // advData (as length)
+    UnsafePointer<CChar>, // advertisement (JSON)
     Int32,                // rssi
     UnsafePointer<CChar>, // macAddr
     UnsafePointer<CChar>, // blName
@@ -72,6 +107,7 @@ public typealias DeviceCallback = @convention(c) @Sendable (
 ) -> Void;
 
 public typealias RssiUpdateCallback = @convention(c) @Sendable (
+    UnsafePointer<CChar>, // uuid
     Int32, // rssi
     Int32, // estimatedRSSI
     Bool
@@ -84,6 +120,29 @@ public typealias PresenceUpdateCallback = @convention(c) @Sendable (
 
 public typealias BlePowerWarnCallback = @convention(c) @Sendable () -> Void;
 
+public typealias AdapterStateCallback = @convention(c) @Sendable (Int32) -> Void;
+
+public typealias ConnectionStateCallback = @convention(c) @Sendable (
+    UnsafePointer<CChar>, // uuid
+    Bool,                 // connected
+    UnsafePointer<CChar>  // error, empty on success and for a requested disconnection
+) -> Void;
+
+public typealias GattResultCallback = @convention(c) @Sendable (
+    UnsafePointer<CChar>, // uuid
+    UnsafePointer<CChar>, // service uuid, empty for the service discovery
+    UnsafePointer<CChar>, // error, empty on success
+    UnsafePointer<CChar>  // result (JSON)
+) -> Void;
+
+public typealias CharacteristicResultCallback = @convention(c) @Sendable (
+    UnsafePointer<CChar>, // uuid
+    UnsafePointer<CChar>, // service uuid
+    UnsafePointer<CChar>, // characteristic uuid
+    UnsafePointer<CChar>, // error, empty on success
+    UnsafePointer<CChar>  // value (hex), empty for writes
+) -> Void;
+
 // MARK: - Bridge Function Definition
 
 @_cdecl("echo")
@@ -99,19 +158,21 @@ public func initialize() {
 }
 
 @_cdecl("start_scanning")
-public func startScanning() -> Bool {
-    DispatchQueue.main.async {
-        SharedBLE.shared.startScanning();
+public func startScanning(services: UnsafePointer<CChar>, allowDuplicates: Bool) -> Int32 {
+    // services 是 UUID 的 JSON 数组，Rust 侧已校验格式
+    let json = String(cString: services)
+    let uuids = (try? JSONSerialization.jsonObject(with: Data(json.utf8))) as? [String] ?? []
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.startScanning(services: uuids.map { CBUUID(string: $0) }, allowDuplicates: allowDuplicates)
     }
-    return true
 }
 
 @_cdecl("stop_scanning")
-public func stopScanning() -> Bool {
+public func stopScanning() -> Int32 {
     DispatchQueue.main.async{
         SharedBLE.shared.stopScanning();
     }
-    return true
+    return STATUS_DONE
 }
 
 @_cdecl("set_passive_mode")
@@ -122,16 +183,16 @@ public func setPassiveMode(mode: Bool) {
 }
 
 @_cdecl("connect_device")
-public func connectDevice(identifier: UnsafePointer<CChar>) -> Bool {
-    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return false}
+public func connectDevice(identifier: UnsafePointer<CChar>) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
     return DispatchQueue.main.sync {
         return SharedBLE.shared.connectDevice(identifier: uuid)
     }
 }
 
 @_cdecl("disconnect_device")
-public func disconnectDevice(identifier: UnsafePointer<CChar>) -> Bool {
-    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return false}
+public func disconnectDevice(identifier: UnsafePointer<CChar>) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
     
     return DispatchQueue.main.sync {
         return SharedBLE.shared.disconnectDevice(identifier: uuid)
@@ -139,10 +200,105 @@ public func disconnectDevice(identifier: UnsafePointer<CChar>) -> Bool {
 }
 
 @_cdecl("read_rssi")
-public func readRssi(identifier: UnsafePointer<CChar>) -> Void {
-    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return}
+public func readRssi(identifier: UnsafePointer<CChar>) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.readRssi(identifier: uuid)
+    }
+}
+
+@_cdecl("discover_services")
+public func discoverServices(identifier: UnsafePointer<CChar>) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.discoverServices(identifier: uuid)
+    }
+}
+
+@_cdecl("discover_characteristics")
+public func discoverCharacteristics(identifier: UnsafePointer<CChar>, service: UnsafePointer<CChar>) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
+    let serviceUUID = CBUUID(string: String(cString: service))
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.discoverCharacteristics(identifier: uuid, service: serviceUUID)
+    }
+}
+
+@_cdecl("read_characteristic")
+public func readCharacteristic(identifier: UnsafePointer<CChar>, service: UnsafePointer<CChar>, characteristic: UnsafePointer<CChar>) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
+    let serviceUUID = CBUUID(string: String(cString: service))
+    let characteristicUUID = CBUUID(string: String(cString: characteristic))
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.readCharacteristic(identifier: uuid, service: serviceUUID, characteristic: characteristicUUID)
+    }
+}
+
+@_cdecl("write_characteristic")
+public func writeCharacteristic(identifier: UnsafePointer<CChar>, service: UnsafePointer<CChar>, characteristic: UnsafePointer<CChar>,
+                                value: UnsafePointer<UInt8>, len: Int, withResponse: Bool) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
+    let serviceUUID = CBUUID(string: String(cString: service))
+    let characteristicUUID = CBUUID(string: String(cString: characteristic))
+    // 在返回前复制数据，调用方的缓冲区只在调用期间有效
+    let data = Data(bytes: value, count: len)
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.writeCharacteristic(identifier: uuid, service: serviceUUID, characteristic: characteristicUUID,
+                                                    value: data, withResponse: withResponse)
+    }
+}
+
+@_cdecl("set_notify")
+public func setNotify(identifier: UnsafePointer<CChar>, service: UnsafePointer<CChar>, characteristic: UnsafePointer<CChar>, enabled: Bool) -> Int32 {
+    guard let uuid = UUID(uuidString: String(cString: identifier)) else {return STATUS_INVALID_IDENTIFIER}
+    let serviceUUID = CBUUID(string: String(cString: service))
+    let characteristicUUID = CBUUID(string: String(cString: characteristic))
+    return DispatchQueue.main.sync {
+        return SharedBLE.shared.setNotify(identifier: uuid, service: serviceUUID, characteristic: characteristicUUID, enabled: enabled)
+    }
+}
+
+@_cdecl("set_gatt_delegate")
+public func setGattDelegate(onGattResult: GattResultCallback,
+                            onCharacteristicRead: CharacteristicResultCallback,
+                            onCharacteristicWrite: CharacteristicResultCallback
+) {
+    DispatchQueue.main.async {
+        SharedBLE.shareDelegate.onGattResult = onGattResult
+        SharedBLE.shareDelegate.onCharacteristicRead = onCharacteristicRead
+        SharedBLE.shareDelegate.onCharacteristicWrite = onCharacteristicWrite
+    }
+}
+
+@_cdecl("set_notification_delegate")
+public func setNotificationDelegate(onNotificationState: CharacteristicResultCallback,
+                                    onNotification: CharacteristicResultCallback
+) {
+    DispatchQueue.main.async {
+        SharedBLE.shareDelegate.onNotificationState = onNotificationState
+        SharedBLE.shareDelegate.onNotification = onNotification
+    }
+}
+
+@_cdecl("set_adapter_delegate")
+public func setAdapterDelegate(onAdapterState: AdapterStateCallback) {
+    DispatchQueue.main.async {
+        SharedBLE.shareDelegate.onAdapterState = onAdapterState
+    }
+}
+
+@_cdecl("adapter_state")
+public func adapterState() -> Int32 {
+    return DispatchQueue.main.sync {
+        // unknown until `initialize` created the central manager
+        return Int32(SharedBLE.shared.centralMgr?.state.rawValue ?? 0)
+    }
+}
+
+@_cdecl("set_connection_delegate")
+public func setConnectionDelegate(onConnectionState: ConnectionStateCallback) {
     DispatchQueue.main.async {
-        SharedBLE.shared.readRssi(identifier: uuid)
+        SharedBLE.shareDelegate.onConnectionState = onConnectionState
     }
 }
 
@@ -164,40 +320,41 @@ public func setDelegate(onDeviceNew: DeviceCallback,
     }
 }
 
+// The strings handed to the callbacks are only valid during the call, Rust copies them.
 func callDeviceCallback(_ device: Device, callback: DeviceCallback) {
     // 1. UUID
-    let uuidCStr = strdup(device.uuid.uuidString)
+    let uuid = device.uuid.uuidString
     
     // 2. manufacture
-    let manufactureCStr = strdup(device.manufacture ?? "")
+    let manufacture = device.manufacture ?? ""
     
     // 3. model
-    let modelCStr = strdup(device.model ?? "")
+    let model = device.model ?? ""
     
-    // 4. advData length
-    let advLength = Int64(device.advData?.count ?? 0)
+    // 4. advertisement
+    let advertisement = device.advertisement.json()
     
     // 5. rssi
     let rssi = Int32(device.rssi)
     
     // 6. macAddr
-    let macAddrCStr = strdup(device.macAddr ?? "")
+    let macAddr = device.macAddr ?? ""
     
     // 7. blName
     let blNameCStr = device.getDescription();
-    let name = strdup(device.peripheral?.name ?? "");
+    let name = device.peripheral?.name ?? "";
     let state = peripheralStateString(device.peripheral?.state);
     
     // 调用回调
     callback(
-        uuidCStr!,
-        manufactureCStr!,
-        modelCStr!,
-        advLength,
+        uuid,
+        manufacture,
+        model,
+        advertisement,
         rssi,
-        macAddrCStr!,
+        macAddr,
         blNameCStr,
-        name!,
+        name,
         String(describing: state)
     )
 }
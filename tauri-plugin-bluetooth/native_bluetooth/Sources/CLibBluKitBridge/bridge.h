@@ -8,26 +8,53 @@ const char* echo(const char* echo);
 // initialize the bluetooth, include delegate
 void initialize();
 
-// start scan
-bool start_scanning();
+// start scan, services is a JSON array of the service UUIDs to scan for, returns a status code
+int start_scanning(const char* services, bool allow_duplicates);
 
-// stop scan
-bool stop_scanning();
+// stop scan, returns a status code
+int stop_scanning();
+
+// the state of the adapter, a raw CBManagerState
+int adapter_state();
 
 // set passive mode
 void set_passive_mode(bool);
 
-// connect to device
-bool connect_device(const char* identifier);
+// connect to device, returns a status code
+int connect_device(const char* identifier);
+
+// disconnect device, returns a status code
+int disconnect_device(const char* identifier);
+
+// read rssi, returns a status code
+int read_rssi(const char* identifier);
+
+// discover the services of a connected device, returns a status code
+int discover_services(const char* identifier);
+
+// discover the characteristics of a discovered service, returns a status code
+int discover_characteristics(const char* identifier, const char* service);
 
-// disconnect device
-bool disconnect_device(const char* identifier);
+// read a characteristic, returns a status code
+int read_characteristic(const char* identifier, const char* service, const char* characteristic);
 
-// read rssi
-int readRssi(const char* identifier);
+// write a characteristic, returns a status code
+int write_characteristic(const char* identifier, const char* service, const char* characteristic,
+                         const unsigned char* value, unsigned long len, bool with_response);
+
+// enable or disable the notifications of a characteristic, returns a status code
+int set_notify(const char* identifier, const char* service, const char* characteristic, bool enabled);
 
 void set_delegate();
 
+void set_gatt_delegate();
+
+void set_notification_delegate();
+
+void set_adapter_delegate();
+
+void set_connection_delegate();
+
 #ifdef __cplusplus
 }
 #endif
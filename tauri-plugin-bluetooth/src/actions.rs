@@ -0,0 +1,603 @@
+use crate::clock::{check_seconds, seconds, Clock, SystemClock};
+use crate::events::BluetoothEvent;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::process::Command;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Runtime};
+
+/// Seconds a webhook may take to answer when the configuration sets no timeout.
+const DEFAULT_WEBHOOK_TIMEOUT: f64 = 5.0;
+
+/// A change of presence handed to the actions, also the payload of the event and webhook actions.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceChange {
+    pub presence: bool,
+    /// Why a device arrived or left, none for the aggregate presence.
+    pub reason: Option<String>,
+    /// The trusted device that arrived or left, or whose change flipped the aggregate presence.
+    pub identifier: Option<String>,
+}
+
+impl PresenceChange {
+    /// The change an event reports, for the presence events only.
+    pub fn from_event(event: &BluetoothEvent) -> Option<Self> {
+        match event {
+            BluetoothEvent::Presence(update) => Some(PresenceChange {
+                presence: update.presence,
+                reason: Some(update.reason.clone()),
+                identifier: update.identifier.clone(),
+            }),
+            BluetoothEvent::AggregatePresence(aggregate) => Some(PresenceChange {
+                presence: aggregate.presence,
+                reason: None,
+                identifier: aggregate.identifier.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+/// Something done when the user arrives or leaves, run off the event thread.
+pub trait PresenceAction: Send + Sync {
+    fn run(&self, change: &PresenceChange) -> Result<()>;
+}
+
+/// The transitions an action runs on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresenceEdge {
+    Arrive,
+    Leave,
+    #[default]
+    Both,
+}
+
+impl PresenceEdge {
+    pub fn matches(self, presence: bool) -> bool {
+        match self {
+            PresenceEdge::Arrive => presence,
+            PresenceEdge::Leave => !presence,
+            PresenceEdge::Both => true,
+        }
+    }
+}
+
+/// When an action runs.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ActionTrigger {
+    pub on: PresenceEdge,
+    /// The trusted device followed, the presence of the user when none: the aggregate presence
+    /// of the trusted devices, or the monitoring of the Swift side.
+    pub device: Option<String>,
+    /// Seconds the new presence must hold before the action runs, flipping back meanwhile
+    /// cancels it.
+    pub debounce: f64,
+    /// Seconds after a run during which the action is skipped.
+    pub cooldown: f64,
+}
+
+impl ActionTrigger {
+    /// Check the debounce and cooldown are usable durations.
+    pub fn validate(&self) -> Result<()> {
+        check_seconds("debounce", self.debounce)?;
+        check_seconds("cooldown", self.cooldown)
+    }
+
+    /// Whether the action follows the presence reported by `event`.
+    fn follows(&self, event: &BluetoothEvent) -> bool {
+        match (event, &self.device) {
+            (BluetoothEvent::Presence(update), Some(device)) => update
+                .identifier
+                .as_deref()
+                .is_some_and(|identifier| identifier == device),
+            (BluetoothEvent::Presence(update), None) => update.identifier.is_none(),
+            (BluetoothEvent::AggregatePresence(_), None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// The built-in actions, as written in the plugin configuration.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ActionKind {
+    /// Run through `sh -c`, or `cmd /C` on Windows, see [`ShellAction`].
+    Shell { command: String },
+    /// Emit a Tauri event with the [`PresenceChange`] as payload.
+    Event { name: String },
+    /// POST the [`PresenceChange`] as JSON, only `http://` URLs are supported.
+    Webhook {
+        url: String,
+        /// Seconds to connect and get an answer, more than 0, 5 by default.
+        #[serde(default)]
+        timeout: Option<f64>,
+    },
+}
+
+/// An entry of `presenceActions` in the plugin configuration, e.g.
+/// `{ "on": "leave", "debounce": 3, "shell": { "command": "pmset displaysleepnow" } }`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceActionConfig {
+    #[serde(flatten)]
+    pub trigger: ActionTrigger,
+    #[serde(flatten)]
+    pub action: ActionKind,
+}
+
+impl PresenceActionConfig {
+    pub fn build<R: Runtime>(&self, app: &AppHandle<R>) -> Result<Arc<dyn PresenceAction>> {
+        Ok(match &self.action {
+            ActionKind::Shell { command } => Arc::new(ShellAction::new(command.clone())),
+            ActionKind::Event { name } => Arc::new(EventAction::new(app.clone(), name.clone())),
+            ActionKind::Webhook { url, timeout } => {
+                let timeout = timeout.unwrap_or(DEFAULT_WEBHOOK_TIMEOUT);
+                check_seconds("timeout", timeout)?;
+                Arc::new(WebhookAction::new(url, seconds(timeout))?)
+            }
+        })
+    }
+}
+
+/// Runs a shell command without waiting for it. The change is passed in the environment:
+/// `BLUETOOTH_PRESENCE` is `1` or `0`, `BLUETOOTH_PRESENCE_REASON` and
+/// `BLUETOOTH_PRESENCE_DEVICE` are set when known.
+pub struct ShellAction {
+    command: String,
+}
+
+impl ShellAction {
+    pub fn new(command: String) -> Self {
+        ShellAction { command }
+    }
+}
+
+impl PresenceAction for ShellAction {
+    fn run(&self, change: &PresenceChange) -> Result<()> {
+        let mut command = if cfg!(windows) {
+            let mut command = Command::new("cmd");
+            command.arg("/C");
+            command
+        } else {
+            let mut command = Command::new("sh");
+            command.arg("-c");
+            command
+        };
+        command.arg(&self.command).env(
+            "BLUETOOTH_PRESENCE",
+            if change.presence { "1" } else { "0" },
+        );
+        if let Some(reason) = &change.reason {
+            command.env("BLUETOOTH_PRESENCE_REASON", reason);
+        }
+        if let Some(identifier) = &change.identifier {
+            command.env("BLUETOOTH_PRESENCE_DEVICE", identifier);
+        }
+        let mut child = command.spawn()?;
+        // reaped in the background, so a long running command doesn't hold the other actions.
+        std::thread::spawn(move || child.wait());
+        Ok(())
+    }
+}
+
+/// Emits a Tauri event with the change as payload.
+pub struct EventAction<R: Runtime> {
+    app: AppHandle<R>,
+    name: String,
+}
+
+impl<R: Runtime> EventAction<R> {
+    pub fn new(app: AppHandle<R>, name: String) -> Self {
+        EventAction { app, name }
+    }
+}
+
+impl<R: Runtime> PresenceAction for EventAction<R> {
+    fn run(&self, change: &PresenceChange) -> Result<()> {
+        self.app
+            .emit(&self.name, change)
+            .map_err(|e| Error::PresenceAction(e.to_string()))
+    }
+}
+
+/// POSTs the change as JSON to a plain HTTP endpoint, any 2xx answer counts as success.
+pub struct WebhookAction {
+    url: String,
+    host: String,
+    port: u16,
+    path: String,
+    timeout: Duration,
+}
+
+impl WebhookAction {
+    pub fn new(url: &str, timeout: Duration) -> Result<Self> {
+        if timeout.is_zero() {
+            return Err(Error::InvalidValue(format!(
+                "{url}: the timeout must be more than 0"
+            )));
+        }
+        let rest = url.strip_prefix("http://").ok_or_else(|| {
+            Error::Unsupported(format!("{url}: only http:// webhooks are supported"))
+        })?;
+        let (authority, path) = match rest.find('/') {
+            Some(index) => rest.split_at(index),
+            None => (rest, "/"),
+        };
+        let (host, port) = match authority.rsplit_once(':') {
+            Some((host, port)) if !authority.ends_with(']') => (
+                host,
+                port.parse()
+                    .map_err(|_| Error::InvalidValue(format!("invalid port in {url}")))?,
+            ),
+            _ => (authority, 80),
+        };
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.is_empty() {
+            return Err(Error::InvalidValue(format!("no host in {url}")));
+        }
+        Ok(WebhookAction {
+            url: url.to_string(),
+            host: host.to_string(),
+            port,
+            path: path.to_string(),
+            timeout,
+        })
+    }
+}
+
+impl PresenceAction for WebhookAction {
+    fn run(&self, change: &PresenceChange) -> Result<()> {
+        let body = serde_json::to_vec(change)?;
+        let address = (self.host.as_str(), self.port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| Error::PresenceAction(format!("{} does not resolve", self.host)))?;
+        let mut stream = TcpStream::connect_timeout(&address, self.timeout)?;
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        let host = if self.host.contains(':') {
+            format!("[{}]:{}", self.host, self.port)
+        } else {
+            format!("{}:{}", self.host, self.port)
+        };
+        write!(
+            stream,
+            "POST {} HTTP/1.1\r\nHost: {host}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n",
+            self.path,
+            body.len()
+        )?;
+        stream.write_all(&body)?;
+        let mut status = String::new();
+        BufReader::new(stream).read_line(&mut status)?;
+        match status
+            .split_whitespace()
+            .nth(1)
+            .and_then(|code| code.parse::<u16>().ok())
+        {
+            Some(200..=299) => Ok(()),
+            _ => Err(Error::PresenceAction(format!(
+                "{} answered {:?}",
+                self.url,
+                status.trim()
+            ))),
+        }
+    }
+}
+
+/// The debounce and cooldown of one action.
+///
+/// Feed it the presence it follows and call [`poll`](Self::poll) regularly: it returns the change
+/// to run the action with once the debounce elapsed, unless still cooling down.
+pub struct ActionSchedule<C: Clock = SystemClock> {
+    trigger: ActionTrigger,
+    clock: C,
+    presence: Option<bool>,
+    pending: Option<(Instant, PresenceChange)>,
+    last_run: Option<Instant>,
+}
+
+impl ActionSchedule<SystemClock> {
+    pub fn new(trigger: ActionTrigger) -> Self {
+        Self::with_clock(trigger, SystemClock)
+    }
+}
+
+impl<C: Clock> ActionSchedule<C> {
+    pub fn with_clock(trigger: ActionTrigger, clock: C) -> Self {
+        ActionSchedule {
+            trigger,
+            clock,
+            presence: None,
+            pending: None,
+            last_run: None,
+        }
+    }
+
+    pub fn trigger(&self) -> &ActionTrigger {
+        &self.trigger
+    }
+
+    /// Record the presence followed, repeating the current presence changes nothing.
+    pub fn on_change(&mut self, change: PresenceChange) {
+        if self.presence == Some(change.presence) {
+            return;
+        }
+        self.presence = Some(change.presence);
+        // flipping back within the debounce cancels the pending run.
+        self.pending = None;
+        if self.trigger.on.matches(change.presence) {
+            let deadline = self.clock.now() + seconds(self.trigger.debounce);
+            self.pending = Some((deadline, change));
+        }
+    }
+
+    /// The change to run the action with, if it is due.
+    pub fn poll(&mut self) -> Option<PresenceChange> {
+        let now = self.clock.now();
+        if self.pending.as_ref()?.0 > now {
+            return None;
+        }
+        let (_, change) = self.pending.take()?;
+        let cooling_down = self
+            .last_run
+            .is_some_and(|at| at + seconds(self.trigger.cooldown) > now);
+        if cooling_down {
+            return None;
+        }
+        self.last_run = Some(now);
+        Some(change)
+    }
+}
+
+/// The registered actions with their schedule.
+#[derive(Default)]
+pub struct PresenceActions {
+    actions: Mutex<Vec<(ActionSchedule, Arc<dyn PresenceAction>)>>,
+}
+
+impl PresenceActions {
+    pub fn add(&self, trigger: ActionTrigger, action: Arc<dyn PresenceAction>) {
+        self.lock().push((ActionSchedule::new(trigger), action));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// Hand a presence event to the actions following it.
+    pub fn observe(&self, event: &BluetoothEvent) {
+        let Some(change) = PresenceChange::from_event(event) else {
+            return;
+        };
+        for (schedule, _) in self.lock().iter_mut() {
+            if schedule.trigger().follows(event) {
+                schedule.on_change(change.clone());
+            }
+        }
+    }
+
+    /// The actions due, with the change to run them with.
+    pub fn poll(&self) -> Vec<(Arc<dyn PresenceAction>, PresenceChange)> {
+        self.lock()
+            .iter_mut()
+            .filter_map(|(schedule, action)| Some((action.clone(), schedule.poll()?)))
+            .collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<(ActionSchedule, Arc<dyn PresenceAction>)>> {
+        self.actions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use crate::events::PresenceUpdate;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread::JoinHandle;
+
+    fn change(presence: bool) -> PresenceChange {
+        PresenceChange {
+            presence,
+            reason: Some("rssi".into()),
+            identifier: Some("AA".into()),
+        }
+    }
+
+    fn trigger(on: PresenceEdge, debounce: f64, cooldown: f64) -> ActionTrigger {
+        ActionTrigger {
+            on,
+            device: None,
+            debounce,
+            cooldown,
+        }
+    }
+
+    /// The lines of the head and the body of a request.
+    type Request = (Vec<String>, Vec<u8>);
+
+    /// A one-shot HTTP server answering `status`, returning the request it read.
+    fn serve(status: &'static str) -> (u16, JoinHandle<Request>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let server = std::thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = Vec::new();
+            loop {
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                if line == "\r\n" {
+                    break;
+                }
+                head.push(line.trim_end().to_string());
+            }
+            let length = head
+                .iter()
+                .find_map(|line| line.strip_prefix("Content-Length: "))
+                .unwrap()
+                .parse()
+                .unwrap();
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status}\r\nContent-Length: 0\r\n\r\n"
+            )
+            .unwrap();
+            (head, body)
+        });
+        (port, server)
+    }
+
+    #[test]
+    fn webhooks_post_the_change_as_json() {
+        let (port, server) = serve("204 No Content");
+        let webhook = WebhookAction::new(
+            &format!("http://127.0.0.1:{port}/hooks/presence"),
+            seconds(5.0),
+        )
+        .unwrap();
+        webhook.run(&change(false)).unwrap();
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "POST /hooks/presence HTTP/1.1");
+        assert!(head.contains(&format!("Host: 127.0.0.1:{port}")));
+        assert!(head.contains(&"Content-Type: application/json".to_string()));
+        assert!(head.contains(&format!("Content-Length: {}", body.len())));
+        assert!(head.contains(&"Connection: close".to_string()));
+        let json: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({ "presence": false, "reason": "rssi", "identifier": "AA" })
+        );
+    }
+
+    #[test]
+    fn webhooks_fail_on_other_answers() {
+        let (port, server) = serve("500 Internal Server Error");
+        let webhook =
+            WebhookAction::new(&format!("http://127.0.0.1:{port}"), seconds(5.0)).unwrap();
+        let error = webhook.run(&change(true)).unwrap_err();
+        assert!(matches!(error, Error::PresenceAction(_)), "{error:?}");
+
+        let (head, body) = server.join().unwrap();
+        assert_eq!(head[0], "POST / HTTP/1.1");
+        assert_eq!(
+            serde_json::from_slice::<PresenceChange>(&body).unwrap(),
+            change(true)
+        );
+    }
+
+    #[test]
+    fn webhooks_reject_unusable_settings() {
+        let timeout = seconds(5.0);
+        assert!(matches!(
+            WebhookAction::new("http://127.0.0.1/", Duration::ZERO),
+            Err(Error::InvalidValue(_))
+        ));
+        assert!(matches!(
+            WebhookAction::new("https://example.com/", timeout),
+            Err(Error::Unsupported(_))
+        ));
+        assert!(matches!(
+            WebhookAction::new("http:///hook", timeout),
+            Err(Error::InvalidValue(_))
+        ));
+        assert!(matches!(
+            WebhookAction::new("http://example.com:port/", timeout),
+            Err(Error::InvalidValue(_))
+        ));
+
+        let webhook = WebhookAction::new("http://[::1]:8080/hook", timeout).unwrap();
+        assert_eq!((webhook.host.as_str(), webhook.port), ("::1", 8080));
+        assert_eq!(webhook.path, "/hook");
+    }
+
+    #[test]
+    fn triggers_reject_unusable_durations() {
+        assert!(trigger(PresenceEdge::Both, 3.0, 60.0).validate().is_ok());
+        assert!(trigger(PresenceEdge::Both, -1.0, 0.0).validate().is_err());
+        assert!(trigger(PresenceEdge::Both, f64::NAN, 0.0)
+            .validate()
+            .is_err());
+        assert!(trigger(PresenceEdge::Both, 0.0, f64::INFINITY)
+            .validate()
+            .is_err());
+    }
+
+    #[test]
+    fn the_debounce_delays_the_run_and_flipping_back_cancels_it() {
+        let clock = ManualClock::new();
+        let mut schedule =
+            ActionSchedule::with_clock(trigger(PresenceEdge::Leave, 3.0, 0.0), clock.clone());
+
+        schedule.on_change(change(true));
+        assert_eq!(schedule.poll(), None);
+
+        schedule.on_change(change(false));
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(schedule.poll(), None);
+        schedule.on_change(change(true));
+        schedule.on_change(change(false));
+        clock.advance(Duration::from_secs(2));
+        // the flip back restarted the debounce.
+        assert_eq!(schedule.poll(), None);
+        // repeating the presence does not.
+        schedule.on_change(change(false));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(schedule.poll(), Some(change(false)));
+        assert_eq!(schedule.poll(), None);
+    }
+
+    #[test]
+    fn the_cooldown_skips_runs() {
+        let clock = ManualClock::new();
+        let mut schedule =
+            ActionSchedule::with_clock(trigger(PresenceEdge::Both, 0.0, 10.0), clock.clone());
+
+        schedule.on_change(change(false));
+        assert_eq!(schedule.poll(), Some(change(false)));
+
+        clock.advance(Duration::from_secs(1));
+        schedule.on_change(change(true));
+        assert_eq!(schedule.poll(), None);
+        // the skipped run is dropped, not delayed.
+        clock.advance(Duration::from_secs(10));
+        assert_eq!(schedule.poll(), None);
+
+        schedule.on_change(change(false));
+        assert_eq!(schedule.poll(), Some(change(false)));
+    }
+
+    #[test]
+    fn triggers_follow_their_device_only() {
+        let update = |identifier: Option<&str>| {
+            BluetoothEvent::Presence(PresenceUpdate {
+                presence: true,
+                reason: "rssi".into(),
+                identifier: identifier.map(str::to_string),
+            })
+        };
+        let device = ActionTrigger {
+            device: Some("AA-BB".into()),
+            ..ActionTrigger::default()
+        };
+        assert!(device.follows(&update(Some("AA-BB"))));
+        assert!(!device.follows(&update(Some("CC"))));
+        assert!(!device.follows(&update(None)));
+
+        let user = ActionTrigger::default();
+        assert!(user.follows(&update(None)));
+        assert!(!user.follows(&update(Some("AA-BB"))));
+    }
+}
@@ -0,0 +1,73 @@
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// State of the bluetooth adapter, as CoreBluetooth reports it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AdapterState {
+    /// Not known yet, right after the plugin started.
+    #[default]
+    Unknown,
+    /// The connection to the system bluetooth service was lost, an update follows.
+    Resetting,
+    /// This machine has no bluetooth low energy.
+    Unsupported,
+    /// The app is not allowed to use bluetooth.
+    Unauthorized,
+    PoweredOff,
+    PoweredOn,
+}
+
+impl AdapterState {
+    /// Map a raw `CBManagerState`.
+    pub fn from_raw(raw: i32) -> Self {
+        match raw {
+            1 => AdapterState::Resetting,
+            2 => AdapterState::Unsupported,
+            3 => AdapterState::Unauthorized,
+            4 => AdapterState::PoweredOff,
+            5 => AdapterState::PoweredOn,
+            _ => AdapterState::Unknown,
+        }
+    }
+
+    pub fn is_powered_on(self) -> bool {
+        self == AdapterState::PoweredOn
+    }
+
+    /// Whether scans and connections can be issued, the error tells why not.
+    pub fn check(self) -> Result<()> {
+        match self {
+            AdapterState::PoweredOn => Ok(()),
+            AdapterState::PoweredOff => Err(Error::AdapterPoweredOff),
+            AdapterState::Unauthorized => Err(Error::Unauthorized),
+            AdapterState::Unsupported => Err(Error::Unsupported(
+                "this machine has no bluetooth low energy".to_string(),
+            )),
+            AdapterState::Unknown | AdapterState::Resetting => Err(Error::AdapterNotReady),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn raw_states_map_to_their_check() {
+        let states = [
+            (0, AdapterState::Unknown, Some("adapterNotReady")),
+            (1, AdapterState::Resetting, Some("adapterNotReady")),
+            (2, AdapterState::Unsupported, Some("unsupported")),
+            (3, AdapterState::Unauthorized, Some("unauthorized")),
+            (4, AdapterState::PoweredOff, Some("adapterPoweredOff")),
+            (5, AdapterState::PoweredOn, None),
+            (42, AdapterState::Unknown, Some("adapterNotReady")),
+        ];
+        for (raw, state, error) in states {
+            assert_eq!(AdapterState::from_raw(raw), state, "{raw}");
+            assert_eq!(state.is_powered_on(), error.is_none());
+            assert_eq!(state.check().err().map(|e| e.code()), error, "{state:?}");
+        }
+    }
+}
@@ -0,0 +1,153 @@
+use crate::gatt::{normalize_uuid, same_uuid};
+use crate::hex;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// The advertisement data of a peripheral, merged over the advertising and scan response packets.
+///
+/// Byte strings are hex encoded in JSON, which is also how the Swift side hands them over.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Advertisement {
+    pub local_name: Option<String>,
+    /// Manufacturer specific payload keyed by Bluetooth SIG company identifier, the payload
+    /// doesn't include the identifier.
+    #[serde(with = "hex::map")]
+    pub manufacturer_data: BTreeMap<u16, Vec<u8>>,
+    pub service_uuids: Vec<String>,
+    /// Service data keyed by the UUID of the service.
+    #[serde(with = "hex::map")]
+    pub service_data: BTreeMap<String, Vec<u8>>,
+    /// Transmit power in dBm.
+    pub tx_power_level: Option<i32>,
+    pub is_connectable: Option<bool>,
+    pub solicited_service_uuids: Vec<String>,
+    /// UUIDs that didn't fit in the advertising packet.
+    pub overflow_service_uuids: Vec<String>,
+}
+
+impl Advertisement {
+    pub fn is_empty(&self) -> bool {
+        self == &Advertisement::default()
+    }
+
+    /// The payload advertised for a company identifier.
+    pub fn manufacturer_payload(&self, company_id: u16) -> Option<&[u8]> {
+        self.manufacturer_data.get(&company_id).map(Vec::as_slice)
+    }
+
+    /// The service data of `uuid`, in any of its forms.
+    pub fn service_data(&self, uuid: &str) -> Option<&[u8]> {
+        self.service_data
+            .iter()
+            .find(|(key, _)| same_uuid(key, uuid))
+            .map(|(_, data)| data.as_slice())
+    }
+
+    pub fn has_service(&self, uuid: &str) -> bool {
+        self.service_uuids
+            .iter()
+            .chain(&self.overflow_service_uuids)
+            .any(|service| same_uuid(service, uuid))
+    }
+
+    /// Fold a later packet in, its fields take precedence and the UUID lists are joined. The
+    /// UUIDs are normalized, so a service advertised in two forms is kept once.
+    pub fn merge(&mut self, other: &Advertisement) {
+        if other.local_name.is_some() {
+            self.local_name.clone_from(&other.local_name);
+        }
+        self.manufacturer_data.extend(
+            other
+                .manufacturer_data
+                .iter()
+                .map(|(id, data)| (*id, data.clone())),
+        );
+        self.service_data = std::mem::take(&mut self.service_data)
+            .into_iter()
+            .chain(other.service_data.clone())
+            .map(|(uuid, data)| (normalize_uuid(&uuid), data))
+            .collect();
+        self.tx_power_level = other.tx_power_level.or(self.tx_power_level);
+        self.is_connectable = other.is_connectable.or(self.is_connectable);
+        join(&mut self.service_uuids, &other.service_uuids);
+        join(
+            &mut self.solicited_service_uuids,
+            &other.solicited_service_uuids,
+        );
+        join(
+            &mut self.overflow_service_uuids,
+            &other.overflow_service_uuids,
+        );
+    }
+}
+
+fn join(uuids: &mut Vec<String>, other: &[String]) {
+    for uuid in other {
+        if !uuids.iter().any(|known| same_uuid(known, uuid)) {
+            uuids.push(normalize_uuid(uuid));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service_data(entries: &[(&str, u8)]) -> BTreeMap<String, Vec<u8>> {
+        entries
+            .iter()
+            .map(|(uuid, byte)| (uuid.to_string(), vec![*byte]))
+            .collect()
+    }
+
+    #[test]
+    fn services_are_found_in_any_form() {
+        let advertisement = Advertisement {
+            service_uuids: vec!["180D".to_string()],
+            overflow_service_uuids: vec!["0000180f-0000-1000-8000-00805f9b34fb".to_string()],
+            service_data: service_data(&[("feaa", 1)]),
+            ..Default::default()
+        };
+        assert!(advertisement.has_service("0000180D"));
+        assert!(advertisement.has_service("180f"));
+        assert!(!advertisement.has_service("180A"));
+        assert_eq!(
+            advertisement.service_data("0000FEAA-0000-1000-8000-00805F9B34FB"),
+            Some([1].as_slice())
+        );
+        assert_eq!(advertisement.service_data("FEAB"), None);
+    }
+
+    #[test]
+    fn later_packets_take_precedence_and_keep_each_service_once() {
+        let mut advertisement = Advertisement {
+            local_name: Some("Lamp".to_string()),
+            manufacturer_data: BTreeMap::from([(0x004C, vec![1])]),
+            service_uuids: vec!["180D".to_string()],
+            service_data: service_data(&[("feaa", 1), ("180F", 1)]),
+            tx_power_level: Some(-4),
+            ..Default::default()
+        };
+        advertisement.merge(&Advertisement {
+            manufacturer_data: BTreeMap::from([(0x004C, vec![2])]),
+            service_uuids: vec!["0000180d".to_string(), "180a".to_string()],
+            service_data: service_data(&[("0000FEAA-0000-1000-8000-00805F9B34FB", 2)]),
+            is_connectable: Some(true),
+            ..Default::default()
+        });
+
+        assert_eq!(advertisement.local_name.as_deref(), Some("Lamp"));
+        assert_eq!(
+            advertisement.manufacturer_payload(0x004C),
+            Some([2].as_slice())
+        );
+        assert_eq!(advertisement.service_uuids, ["180D", "180A"]);
+        assert_eq!(
+            advertisement.service_data,
+            service_data(&[("FEAA", 2), ("180F", 1)])
+        );
+        assert_eq!(advertisement.tx_power_level, Some(-4));
+        assert_eq!(advertisement.is_connectable, Some(true));
+    }
+}
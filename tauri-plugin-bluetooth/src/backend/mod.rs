@@ -0,0 +1,95 @@
+use crate::adapter::AdapterState;
+use crate::gatt::{GattCharacteristic, GattService};
+use crate::ScanOptions;
+
+#[cfg(native_bridge)]
+mod native;
+mod simulated;
+
+pub use crate::desktop::Dispatcher;
+#[cfg(native_bridge)]
+pub use native::NativeBackend;
+pub use simulated::{SimulatedBackend, VirtualPeripheral};
+
+/// Backend used by [`init`](crate::init): CoreBluetooth when the Swift bridge is built with the
+/// `native-macos` feature, the simulator otherwise.
+#[cfg(native_bridge)]
+pub type DefaultBackend = NativeBackend;
+#[cfg(not(native_bridge))]
+pub type DefaultBackend = SimulatedBackend;
+
+/// The operations the plugin needs from a bluetooth stack.
+///
+/// Events produced by a backend (discoveries, RSSI readings, presence changes, ...) are handed to
+/// the `dispatch_*` methods of the plugin state, reached through the [`Dispatcher`] passed to
+/// `initialize`, which forward them to the [`BLEDelegate`].
+///
+/// [`BLEDelegate`]: crate::bridge::BLEDelegate
+pub trait BluetoothBackend: Send + Sync {
+    fn echo(&self, value: &str) -> String;
+
+    /// Prepare the backend and start delivering events to `dispatcher`.
+    fn initialize(&self, dispatcher: Dispatcher);
+
+    fn adapter_state(&self) -> AdapterState;
+
+    /// Start scanning for the devices advertising `options.services`, every device if empty.
+    /// The other criteria are checked by the plugin.
+    fn start_scanning(&self, options: &ScanOptions) -> crate::Result<()>;
+
+    fn stop_scanning(&self) -> crate::Result<()>;
+
+    fn set_passive_mode(&self, mode: bool);
+
+    /// Start connecting to a device, the connection is reported by a device update.
+    ///
+    /// The outcome is also handed to `dispatch_connection_state`, right away when the device is
+    /// already connected, as is the end of the connection.
+    fn connect_device(&self, identifier: &str) -> crate::Result<()>;
+
+    /// Close the connection to a device, or cancel the connection in progress.
+    fn disconnect_device(&self, identifier: &str) -> crate::Result<()>;
+
+    /// Request a reading, handed to `dispatch_rssi`.
+    fn read_rssi(&self, identifier: &str) -> crate::Result<()>;
+
+    /// Services of a connected peripheral, without their characteristics.
+    fn discover_services(&self, identifier: &str) -> crate::Result<Vec<GattService>>;
+
+    /// Characteristics of a discovered service, with their descriptors.
+    fn discover_characteristics(
+        &self,
+        identifier: &str,
+        service: &str,
+    ) -> crate::Result<Vec<GattCharacteristic>>;
+
+    fn read_characteristic(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+    ) -> crate::Result<Vec<u8>>;
+
+    /// Write a value, waiting for the peripheral to acknowledge it when `with_response` is set.
+    fn write_characteristic(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        value: &[u8],
+        with_response: bool,
+    ) -> crate::Result<()>;
+
+    /// Enable or disable the notifications of a characteristic.
+    ///
+    /// Enabled notifications survive a lost connection: the backend enables them again when the
+    /// peripheral reconnects, until they are disabled or the device is disconnected on purpose.
+    /// Values are handed to `dispatch_notification`.
+    fn set_notify(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        enabled: bool,
+    ) -> crate::Result<()>;
+}
@@ -0,0 +1,351 @@
+use crate::adapter::AdapterState;
+use crate::backend::BluetoothBackend;
+use crate::bridge;
+use crate::desktop::{
+    bluetooth_power_warn, gatt_request_key, on_adapter_state, on_characteristic_read,
+    on_characteristic_write, on_connection_state, on_device_new, on_device_removed,
+    on_device_update, on_gatt_result, on_notification, on_notification_state, on_rssi_updated,
+    presence_update, set_native_dispatcher, with_native_state, Dispatcher, GattOperation,
+};
+use crate::gatt::{self, GattCharacteristic, GattService, NativeCharacteristic, NativeService};
+use crate::hex;
+use crate::{Error, Result, ScanOptions};
+use std::ffi::{CStr, CString};
+use std::time::Duration;
+
+/// How long a GATT operation may take before giving up.
+const GATT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Status codes returned by the Swift side.
+const STATUS_STARTED: i32 = 0;
+const STATUS_NOT_CONNECTED: i32 = 1;
+const STATUS_SERVICE_NOT_FOUND: i32 = 2;
+const STATUS_CHARACTERISTIC_NOT_FOUND: i32 = 3;
+const STATUS_NOT_PERMITTED: i32 = 4;
+/// The operation completed right away, e.g. a write without response.
+const STATUS_DONE: i32 = 5;
+const STATUS_POWERED_OFF: i32 = 6;
+const STATUS_UNAUTHORIZED: i32 = 7;
+const STATUS_UNSUPPORTED: i32 = 8;
+const STATUS_DEVICE_NOT_FOUND: i32 = 9;
+const STATUS_INVALID_IDENTIFIER: i32 = 10;
+
+/// Prefix of the errors carrying an ATT error code, `att:<code>:<description>`.
+const ATT_ERROR_PREFIX: &str = "att:";
+
+/// Backend calling into the CoreBluetooth bridge compiled from `native_bluetooth`.
+#[derive(Debug, Default)]
+pub struct NativeBackend;
+
+impl NativeBackend {
+    pub fn new() -> Self {
+        NativeBackend
+    }
+}
+
+impl BluetoothBackend for NativeBackend {
+    fn echo(&self, value: &str) -> String {
+        let Ok(value) = CString::new(value) else {
+            return String::new();
+        };
+        unsafe {
+            let ret = bridge::echo(value.as_ptr());
+            CStr::from_ptr(ret).to_string_lossy().into_owned()
+        }
+    }
+
+    fn initialize(&self, dispatcher: Dispatcher) {
+        set_native_dispatcher(dispatcher);
+        unsafe {
+            bridge::set_delegate(
+                on_device_new,
+                on_device_update,
+                on_device_removed,
+                on_rssi_updated,
+                presence_update,
+                bluetooth_power_warn,
+            );
+            bridge::set_gatt_delegate(
+                on_gatt_result,
+                on_characteristic_read,
+                on_characteristic_write,
+            );
+            bridge::set_notification_delegate(on_notification_state, on_notification);
+            bridge::set_adapter_delegate(on_adapter_state);
+            bridge::set_connection_delegate(on_connection_state);
+            bridge::initialize();
+        }
+    }
+
+    fn adapter_state(&self) -> AdapterState {
+        AdapterState::from_raw(unsafe { bridge::adapter_state() })
+    }
+
+    fn start_scanning(&self, options: &ScanOptions) -> Result<()> {
+        // the UUIDs were validated by `ScanFilter::new`, CBUUID raises on malformed ones.
+        let services = c_string(serde_json::to_string(&options.services)?)?;
+        let status = unsafe { bridge::start_scanning(services.as_ptr(), options.allow_duplicates) };
+        check_status(status, "")
+    }
+
+    fn stop_scanning(&self) -> Result<()> {
+        check_status(unsafe { bridge::stop_scanning() }, "")
+    }
+
+    fn set_passive_mode(&self, mode: bool) {
+        unsafe { bridge::set_passive_mode(mode) }
+    }
+
+    fn connect_device(&self, identifier: &str) -> Result<()> {
+        let value = c_identifier(identifier)?;
+        check_status(
+            unsafe { bridge::connect_device(value.as_ptr()) },
+            identifier,
+        )
+    }
+
+    fn disconnect_device(&self, identifier: &str) -> Result<()> {
+        let value = c_identifier(identifier)?;
+        check_status(
+            unsafe { bridge::disconnect_device(value.as_ptr()) },
+            identifier,
+        )
+    }
+
+    fn read_rssi(&self, identifier: &str) -> Result<()> {
+        let value = c_identifier(identifier)?;
+        check_status(unsafe { bridge::read_rssi(value.as_ptr()) }, identifier)
+    }
+
+    fn discover_services(&self, identifier: &str) -> Result<Vec<GattService>> {
+        let value = c_identifier(identifier)?;
+        let key = gatt_request_key(GattOperation::Services, identifier, &[]);
+        let result = gatt_request(&key, identifier, || unsafe {
+            bridge::discover_services(value.as_ptr())
+        })?;
+        let services: Vec<NativeService> = serde_json::from_str(&result)?;
+        Ok(services.into_iter().map(GattService::from).collect())
+    }
+
+    fn discover_characteristics(
+        &self,
+        identifier: &str,
+        service: &str,
+    ) -> Result<Vec<GattCharacteristic>> {
+        let value = c_identifier(identifier)?;
+        let [service_c] = c_uuids([service])?;
+        let key = gatt_request_key(GattOperation::Characteristics, identifier, &[service]);
+        let result = gatt_request(&key, identifier, || unsafe {
+            bridge::discover_characteristics(value.as_ptr(), service_c.as_ptr())
+        })?;
+        let characteristics: Vec<NativeCharacteristic> = serde_json::from_str(&result)?;
+        Ok(characteristics
+            .into_iter()
+            .map(GattCharacteristic::from)
+            .collect())
+    }
+
+    fn read_characteristic(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+    ) -> Result<Vec<u8>> {
+        let value = c_identifier(identifier)?;
+        let [service_c, characteristic_c] = c_uuids([service, characteristic])?;
+        let key = gatt_request_key(GattOperation::Read, identifier, &[service, characteristic]);
+        let value = gatt_request(&key, identifier, || unsafe {
+            bridge::read_characteristic(
+                value.as_ptr(),
+                service_c.as_ptr(),
+                characteristic_c.as_ptr(),
+            )
+        })?;
+        hex::decode(&value).ok_or_else(|| Error::Gatt(format!("malformed value {value:?}")))
+    }
+
+    fn write_characteristic(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        value: &[u8],
+        with_response: bool,
+    ) -> Result<()> {
+        let identifier_c = c_identifier(identifier)?;
+        let [service_c, characteristic_c] = c_uuids([service, characteristic])?;
+        let key = gatt_request_key(GattOperation::Write, identifier, &[service, characteristic]);
+        gatt_request(&key, identifier, || unsafe {
+            bridge::write_characteristic(
+                identifier_c.as_ptr(),
+                service_c.as_ptr(),
+                characteristic_c.as_ptr(),
+                value.as_ptr(),
+                value.len(),
+                with_response,
+            )
+        })?;
+        Ok(())
+    }
+
+    fn set_notify(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        let identifier_c = c_identifier(identifier)?;
+        let [service_c, characteristic_c] = c_uuids([service, characteristic])?;
+        let key = gatt_request_key(
+            GattOperation::Notify,
+            identifier,
+            &[service, characteristic],
+        );
+        gatt_request(&key, identifier, || unsafe {
+            bridge::set_notify(
+                identifier_c.as_ptr(),
+                service_c.as_ptr(),
+                characteristic_c.as_ptr(),
+                enabled,
+            )
+        })?;
+        Ok(())
+    }
+}
+
+fn c_string(value: String) -> Result<CString> {
+    CString::new(value).map_err(|e| Error::InvalidValue(e.to_string()))
+}
+
+/// A peripheral identifier as a C string, interior NULs would cut it short.
+fn c_identifier(identifier: &str) -> Result<CString> {
+    CString::new(identifier).map_err(|_| Error::InvalidIdentifier(identifier.to_string()))
+}
+
+/// UUIDs as C strings, CBUUID raises on malformed strings so they must not reach the Swift side.
+fn c_uuids<const N: usize>(uuids: [&str; N]) -> Result<[CString; N]> {
+    if let Some(uuid) = uuids.iter().find(|uuid| !gatt::is_valid_uuid(uuid)) {
+        return Err(Error::InvalidValue(format!("invalid uuid {uuid}")));
+    }
+    // a valid UUID holds no NUL.
+    Ok(uuids.map(|uuid| CString::new(uuid).unwrap_or_default()))
+}
+
+/// Map a status code of the Swift side to the error it stands for.
+fn check_status(status: i32, identifier: &str) -> Result<()> {
+    match status {
+        STATUS_STARTED | STATUS_DONE => Ok(()),
+        STATUS_NOT_CONNECTED => Err(Error::NotConnected(identifier.to_string())),
+        STATUS_SERVICE_NOT_FOUND => Err(Error::Gatt(
+            "service not found, discover the services first".to_string(),
+        )),
+        STATUS_CHARACTERISTIC_NOT_FOUND => Err(Error::Gatt(
+            "characteristic not found, discover the characteristics first".to_string(),
+        )),
+        STATUS_NOT_PERMITTED => Err(Error::Unsupported(
+            "the characteristic doesn't support this operation".to_string(),
+        )),
+        STATUS_POWERED_OFF => Err(Error::AdapterPoweredOff),
+        STATUS_UNAUTHORIZED => Err(Error::Unauthorized),
+        STATUS_UNSUPPORTED => AdapterState::Unsupported.check(),
+        STATUS_DEVICE_NOT_FOUND => Err(Error::DeviceNotFound(identifier.to_string())),
+        STATUS_INVALID_IDENTIFIER => Err(Error::InvalidIdentifier(identifier.to_string())),
+        status => Err(Error::Gatt(format!("unexpected status {status}"))),
+    }
+}
+
+/// The error reported by a Swift callback, ATT errors keep their code.
+fn native_error(error: String) -> Error {
+    let code = error
+        .strip_prefix(ATT_ERROR_PREFIX)
+        .and_then(|rest| rest.split(':').next())
+        .and_then(|code| code.parse().ok());
+    match code {
+        Some(code) => Error::GattError(code),
+        None => Error::Gatt(error),
+    }
+}
+
+/// Start a GATT operation and wait for the callback answering it.
+///
+/// `start` returns one of the `STATUS_*` codes of the Swift side, with [`STATUS_DONE`] no
+/// answer is expected.
+fn gatt_request(key: &str, identifier: &str, start: impl FnOnce() -> i32) -> Result<String> {
+    let (token, receiver) = with_native_state(|state| state.gatt_requests.register(key))
+        .ok_or(Error::AdapterNotReady)?;
+    let status = start();
+    if status != STATUS_STARTED {
+        with_native_state(|state| state.gatt_requests.cancel(key, token));
+    }
+    check_status(status, identifier)?;
+    if status == STATUS_DONE {
+        return Ok(String::new());
+    }
+    match receiver.recv_timeout(GATT_TIMEOUT) {
+        Ok(Ok(result)) => Ok(result),
+        Ok(Err(error)) => Err(native_error(error)),
+        Err(_) => {
+            with_native_state(|state| state.gatt_requests.cancel(key, token));
+            Err(Error::Timeout(identifier.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn statuses_map_to_their_error() {
+        let code = |status| check_status(status, "AA").map_err(|error| error.code());
+        assert_eq!(code(STATUS_STARTED), Ok(()));
+        assert_eq!(code(STATUS_DONE), Ok(()));
+        let failures = [
+            (STATUS_NOT_CONNECTED, "notConnected"),
+            (STATUS_SERVICE_NOT_FOUND, "gatt"),
+            (STATUS_CHARACTERISTIC_NOT_FOUND, "gatt"),
+            (STATUS_NOT_PERMITTED, "unsupported"),
+            (STATUS_POWERED_OFF, "adapterPoweredOff"),
+            (STATUS_UNAUTHORIZED, "unauthorized"),
+            (STATUS_UNSUPPORTED, "unsupported"),
+            (STATUS_DEVICE_NOT_FOUND, "deviceNotFound"),
+            (STATUS_INVALID_IDENTIFIER, "invalidIdentifier"),
+            (42, "gatt"),
+        ];
+        for (status, expected) in failures {
+            assert_eq!(code(status), Err(expected), "status {status}");
+        }
+        assert_eq!(
+            check_status(STATUS_DEVICE_NOT_FOUND, "AA")
+                .unwrap_err()
+                .to_string(),
+            "device AA not found"
+        );
+    }
+
+    #[test]
+    fn att_errors_keep_their_code() {
+        assert!(matches!(
+            native_error("att:10:Attribute not found".to_string()),
+            Error::GattError(10)
+        ));
+        assert!(matches!(
+            native_error("att:3".to_string()),
+            Error::GattError(3)
+        ));
+        // not an ATT error, or a code out of range, keeps the description.
+        for error in [
+            "connection lost",
+            "att:",
+            "att:x:bad",
+            "att:300:big",
+            "ATT:1:no",
+        ] {
+            match native_error(error.to_string()) {
+                Error::Gatt(description) => assert_eq!(description, error),
+                other => panic!("{error} gave {other:?}"),
+            }
+        }
+    }
+}
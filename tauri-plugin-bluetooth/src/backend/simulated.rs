@@ -0,0 +1,814 @@
+use crate::adapter::AdapterState;
+use crate::advertisement::Advertisement;
+use crate::backend::{BluetoothBackend, Dispatcher};
+use crate::bridge::Device;
+use crate::desktop::State;
+use crate::gatt::{self, GattCharacteristic, GattService};
+use crate::{Error, Result, ScanOptions};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Number of samples used for the estimated RSSI, same as `latestN` on the Swift side.
+const ESTIMATE_WINDOW: usize = 5;
+
+/// A scripted peripheral living inside a [`SimulatedBackend`].
+#[derive(Debug, Clone)]
+pub struct VirtualPeripheral {
+    uuid: String,
+    manufacture: Option<String>,
+    model: Option<String>,
+    advertisement: Advertisement,
+    mac_addr: Option<String>,
+    bl_name: Option<String>,
+    name: Option<String>,
+    rssi_curve: Vec<i32>,
+    connectable: bool,
+    services: Vec<GattService>,
+    values: HashMap<String, Vec<u8>>,
+    read_errors: HashMap<String, String>,
+}
+
+impl VirtualPeripheral {
+    /// A peripheral reported as `uuid` in upper case, the form CoreBluetooth reports
+    /// identifiers in and the commands pass them in.
+    pub fn new(uuid: impl Into<String>) -> Self {
+        VirtualPeripheral {
+            uuid: uuid.into().to_ascii_uppercase(),
+            manufacture: None,
+            model: None,
+            advertisement: Advertisement::default(),
+            mac_addr: None,
+            bl_name: None,
+            name: None,
+            rssi_curve: vec![-60],
+            connectable: true,
+            services: vec![],
+            values: HashMap::new(),
+            read_errors: HashMap::new(),
+        }
+    }
+
+    pub fn manufacture(mut self, manufacture: impl Into<String>) -> Self {
+        self.manufacture = Some(manufacture.into());
+        self
+    }
+
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    pub fn advertisement(mut self, advertisement: Advertisement) -> Self {
+        self.advertisement = advertisement;
+        self
+    }
+
+    /// Advertise a manufacturer specific payload, `payload` excludes the company identifier.
+    pub fn manufacturer_data(mut self, company_id: u16, payload: impl Into<Vec<u8>>) -> Self {
+        self.advertisement
+            .manufacturer_data
+            .insert(company_id, payload.into());
+        self
+    }
+
+    pub fn mac_addr(mut self, mac_addr: impl Into<String>) -> Self {
+        self.mac_addr = Some(mac_addr.into());
+        self
+    }
+
+    pub fn bl_name(mut self, bl_name: impl Into<String>) -> Self {
+        self.bl_name = Some(bl_name.into());
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// Constant RSSI for the whole simulation.
+    pub fn rssi(self, rssi: i32) -> Self {
+        self.rssi_curve(vec![rssi])
+    }
+
+    /// RSSI samples played back one per [`SimulatedBackend::advance`], the last one is held.
+    pub fn rssi_curve(mut self, curve: Vec<i32>) -> Self {
+        if !curve.is_empty() {
+            self.rssi_curve = curve;
+        }
+        self
+    }
+
+    pub fn connectable(mut self, connectable: bool) -> Self {
+        self.connectable = connectable;
+        self
+    }
+
+    /// Offer a GATT service, with its characteristics, once connected.
+    pub fn service(mut self, service: GattService) -> Self {
+        self.services.push(service);
+        self
+    }
+
+    /// Initial value of a characteristic, writes replace it.
+    pub fn value(mut self, service: &str, characteristic: &str, value: impl Into<Vec<u8>>) -> Self {
+        self.values
+            .insert(value_key(service, characteristic), value.into());
+        self
+    }
+
+    /// Fail the reads of a readable characteristic with `error`, like an attribute the
+    /// peripheral guards behind authentication.
+    pub fn read_error(mut self, service: &str, characteristic: &str, error: &str) -> Self {
+        self.read_errors
+            .insert(value_key(service, characteristic), error.to_string());
+        self
+    }
+
+    fn characteristic(&self, service: &str, characteristic: &str) -> Result<&GattCharacteristic> {
+        let service = self
+            .services
+            .iter()
+            .find(|known| gatt::same_uuid(&known.uuid, service))
+            .ok_or_else(|| Error::Gatt(format!("service {service} not found")))?;
+        service
+            .characteristics
+            .iter()
+            .find(|known| gatt::same_uuid(&known.uuid, characteristic))
+            .ok_or_else(|| Error::Gatt(format!("characteristic {characteristic} not found")))
+    }
+}
+
+fn value_key(service: &str, characteristic: &str) -> String {
+    format!(
+        "{}/{}",
+        gatt::normalize_uuid(service),
+        gatt::normalize_uuid(characteristic)
+    )
+}
+
+struct SimulatedPeripheral {
+    spec: VirtualPeripheral,
+    cursor: usize,
+    discovered: bool,
+    connected: bool,
+    /// A connection attempt held by [`SimulatedBackend::hold_connections`].
+    connecting: bool,
+    latest_rssis: VecDeque<i32>,
+    /// Characteristics with notifications enabled, keyed by `value_key`.
+    notifying: HashSet<String>,
+}
+
+impl SimulatedPeripheral {
+    fn rssi(&self) -> i32 {
+        let last = self.spec.rssi_curve.len() - 1;
+        self.spec.rssi_curve[self.cursor.min(last)]
+    }
+
+    fn sample_rssi(&mut self) -> (i32, i32) {
+        let rssi = self.rssi();
+        if self.latest_rssis.len() >= ESTIMATE_WINDOW {
+            self.latest_rssis.pop_front();
+        }
+        self.latest_rssis.push_back(rssi);
+        let estimated = self.latest_rssis.iter().sum::<i32>() / self.latest_rssis.len() as i32;
+        (rssi, estimated)
+    }
+
+    fn device(&self) -> Device {
+        let state = if self.connected {
+            "connected"
+        } else {
+            "disconnected"
+        };
+        Device {
+            uuid: self.spec.uuid.clone(),
+            manufacture: self.spec.manufacture.clone(),
+            model: self.spec.model.clone(),
+            advertisement: self.spec.advertisement.clone(),
+            rssi: self.rssi(),
+            mac_addr: self.spec.mac_addr.clone(),
+            bl_name: self.spec.bl_name.clone(),
+            name: self.spec.name.clone(),
+            state: Some(state.to_string()),
+            beacons: vec![],
+        }
+    }
+}
+
+struct SimulatedState {
+    peripherals: HashMap<String, SimulatedPeripheral>,
+    scanning: bool,
+    allow_duplicates: bool,
+    passive_mode: bool,
+    adapter: AdapterState,
+    power_warned: bool,
+    hold_connections: bool,
+    /// Set by `initialize`, nothing is reported before.
+    dispatcher: Option<Dispatcher>,
+}
+
+impl Default for SimulatedState {
+    fn default() -> Self {
+        SimulatedState {
+            peripherals: HashMap::new(),
+            scanning: false,
+            allow_duplicates: false,
+            passive_mode: false,
+            adapter: AdapterState::PoweredOn,
+            power_warned: false,
+            hold_connections: false,
+            dispatcher: None,
+        }
+    }
+}
+
+/// Events collected while the state is locked and dispatched once it is released, so a delegate
+/// may call back into the backend.
+enum SimulatedEvent {
+    NewDevice(Device),
+    UpdateDevice(Device),
+    RemoveDevice(Device),
+    Rssi(String, i32, i32, bool),
+    Presence(bool, String),
+    PowerWarn,
+    AdapterState(AdapterState),
+    ConnectionState(String, bool, Option<String>),
+    Notification(String, String, String, Vec<u8>),
+}
+
+impl SimulatedEvent {
+    fn dispatch(self, state: &State) {
+        match self {
+            SimulatedEvent::NewDevice(device) => state.dispatch_new_device(device),
+            SimulatedEvent::UpdateDevice(device) => state.dispatch_update_device(device),
+            SimulatedEvent::RemoveDevice(device) => state.dispatch_remove_device(device),
+            SimulatedEvent::Rssi(identifier, rssi, estimated_rssi, active) => {
+                state.dispatch_rssi(identifier, rssi, estimated_rssi, active)
+            }
+            SimulatedEvent::Presence(presence, reason) => state.dispatch_presence(presence, reason),
+            SimulatedEvent::PowerWarn => state.dispatch_power_warn(),
+            SimulatedEvent::AdapterState(adapter) => state.dispatch_adapter_state(adapter),
+            SimulatedEvent::ConnectionState(identifier, connected, error) => {
+                state.dispatch_connection_state(&identifier, connected, error)
+            }
+            SimulatedEvent::Notification(identifier, service, characteristic, value) => {
+                state.dispatch_notification(identifier, service, characteristic, value)
+            }
+        }
+    }
+}
+
+/// In-process backend driven by [`VirtualPeripheral`]s, for running the plugin without CoreBluetooth.
+///
+/// Nothing happens on its own: peripherals are advertised when scanning starts or when they are
+/// added during a scan, and [`advance`](Self::advance) moves every RSSI curve one sample forward.
+/// Clones share the same simulation, keep one around to script it after handing the backend to
+/// [`init_with_backend`](crate::init_with_backend).
+#[derive(Clone, Default)]
+pub struct SimulatedBackend {
+    state: Arc<Mutex<SimulatedState>>,
+}
+
+impl SimulatedBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a peripheral, advertising it right away if a scan is running.
+    pub fn add_peripheral(&self, peripheral: VirtualPeripheral) {
+        let events = self.with_state(|state| {
+            let mut simulated = SimulatedPeripheral {
+                spec: peripheral,
+                cursor: 0,
+                discovered: false,
+                connected: false,
+                connecting: false,
+                latest_rssis: VecDeque::new(),
+                notifying: HashSet::new(),
+            };
+            let mut events = vec![];
+            if state.scanning && state.adapter.is_powered_on() {
+                simulated.discovered = true;
+                events.push(SimulatedEvent::NewDevice(simulated.device()));
+            }
+            state
+                .peripherals
+                .insert(simulated.spec.uuid.clone(), simulated);
+            events
+        });
+        self.dispatch(events);
+    }
+
+    /// Remove a peripheral as if it went out of range.
+    pub fn remove_peripheral(&self, identifier: &str) {
+        let events = self.with_state(|state| match state.peripherals.remove(identifier) {
+            Some(peripheral) if peripheral.discovered => {
+                vec![SimulatedEvent::RemoveDevice(peripheral.device())]
+            }
+            _ => vec![],
+        });
+        self.dispatch(events);
+    }
+
+    /// Move every RSSI curve one sample forward.
+    ///
+    /// Discovered peripherals are reported again while scanning, and connected ones produce an
+    /// RSSI update unless passive mode is on.
+    pub fn advance(&self) {
+        let events = self.with_state(|state| {
+            let mut events = vec![];
+            if !state.adapter.is_powered_on() {
+                return events;
+            }
+            let scanning = state.scanning;
+            let allow_duplicates = state.allow_duplicates;
+            let active = !state.passive_mode;
+            for peripheral in state.peripherals.values_mut() {
+                peripheral.cursor += 1;
+                if scanning && !peripheral.discovered {
+                    peripheral.discovered = true;
+                    events.push(SimulatedEvent::NewDevice(peripheral.device()));
+                } else if scanning && allow_duplicates {
+                    events.push(SimulatedEvent::UpdateDevice(peripheral.device()));
+                }
+                if peripheral.connected && active {
+                    let (rssi, estimated_rssi) = peripheral.sample_rssi();
+                    events.push(SimulatedEvent::Rssi(
+                        peripheral.spec.uuid.clone(),
+                        rssi,
+                        estimated_rssi,
+                        active,
+                    ));
+                }
+            }
+            events
+        });
+        self.dispatch(events);
+    }
+
+    /// Call [`advance`](Self::advance) `steps` times.
+    pub fn advance_by(&self, steps: usize) {
+        for _ in 0..steps {
+            self.advance();
+        }
+    }
+
+    /// Drop the connection to a peripheral as if the link was lost.
+    pub fn drop_connection(&self, identifier: &str) -> bool {
+        let events = self.with_state(|state| match state.peripherals.get_mut(identifier) {
+            Some(peripheral) if peripheral.connected => {
+                peripheral.connected = false;
+                vec![
+                    SimulatedEvent::UpdateDevice(peripheral.device()),
+                    SimulatedEvent::ConnectionState(
+                        identifier.to_string(),
+                        false,
+                        Some("connection lost".to_string()),
+                    ),
+                ]
+            }
+            _ => vec![],
+        });
+        let dropped = !events.is_empty();
+        self.dispatch(events);
+        dropped
+    }
+
+    /// Leave the connection attempts unanswered while `hold` is set, as a slow or unresponsive
+    /// peripheral would, until [`finish_connection`](Self::finish_connection).
+    pub fn hold_connections(&self, hold: bool) {
+        self.with_state(|state| state.hold_connections = hold);
+    }
+
+    /// Answer the held connection attempt to a peripheral, connecting it or failing with `error`.
+    /// Returns whether an attempt was held.
+    pub fn finish_connection(&self, identifier: &str, error: Option<&str>) -> bool {
+        let events = self.with_state(|state| match state.peripherals.get_mut(identifier) {
+            Some(peripheral) if peripheral.connecting => {
+                peripheral.connecting = false;
+                if let Some(error) = error {
+                    return vec![SimulatedEvent::ConnectionState(
+                        identifier.to_string(),
+                        false,
+                        Some(error.to_string()),
+                    )];
+                }
+                peripheral.connected = true;
+                vec![
+                    SimulatedEvent::UpdateDevice(peripheral.device()),
+                    SimulatedEvent::ConnectionState(identifier.to_string(), true, None),
+                ]
+            }
+            _ => vec![],
+        });
+        let held = !events.is_empty();
+        self.dispatch(events);
+        held
+    }
+
+    /// Change whether a peripheral accepts new connections.
+    pub fn set_connectable(&self, identifier: &str, connectable: bool) {
+        self.with_state(|state| {
+            if let Some(peripheral) = state.peripherals.get_mut(identifier) {
+                peripheral.spec.connectable = connectable;
+            }
+        });
+    }
+
+    /// Change the value of a characteristic as the peripheral would, notifying it if enabled.
+    ///
+    /// Nothing is sent while disconnected, enabled notifications resume after a reconnection.
+    pub fn notify(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        value: impl Into<Vec<u8>>,
+    ) {
+        let value = value.into();
+        let events = self.with_state(|state| {
+            let Some(peripheral) = state.peripherals.get_mut(identifier) else {
+                return vec![];
+            };
+            let key = value_key(service, characteristic);
+            peripheral.spec.values.insert(key.clone(), value.clone());
+            if !peripheral.connected || !peripheral.notifying.contains(&key) {
+                return vec![];
+            }
+            vec![SimulatedEvent::Notification(
+                identifier.to_string(),
+                service.to_string(),
+                characteristic.to_string(),
+                value,
+            )]
+        });
+        self.dispatch(events);
+    }
+
+    /// Report a presence change, the simulator has no lock/unlock logic of its own.
+    pub fn set_presence(&self, presence: bool, reason: impl Into<String>) {
+        self.dispatch(vec![SimulatedEvent::Presence(presence, reason.into())]);
+    }
+
+    /// Toggle the simulated adapter power, powering off drops every connection.
+    pub fn set_powered(&self, powered: bool) {
+        self.set_adapter_state(if powered {
+            AdapterState::PoweredOn
+        } else {
+            AdapterState::PoweredOff
+        });
+    }
+
+    /// Move the simulated adapter to `adapter`, leaving the powered on state drops every
+    /// connection.
+    pub fn set_adapter_state(&self, adapter: AdapterState) {
+        let events = self.with_state(|state| {
+            if state.adapter == adapter {
+                return vec![];
+            }
+            let mut events = vec![SimulatedEvent::AdapterState(adapter)];
+            state.adapter = adapter;
+            if adapter.is_powered_on() {
+                state.power_warned = false;
+                return events;
+            }
+            for peripheral in state.peripherals.values_mut() {
+                if std::mem::take(&mut peripheral.connecting) {
+                    events.push(SimulatedEvent::ConnectionState(
+                        peripheral.spec.uuid.clone(),
+                        false,
+                        Some("bluetooth unavailable".to_string()),
+                    ));
+                }
+                if peripheral.connected {
+                    peripheral.connected = false;
+                    events.push(SimulatedEvent::UpdateDevice(peripheral.device()));
+                    events.push(SimulatedEvent::ConnectionState(
+                        peripheral.spec.uuid.clone(),
+                        false,
+                        Some("bluetooth unavailable".to_string()),
+                    ));
+                }
+            }
+            if adapter == AdapterState::PoweredOff && !state.power_warned {
+                state.power_warned = true;
+                events.push(SimulatedEvent::PowerWarn);
+            }
+            events
+        });
+        self.dispatch(events);
+    }
+
+    pub fn is_scanning(&self) -> bool {
+        self.with_state(|state| state.scanning)
+    }
+
+    pub fn is_connected(&self, identifier: &str) -> bool {
+        self.with_state(|state| {
+            state
+                .peripherals
+                .get(identifier)
+                .is_some_and(|peripheral| peripheral.connected)
+        })
+    }
+
+    /// Whether a connection attempt to the peripheral is held, see
+    /// [`hold_connections`](Self::hold_connections).
+    pub fn is_connecting(&self, identifier: &str) -> bool {
+        self.with_state(|state| {
+            state
+                .peripherals
+                .get(identifier)
+                .is_some_and(|peripheral| peripheral.connecting)
+        })
+    }
+
+    /// Run `f` on a connected peripheral.
+    fn with_connected<T>(
+        &self,
+        identifier: &str,
+        f: impl FnOnce(&mut SimulatedPeripheral) -> Result<T>,
+    ) -> Result<T> {
+        self.with_state(|state| {
+            state.adapter.check()?;
+            match state.peripherals.get_mut(identifier) {
+                Some(peripheral) if peripheral.connected => f(peripheral),
+                Some(peripheral) if peripheral.discovered => {
+                    Err(Error::NotConnected(identifier.to_string()))
+                }
+                _ => Err(Error::DeviceNotFound(identifier.to_string())),
+            }
+        })
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut SimulatedState) -> T) -> T {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        f(&mut state)
+    }
+
+    fn dispatch(&self, events: Vec<SimulatedEvent>) {
+        let dispatcher = self.with_state(|state| state.dispatcher.clone());
+        let Some(state) = dispatcher.as_ref().and_then(Dispatcher::state) else {
+            return;
+        };
+        for event in events {
+            event.dispatch(&state);
+        }
+    }
+}
+
+impl BluetoothBackend for SimulatedBackend {
+    fn echo(&self, value: &str) -> String {
+        value.to_string()
+    }
+
+    fn initialize(&self, dispatcher: Dispatcher) {
+        self.with_state(|state| state.dispatcher = Some(dispatcher));
+    }
+
+    fn adapter_state(&self) -> AdapterState {
+        self.with_state(|state| state.adapter)
+    }
+
+    fn start_scanning(&self, options: &ScanOptions) -> Result<()> {
+        let events = self.with_state(|state| -> Result<Vec<SimulatedEvent>> {
+            state.adapter.check()?;
+            state.scanning = true;
+            state.allow_duplicates = options.allow_duplicates;
+            Ok(state
+                .peripherals
+                .values_mut()
+                .filter(|peripheral| !peripheral.discovered)
+                .map(|peripheral| {
+                    peripheral.discovered = true;
+                    SimulatedEvent::NewDevice(peripheral.device())
+                })
+                .collect())
+        })?;
+        self.dispatch(events);
+        Ok(())
+    }
+
+    fn stop_scanning(&self) -> Result<()> {
+        self.with_state(|state| state.scanning = false);
+        Ok(())
+    }
+
+    fn set_passive_mode(&self, mode: bool) {
+        let events = self.with_state(|state| {
+            state.passive_mode = mode;
+            if !mode {
+                return vec![];
+            }
+            // Same as the Swift side, passive mode gives up the active connections and attempts.
+            let mut events = vec![];
+            for peripheral in state.peripherals.values_mut() {
+                let connecting = std::mem::take(&mut peripheral.connecting);
+                if !peripheral.connected && !connecting {
+                    continue;
+                }
+                if std::mem::take(&mut peripheral.connected) {
+                    events.push(SimulatedEvent::UpdateDevice(peripheral.device()));
+                }
+                events.push(SimulatedEvent::ConnectionState(
+                    peripheral.spec.uuid.clone(),
+                    false,
+                    Some("passive mode".to_string()),
+                ));
+            }
+            events
+        });
+        self.dispatch(events);
+    }
+
+    fn connect_device(&self, identifier: &str) -> Result<()> {
+        let events = self.with_state(|state| {
+            state.adapter.check()?;
+            let hold = state.hold_connections;
+            match state.peripherals.get_mut(identifier) {
+                Some(peripheral) if peripheral.discovered => {
+                    if !peripheral.spec.connectable {
+                        return Err(Error::Connection(identifier.to_string()));
+                    }
+                    let connected =
+                        SimulatedEvent::ConnectionState(identifier.to_string(), true, None);
+                    if peripheral.connected {
+                        return Ok(vec![connected]);
+                    }
+                    if hold {
+                        peripheral.connecting = true;
+                        return Ok(vec![]);
+                    }
+                    peripheral.connected = true;
+                    Ok(vec![
+                        SimulatedEvent::UpdateDevice(peripheral.device()),
+                        connected,
+                    ])
+                }
+                _ => Err(Error::DeviceNotFound(identifier.to_string())),
+            }
+        })?;
+        self.dispatch(events);
+        Ok(())
+    }
+
+    fn disconnect_device(&self, identifier: &str) -> Result<()> {
+        let cancelled = self.with_state(|state| {
+            state
+                .peripherals
+                .get_mut(identifier)
+                .is_some_and(|peripheral| std::mem::take(&mut peripheral.connecting))
+        });
+        if cancelled {
+            // cancelling a held attempt, nothing to tear down.
+            self.dispatch(vec![SimulatedEvent::ConnectionState(
+                identifier.to_string(),
+                false,
+                None,
+            )]);
+            return Ok(());
+        }
+        let device = self.with_connected(identifier, |peripheral| {
+            peripheral.connected = false;
+            peripheral.notifying.clear();
+            Ok(peripheral.device())
+        })?;
+        self.dispatch(vec![
+            SimulatedEvent::UpdateDevice(device),
+            SimulatedEvent::ConnectionState(identifier.to_string(), false, None),
+        ]);
+        Ok(())
+    }
+
+    fn read_rssi(&self, identifier: &str) -> Result<()> {
+        let active = self.with_state(|state| !state.passive_mode);
+        let (rssi, estimated_rssi) =
+            self.with_connected(identifier, |peripheral| Ok(peripheral.sample_rssi()))?;
+        self.dispatch(vec![SimulatedEvent::Rssi(
+            identifier.to_string(),
+            rssi,
+            estimated_rssi,
+            active,
+        )]);
+        Ok(())
+    }
+
+    fn discover_services(&self, identifier: &str) -> Result<Vec<GattService>> {
+        self.with_connected(identifier, |peripheral| {
+            Ok(peripheral
+                .spec
+                .services
+                .iter()
+                .map(|service| GattService {
+                    characteristics: vec![],
+                    ..service.clone()
+                })
+                .collect())
+        })
+    }
+
+    fn discover_characteristics(
+        &self,
+        identifier: &str,
+        service: &str,
+    ) -> Result<Vec<GattCharacteristic>> {
+        self.with_connected(identifier, |peripheral| {
+            peripheral
+                .spec
+                .services
+                .iter()
+                .find(|known| gatt::same_uuid(&known.uuid, service))
+                .map(|known| known.characteristics.clone())
+                .ok_or_else(|| Error::Gatt(format!("service {service} not found")))
+        })
+    }
+
+    fn read_characteristic(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+    ) -> Result<Vec<u8>> {
+        self.with_connected(identifier, |peripheral| {
+            if !peripheral
+                .spec
+                .characteristic(service, characteristic)?
+                .properties
+                .read
+            {
+                return Err(Error::Unsupported(format!(
+                    "characteristic {characteristic} is not readable"
+                )));
+            }
+            let key = value_key(service, characteristic);
+            if let Some(error) = peripheral.spec.read_errors.get(&key) {
+                return Err(Error::Gatt(error.clone()));
+            }
+            Ok(peripheral
+                .spec
+                .values
+                .get(&key)
+                .cloned()
+                .unwrap_or_default())
+        })
+    }
+
+    fn write_characteristic(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        value: &[u8],
+        with_response: bool,
+    ) -> Result<()> {
+        self.with_connected(identifier, |peripheral| {
+            let properties = peripheral
+                .spec
+                .characteristic(service, characteristic)?
+                .properties;
+            let writable = if with_response {
+                properties.write
+            } else {
+                properties.write_without_response
+            };
+            if !writable {
+                return Err(Error::Unsupported(format!(
+                    "characteristic {characteristic} is not writable"
+                )));
+            }
+            peripheral
+                .spec
+                .values
+                .insert(value_key(service, characteristic), value.to_vec());
+            Ok(())
+        })
+    }
+
+    fn set_notify(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        enabled: bool,
+    ) -> Result<()> {
+        self.with_connected(identifier, |peripheral| {
+            let properties = peripheral
+                .spec
+                .characteristic(service, characteristic)?
+                .properties;
+            if !properties.notify && !properties.indicate {
+                return Err(Error::Unsupported(format!(
+                    "characteristic {characteristic} doesn't notify"
+                )));
+            }
+            let key = value_key(service, characteristic);
+            if enabled {
+                peripheral.notifying.insert(key);
+            } else {
+                peripheral.notifying.remove(&key);
+            }
+            Ok(())
+        })
+    }
+}
@@ -0,0 +1,527 @@
+use crate::advertisement::Advertisement;
+use crate::calibration::PathLossModel;
+use crate::hex;
+use serde::{Deserialize, Serialize};
+
+/// Company identifier of Apple, the only one iBeacons are advertised under.
+pub const APPLE_COMPANY_ID: u16 = 0x004C;
+/// Service UUID carrying the Eddystone frames.
+pub const EDDYSTONE_SERVICE_UUID: &str = "FEAA";
+
+const IBEACON_PREFIX: [u8; 2] = [0x02, 0x15];
+const ALTBEACON_PREFIX: [u8; 2] = [0xBE, 0xAC];
+/// Eddystone calibrates its TX power at 0m, the signal loses about 41dB over the first meter.
+const EDDYSTONE_ONE_METER_LOSS: i32 = 41;
+
+const URL_SCHEMES: [&str; 4] = ["http://www.", "https://www.", "http://", "https://"];
+const URL_EXPANSIONS: [&str; 14] = [
+    ".com/", ".org/", ".edu/", ".net/", ".info/", ".biz/", ".gov/", ".com", ".org", ".edu", ".net",
+    ".info", ".biz", ".gov",
+];
+
+/// A beacon frame decoded from the advertisement of a device.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum BeaconFrame {
+    #[serde(rename_all = "camelCase")]
+    IBeacon {
+        uuid: String,
+        major: u16,
+        minor: u16,
+        /// RSSI at one meter.
+        measured_power: i8,
+    },
+    #[serde(rename_all = "camelCase")]
+    AltBeacon {
+        manufacturer_id: u16,
+        /// First 16 bytes of the beacon ID, formatted as a UUID.
+        id1: String,
+        id2: u16,
+        id3: u16,
+        /// RSSI at one meter.
+        reference_rssi: i8,
+        reserved: u8,
+    },
+    #[serde(rename_all = "camelCase")]
+    EddystoneUid {
+        /// 10 bytes, hex encoded.
+        namespace: String,
+        /// 6 bytes, hex encoded.
+        instance: String,
+        /// TX power at 0m.
+        tx_power: i8,
+    },
+    #[serde(rename_all = "camelCase")]
+    EddystoneUrl { url: String, tx_power: i8 },
+    /// Unencrypted telemetry, it doesn't carry a TX power.
+    #[serde(rename_all = "camelCase")]
+    EddystoneTlm {
+        version: u8,
+        /// Battery voltage in mV, 0 when not supported.
+        battery_voltage: u16,
+        /// Degrees Celsius, if supported.
+        temperature: Option<f64>,
+        advertising_count: u32,
+        /// Seconds since boot.
+        uptime: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    EddystoneEid {
+        /// 8 bytes, hex encoded.
+        eid: String,
+        tx_power: i8,
+    },
+}
+
+impl BeaconFrame {
+    /// RSSI the frame announces at one meter.
+    pub fn measured_power(&self) -> Option<i32> {
+        match *self {
+            BeaconFrame::IBeacon { measured_power, .. } => Some(measured_power as i32),
+            BeaconFrame::AltBeacon { reference_rssi, .. } => Some(reference_rssi as i32),
+            BeaconFrame::EddystoneUid { tx_power, .. }
+            | BeaconFrame::EddystoneUrl { tx_power, .. }
+            | BeaconFrame::EddystoneEid { tx_power, .. } => {
+                Some(tx_power as i32 - EDDYSTONE_ONE_METER_LOSS)
+            }
+            BeaconFrame::EddystoneTlm { .. } => None,
+        }
+    }
+
+    /// Distance in meters for an RSSI, with the free space path loss model.
+    pub fn distance(&self, rssi: i32) -> Option<f64> {
+        let measured_power = self.measured_power()?;
+        let model = PathLossModel {
+            measured_power: measured_power as f64,
+            ..PathLossModel::default()
+        };
+        Some(model.distance(rssi as f64))
+    }
+
+    fn same_kind(&self, other: &BeaconFrame) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// A decoded frame with the distance derived from its TX power.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Beacon {
+    #[serde(flatten)]
+    pub frame: BeaconFrame,
+    /// Meters, `None` for frames without TX power.
+    pub distance: Option<f64>,
+}
+
+/// A beacon heard from a device, sent to the channels of `scan_beacons`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BeaconSighting {
+    pub identifier: String,
+    pub rssi: i32,
+    pub beacon: Beacon,
+}
+
+/// The beacons to look for, every field that is set must match, like a `CLBeaconRegion`.
+///
+/// `uuid`, `major` and `minor` match iBeacons and the IDs of AltBeacons, `namespace` and
+/// `instance` match Eddystone UIDs. An empty region matches every frame.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct BeaconRegion {
+    pub uuid: Option<String>,
+    pub major: Option<u16>,
+    pub minor: Option<u16>,
+    pub namespace: Option<String>,
+    pub instance: Option<String>,
+}
+
+impl BeaconRegion {
+    pub fn matches(&self, frame: &BeaconFrame) -> bool {
+        let (uuid, major, minor, namespace, instance) = match frame {
+            BeaconFrame::IBeacon {
+                uuid, major, minor, ..
+            } => (Some(uuid), Some(*major), Some(*minor), None, None),
+            BeaconFrame::AltBeacon { id1, id2, id3, .. } => {
+                (Some(id1), Some(*id2), Some(*id3), None, None)
+            }
+            BeaconFrame::EddystoneUid {
+                namespace,
+                instance,
+                ..
+            } => (None, None, None, Some(namespace), Some(instance)),
+            _ => (None, None, None, None, None),
+        };
+        fn text(expected: &Option<String>, actual: Option<&String>) -> bool {
+            match expected {
+                Some(expected) => {
+                    actual.is_some_and(|actual| actual.eq_ignore_ascii_case(expected))
+                }
+                None => true,
+            }
+        }
+        fn number(expected: Option<u16>, actual: Option<u16>) -> bool {
+            expected.is_none() || expected == actual
+        }
+        text(&self.uuid, uuid)
+            && number(self.major, major)
+            && number(self.minor, minor)
+            && text(&self.namespace, namespace)
+            && text(&self.instance, instance)
+    }
+}
+
+/// Decode the beacon frames of an advertisement.
+pub fn decode(advertisement: &Advertisement) -> Vec<BeaconFrame> {
+    let mut frames = vec![];
+    for (company_id, payload) in &advertisement.manufacturer_data {
+        if let Some(frame) =
+            decode_ibeacon(*company_id, payload).or_else(|| decode_altbeacon(*company_id, payload))
+        {
+            frames.push(frame);
+        }
+    }
+    if let Some(frame) = advertisement
+        .service_data(EDDYSTONE_SERVICE_UUID)
+        .and_then(decode_eddystone)
+    {
+        frames.push(frame);
+    }
+    frames
+}
+
+/// Decode the beacons of an advertisement heard at `rssi`.
+pub fn beacons(advertisement: &Advertisement, rssi: i32) -> Vec<Beacon> {
+    decode(advertisement)
+        .into_iter()
+        .map(|frame| Beacon {
+            distance: frame.distance(rssi),
+            frame,
+        })
+        .collect()
+}
+
+/// Fold newer beacons in, replacing those of the same kind. Eddystone beacons rotate their
+/// frames, this keeps the latest of each.
+pub(crate) fn merge(beacons: &mut Vec<Beacon>, update: &[Beacon]) {
+    for beacon in update {
+        match beacons
+            .iter_mut()
+            .find(|b| b.frame.same_kind(&beacon.frame))
+        {
+            Some(known) => *known = beacon.clone(),
+            None => beacons.push(beacon.clone()),
+        }
+    }
+}
+
+/// Manufacturer payload: `02 15`, UUID, major, minor and measured power.
+fn decode_ibeacon(company_id: u16, payload: &[u8]) -> Option<BeaconFrame> {
+    if company_id != APPLE_COMPANY_ID || payload.len() < 23 || payload[..2] != IBEACON_PREFIX {
+        return None;
+    }
+    Some(BeaconFrame::IBeacon {
+        uuid: format_uuid(&payload[2..18]),
+        major: u16::from_be_bytes([payload[18], payload[19]]),
+        minor: u16::from_be_bytes([payload[20], payload[21]]),
+        measured_power: payload[22] as i8,
+    })
+}
+
+/// Manufacturer payload: `BE AC`, a 20 bytes beacon ID, reference RSSI and a reserved byte.
+fn decode_altbeacon(company_id: u16, payload: &[u8]) -> Option<BeaconFrame> {
+    if payload.len() < 24 || payload[..2] != ALTBEACON_PREFIX {
+        return None;
+    }
+    Some(BeaconFrame::AltBeacon {
+        manufacturer_id: company_id,
+        id1: format_uuid(&payload[2..18]),
+        id2: u16::from_be_bytes([payload[18], payload[19]]),
+        id3: u16::from_be_bytes([payload[20], payload[21]]),
+        reference_rssi: payload[22] as i8,
+        reserved: payload[23],
+    })
+}
+
+fn decode_eddystone(data: &[u8]) -> Option<BeaconFrame> {
+    let (&frame_type, frame) = data.split_first()?;
+    match frame_type {
+        0x00 if frame.len() >= 17 => Some(BeaconFrame::EddystoneUid {
+            tx_power: frame[0] as i8,
+            namespace: hex::encode(&frame[1..11]),
+            instance: hex::encode(&frame[11..17]),
+        }),
+        0x10 if frame.len() >= 2 => Some(BeaconFrame::EddystoneUrl {
+            tx_power: frame[0] as i8,
+            url: decode_url(frame[1], &frame[2..])?,
+        }),
+        // only the unencrypted version 0 can be read.
+        0x20 if frame.len() >= 13 && frame[0] == 0 => {
+            let temperature = i16::from_be_bytes([frame[3], frame[4]]);
+            Some(BeaconFrame::EddystoneTlm {
+                version: frame[0],
+                battery_voltage: u16::from_be_bytes([frame[1], frame[2]]),
+                temperature: (temperature != i16::MIN).then(|| temperature as f64 / 256.0),
+                advertising_count: u32::from_be_bytes([frame[5], frame[6], frame[7], frame[8]]),
+                uptime: u32::from_be_bytes([frame[9], frame[10], frame[11], frame[12]]) as f64
+                    / 10.0,
+            })
+        }
+        0x30 if frame.len() >= 9 => Some(BeaconFrame::EddystoneEid {
+            tx_power: frame[0] as i8,
+            eid: hex::encode(&frame[1..9]),
+        }),
+        _ => None,
+    }
+}
+
+fn decode_url(scheme: u8, encoded: &[u8]) -> Option<String> {
+    let mut url = URL_SCHEMES.get(scheme as usize)?.to_string();
+    for &byte in encoded {
+        match URL_EXPANSIONS.get(byte as usize) {
+            Some(expansion) => url.push_str(expansion),
+            None if (0x21..0x7F).contains(&byte) => url.push(byte as char),
+            None => return None,
+        }
+    }
+    Some(url)
+}
+
+fn format_uuid(bytes: &[u8]) -> String {
+    let hex = hex::encode(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const UUID: [u8; 16] = [
+        0xE2, 0xC5, 0x6D, 0xB5, 0xDF, 0xFB, 0x48, 0xD2, 0xB0, 0x60, 0xD0, 0xF5, 0xA7, 0x10, 0x96,
+        0xE0,
+    ];
+
+    fn manufacturer(company_id: u16, payload: &[u8]) -> Advertisement {
+        let mut advertisement = Advertisement::default();
+        advertisement
+            .manufacturer_data
+            .insert(company_id, payload.to_vec());
+        advertisement
+    }
+
+    fn eddystone(frame: &[u8]) -> Option<BeaconFrame> {
+        let mut advertisement = Advertisement::default();
+        advertisement
+            .service_data
+            .insert("feaa".to_string(), frame.to_vec());
+        let mut frames = decode(&advertisement);
+        assert!(frames.len() <= 1);
+        frames.pop()
+    }
+
+    fn ibeacon_payload(major: u16, minor: u16, measured_power: i8) -> Vec<u8> {
+        let mut payload = IBEACON_PREFIX.to_vec();
+        payload.extend(UUID);
+        payload.extend(major.to_be_bytes());
+        payload.extend(minor.to_be_bytes());
+        payload.push(measured_power as u8);
+        payload
+    }
+
+    #[test]
+    fn ibeacons_are_read_from_the_apple_payload() {
+        let payload = ibeacon_payload(1, 0xFFFF, -59);
+        assert_eq!(
+            decode(&manufacturer(APPLE_COMPANY_ID, &payload)),
+            [BeaconFrame::IBeacon {
+                uuid: "E2C56DB5-DFFB-48D2-B060-D0F5A71096E0".to_string(),
+                major: 1,
+                minor: 0xFFFF,
+                measured_power: -59,
+            }]
+        );
+        // the same payload under another company, or cut short, is no iBeacon.
+        assert!(decode(&manufacturer(0x0118, &payload)).is_empty());
+        for len in 0..payload.len() {
+            assert!(decode(&manufacturer(APPLE_COMPANY_ID, &payload[..len])).is_empty());
+        }
+    }
+
+    #[test]
+    fn altbeacons_keep_their_manufacturer() {
+        let mut payload = ALTBEACON_PREFIX.to_vec();
+        payload.extend(UUID);
+        payload.extend([0x00, 0x02, 0x01, 0x00, 0xC0, 0x7F]);
+        assert_eq!(
+            decode(&manufacturer(0x0118, &payload)),
+            [BeaconFrame::AltBeacon {
+                manufacturer_id: 0x0118,
+                id1: "E2C56DB5-DFFB-48D2-B060-D0F5A71096E0".to_string(),
+                id2: 2,
+                id3: 256,
+                reference_rssi: -64,
+                reserved: 0x7F,
+            }]
+        );
+        for len in 0..payload.len() {
+            assert!(decode(&manufacturer(0x0118, &payload[..len])).is_empty());
+        }
+    }
+
+    #[test]
+    fn eddystone_uids_split_namespace_and_instance() {
+        let mut frame = vec![0x00, 0xEE];
+        frame.extend([0x8B, 0x0C, 0xA7, 0x50, 0xE7, 0xA7, 0x4E, 0x14, 0xBD, 0x99]);
+        frame.extend([0x00, 0x00, 0x00, 0x00, 0x00, 0x01]);
+        let uid = BeaconFrame::EddystoneUid {
+            namespace: "8B0CA750E7A74E14BD99".to_string(),
+            instance: "000000000001".to_string(),
+            tx_power: -18,
+        };
+        assert_eq!(eddystone(&frame), Some(uid.clone()));
+        // the two reserved bytes may follow.
+        frame.extend([0x00, 0x00]);
+        assert_eq!(eddystone(&frame), Some(uid));
+        for len in 0..18 {
+            assert_eq!(eddystone(&frame[..len]), None);
+        }
+    }
+
+    #[test]
+    fn eddystone_urls_expand_their_scheme_and_suffixes() {
+        let url = |frame: &[u8]| match eddystone(frame) {
+            Some(BeaconFrame::EddystoneUrl { url, tx_power }) => {
+                assert_eq!(tx_power, -21);
+                Some(url)
+            }
+            other => {
+                assert_eq!(other, None);
+                None
+            }
+        };
+        assert_eq!(
+            url(b"\x10\xEB\x00example\x07").as_deref(),
+            Some("http://www.example.com")
+        );
+        assert_eq!(
+            url(b"\x10\xEB\x03goo.gl/S6zT6P").as_deref(),
+            Some("https://goo.gl/S6zT6P")
+        );
+        assert_eq!(
+            url(b"\x10\xEB\x02abc\x00x\x0D").as_deref(),
+            Some("http://abc.com/x.gov")
+        );
+        assert_eq!(url(b"\x10\xEB\x01").as_deref(), Some("https://www."));
+        // an unknown scheme, a reserved code or a byte outside printable ASCII.
+        assert_eq!(url(b"\x10\xEB\x04example"), None);
+        assert_eq!(url(b"\x10\xEB\x00exa\x0Emple"), None);
+        assert_eq!(url(b"\x10\xEB\x00exa\x20mple"), None);
+        assert_eq!(url(b"\x10\xEB\x00exa\x7Fmple"), None);
+        assert_eq!(url(b"\x10\xEB"), None);
+    }
+
+    #[test]
+    fn eddystone_telemetry_reads_the_fixed_point_temperature() {
+        let tlm = |temperature: [u8; 2]| {
+            let mut frame = vec![0x20, 0x00, 0x0B, 0xB8];
+            frame.extend(temperature);
+            frame.extend([0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x64]);
+            frame
+        };
+        assert_eq!(
+            eddystone(&tlm([0x18, 0x80])),
+            Some(BeaconFrame::EddystoneTlm {
+                version: 0,
+                battery_voltage: 3000,
+                temperature: Some(24.5),
+                advertising_count: 256,
+                uptime: 10.0,
+            })
+        );
+        let temperature = |frame: &[u8]| match eddystone(frame) {
+            Some(BeaconFrame::EddystoneTlm { temperature, .. }) => temperature,
+            other => panic!("{other:?}"),
+        };
+        assert_eq!(temperature(&tlm([0xFF, 0x80])), Some(-0.5));
+        // 0x8000 marks a beacon without a sensor.
+        assert_eq!(temperature(&tlm([0x80, 0x00])), None);
+
+        let frame = tlm([0x18, 0x80]);
+        for len in 0..frame.len() {
+            assert_eq!(eddystone(&frame[..len]), None);
+        }
+        // the encrypted telemetry can't be read.
+        let mut encrypted = frame.clone();
+        encrypted[1] = 0x01;
+        assert_eq!(eddystone(&encrypted), None);
+    }
+
+    #[test]
+    fn eddystone_eids_carry_their_identifier() {
+        let frame = [0x30, 0xF0, 0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD, 0xEF];
+        let eid = BeaconFrame::EddystoneEid {
+            eid: "0123456789ABCDEF".to_string(),
+            tx_power: -16,
+        };
+        assert_eq!(eid.measured_power(), Some(-57));
+        assert_eq!(eddystone(&frame), Some(eid));
+        for len in 0..frame.len() {
+            assert_eq!(eddystone(&frame[..len]), None);
+        }
+        assert_eq!(eddystone(&[0x40, 0x00]), None);
+    }
+
+    #[test]
+    fn regions_match_the_fields_they_set() {
+        let ibeacon = BeaconFrame::IBeacon {
+            uuid: "E2C56DB5-DFFB-48D2-B060-D0F5A71096E0".to_string(),
+            major: 1,
+            minor: 2,
+            measured_power: -59,
+        };
+        let uid = BeaconFrame::EddystoneUid {
+            namespace: "8B0CA750E7A74E14BD99".to_string(),
+            instance: "000000000001".to_string(),
+            tx_power: -18,
+        };
+        let everything = BeaconRegion::default();
+        assert!(everything.matches(&ibeacon) && everything.matches(&uid));
+
+        let region = BeaconRegion {
+            uuid: Some("e2c56db5-dffb-48d2-b060-d0f5a71096e0".to_string()),
+            major: Some(1),
+            ..BeaconRegion::default()
+        };
+        assert!(region.matches(&ibeacon));
+        assert!(!region.matches(&uid));
+        let other_major = BeaconRegion {
+            major: Some(2),
+            ..region.clone()
+        };
+        assert!(!other_major.matches(&ibeacon));
+        let minor = BeaconRegion {
+            minor: Some(2),
+            ..region
+        };
+        assert!(minor.matches(&ibeacon));
+
+        let namespace = BeaconRegion {
+            namespace: Some("8b0ca750e7a74e14bd99".to_string()),
+            ..BeaconRegion::default()
+        };
+        assert!(namespace.matches(&uid));
+        assert!(!namespace.matches(&ibeacon));
+        let instance = BeaconRegion {
+            instance: Some("000000000002".to_string()),
+            ..namespace
+        };
+        assert!(!instance.matches(&uid));
+    }
+}
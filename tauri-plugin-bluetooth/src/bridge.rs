@@ -1,20 +1,59 @@
+use crate::adapter::AdapterState;
+use crate::advertisement::Advertisement;
+use crate::beacon::Beacon;
+use crate::calibration::PathLossModel;
+use crate::gatt::{GattCharacteristic, GattService};
+use crate::notifications::Notification;
+use crate::presence::{AggregatePresence, PresencePolicy};
+use crate::profiles::DeviceInformation;
+use crate::reconnect::{ReconnectPolicy, ReconnectUpdate};
+use crate::registry::{DeviceFilter, DeviceSnapshot};
+use crate::rssi::{RssiFilterConfig, RssiUpdate};
+use crate::scan::ScanSummary;
+use crate::trusted::TrustedDevice;
+use crate::{ScanOptions, ValueEncoding};
 use serde::{Deserialize, Serialize};
 use std::ffi::c_char;
 use std::fmt::Debug;
+use std::time::Duration;
+use tauri::ipc::Channel;
 use tauri::Runtime;
 
 ///  Describe the bluetooth device.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Device {
     pub(crate) uuid: String,
     pub(crate) manufacture: Option<String>,
     pub(crate) model: Option<String>,
-    pub(crate) adv_data: i64,
+    pub(crate) advertisement: Advertisement,
     pub(crate) rssi: i32,
     pub(crate) mac_addr: Option<String>,
     pub(crate) bl_name: Option<String>,
     pub(crate) name: Option<String>,
     pub(crate) state: Option<String>,
+    /// Beacon frames decoded from the advertisement.
+    #[serde(default)]
+    pub(crate) beacons: Vec<Beacon>,
+}
+
+impl Device {
+    pub fn uuid(&self) -> &str {
+        &self.uuid
+    }
+
+    pub fn advertisement(&self) -> &Advertisement {
+        &self.advertisement
+    }
+
+    pub fn beacons(&self) -> &[Beacon] {
+        &self.beacons
+    }
+}
+
+/// Device identifiers in the form the plugin keeps them, upper case as CoreBluetooth reports
+/// them, so that every map keyed by device matches whatever the case of the caller.
+pub(crate) fn normalize_identifier(identifier: &str) -> String {
+    identifier.trim().to_ascii_uppercase()
 }
 
 pub trait BLEDelegate: Send + Sync {
@@ -22,41 +61,90 @@ pub trait BLEDelegate: Send + Sync {
     fn update_device(&self, device: Device);
     fn remove_device(&self, device: Device);
     fn update_rssi(&self, rssi: i32, estimated_rssi: i32, active: bool);
+    /// Same reading as [`update_rssi`](Self::update_rssi) with the device it came from and the
+    /// output of its filter pipeline.
+    fn update_filtered_rssi(&self, _update: RssiUpdate) {}
     fn update_presence(&self, presence: bool, reason: String);
     fn bluetooth_power_warn(&self);
+    /// A subscribed characteristic changed, the value is passed as bytes.
+    fn characteristic_changed(&self, _notification: Notification) {}
+    /// A reconnection attempt started, failed or succeeded.
+    fn reconnect_update(&self, _update: ReconnectUpdate) {}
+    /// The adapter was powered on or off, or its authorization changed.
+    fn adapter_state_changed(&self, _state: AdapterState) {}
+    /// The presence of a monitored device changed, [`update_presence`](Self::update_presence)
+    /// only reports the presence of the user.
+    fn update_device_presence(&self, _identifier: String, _presence: bool, _reason: String) {}
+    /// The presence of the monitored devices added up under the presence policy changed.
+    fn update_aggregate_presence(&self, _aggregate: AggregatePresence) {}
 }
 
 pub type NativeDeviceDelegate = extern "C" fn(
     uuid: *const c_char,
     manufacture: *const c_char,
     model: *const c_char,
-    adv_data: i64,
+    advertisement: *const c_char,
     rssi: i32,
     mac_addr: *const c_char,
     bl_name: *const c_char,
     name: *const c_char,
     state: *const c_char,
 );
-pub type NativeRssiUpdateDelegate = extern "C" fn(rssi: i32, estimated_rssi: i32, active: bool);
+pub type NativeRssiUpdateDelegate =
+    extern "C" fn(uuid: *const c_char, rssi: i32, estimated_rssi: i32, active: bool);
 pub type NativeUpdatePresence = extern "C" fn(presence: bool, reason: *const c_char);
 pub type NativeBluetoothPowerWarnHandler = extern "C" fn();
+/// `state` is a raw `CBManagerState`.
+pub type NativeAdapterStateHandler = extern "C" fn(state: i32);
+/// A device connected, failed to connect or disconnected, `error` is empty on success and for a
+/// requested disconnection.
+pub type NativeConnectionStateHandler =
+    extern "C" fn(uuid: *const c_char, connected: bool, error: *const c_char);
+/// Result of a discovery, `service` is empty for the service discovery and `error` on success.
+pub type NativeGattResultDelegate = extern "C" fn(
+    uuid: *const c_char,
+    service: *const c_char,
+    error: *const c_char,
+    result: *const c_char,
+);
+/// Result of a characteristic read or write, `value` is hex encoded and empty for writes.
+pub type NativeCharacteristicResultDelegate = extern "C" fn(
+    uuid: *const c_char,
+    service: *const c_char,
+    characteristic: *const c_char,
+    error: *const c_char,
+    value: *const c_char,
+);
 
+#[cfg(native_bridge)]
 extern "C" {
     pub(crate) fn echo(value: *const c_char) -> *const c_char;
 
     pub(crate) fn initialize();
 
-    pub(crate) fn start_scanning() -> bool;
+    /// A raw `CBManagerState`.
+    pub(crate) fn adapter_state() -> i32;
+
+    /// `services` is a JSON array of UUIDs. Like the other functions returning an `i32`, returns
+    /// one of the `STATUS_*` codes.
+    pub(crate) fn start_scanning(services: *const c_char, allow_duplicates: bool) -> i32;
 
-    pub(crate) fn stop_scanning() -> bool;
+    pub(crate) fn stop_scanning() -> i32;
 
     pub(crate) fn set_passive_mode(mode: bool);
 
-    pub(crate) fn connect_device(identifier: *const c_char) -> bool;
+    pub(crate) fn connect_device(identifier: *const c_char) -> i32;
+
+    pub(crate) fn disconnect_device(identifier: *const c_char) -> i32;
 
-    pub(crate) fn disconnect_device(identifier: *const c_char) -> bool;
+    pub(crate) fn read_rssi(identifier: *const c_char) -> i32;
 
-    pub(crate) fn read_rssi(identifier: *const c_char);
+    pub(crate) fn discover_services(identifier: *const c_char) -> i32;
+
+    pub(crate) fn discover_characteristics(
+        identifier: *const c_char,
+        service: *const c_char,
+    ) -> i32;
 
     pub(crate) fn set_delegate(
         on_device_new: NativeDeviceDelegate,
@@ -66,25 +154,161 @@ extern "C" {
         presence_updated: NativeUpdatePresence,
         bluetooth_power_warn: NativeBluetoothPowerWarnHandler,
     );
+
+    /// Start a read.
+    pub(crate) fn read_characteristic(
+        identifier: *const c_char,
+        service: *const c_char,
+        characteristic: *const c_char,
+    ) -> i32;
+
+    /// Start a write of `len` bytes.
+    pub(crate) fn write_characteristic(
+        identifier: *const c_char,
+        service: *const c_char,
+        characteristic: *const c_char,
+        value: *const u8,
+        len: usize,
+        with_response: bool,
+    ) -> i32;
+
+    pub(crate) fn set_gatt_delegate(
+        on_gatt_result: NativeGattResultDelegate,
+        on_characteristic_read: NativeCharacteristicResultDelegate,
+        on_characteristic_write: NativeCharacteristicResultDelegate,
+    );
+
+    /// Enable or disable the notifications of a characteristic.
+    pub(crate) fn set_notify(
+        identifier: *const c_char,
+        service: *const c_char,
+        characteristic: *const c_char,
+        enabled: bool,
+    ) -> i32;
+
+    /// `on_notification_state` answers `set_notify`, `on_notification` carries the values.
+    pub(crate) fn set_notification_delegate(
+        on_notification_state: NativeCharacteristicResultDelegate,
+        on_notification: NativeCharacteristicResultDelegate,
+    );
+
+    /// `on_adapter_state` is called whenever the state of the adapter changes.
+    pub(crate) fn set_adapter_delegate(on_adapter_state: NativeAdapterStateHandler);
+
+    pub(crate) fn set_connection_delegate(on_connection_state: NativeConnectionStateHandler);
 }
 
+/// Device identifiers are matched whatever their case, see [`normalize_identifier`].
 pub(crate) trait BluetoothApi<R: Runtime> {
     fn echo(&self, value: String) -> String;
 
     fn initialize(&self);
 
-    fn start_scanning(&self) -> bool;
+    fn adapter_state(&self) -> AdapterState;
+
+    fn start_scanning(&self, options: ScanOptions) -> crate::Result<()>;
 
-    fn stop_scanning(&self) -> bool;
+    fn stop_scanning(&self) -> crate::Result<()>;
+
+    /// Scan with `options` for `duration`, alongside the other sessions, returns the session ID.
+    fn start_scan_session(&self, options: ScanOptions, duration: Duration) -> crate::Result<u32>;
+
+    /// End a session early, returns its summary if it was still running.
+    fn stop_scan_session(&self, session_id: u32) -> Option<ScanSummary>;
 
     fn set_passive_mode(&self, mode: bool);
 
-    fn connect_device(&self, identifier: String) -> bool;
+    fn connect_device(&self, identifier: String) -> crate::Result<()>;
+
+    fn disconnect_device(&self, identifier: String) -> crate::Result<()>;
+
+    /// Reconnect a device with `policy` whenever its connection drops, never if `None`.
+    fn set_reconnect_policy(
+        &self,
+        identifier: String,
+        policy: Option<ReconnectPolicy>,
+    ) -> crate::Result<()>;
+
+    fn read_rssi(&self, identifier: String) -> crate::Result<()>;
+
+    fn discover_services(&self, identifier: String) -> crate::Result<Vec<GattService>>;
+
+    fn discover_characteristics(
+        &self,
+        identifier: String,
+        service: String,
+    ) -> crate::Result<Vec<GattCharacteristic>>;
+
+    fn read_characteristic(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+    ) -> crate::Result<Vec<u8>>;
+
+    fn write_characteristic(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+        value: Vec<u8>,
+        with_response: bool,
+    ) -> crate::Result<()>;
+
+    fn subscribe(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+        encoding: ValueEncoding,
+        channel: Channel<Notification>,
+    ) -> crate::Result<()>;
+
+    fn unsubscribe(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+    ) -> crate::Result<bool>;
+
+    /// Read the Device Information service of a connected device, the characteristics that
+    /// can't be read are left `None`. Fails only when the device lacks the service.
+    fn read_device_info(&self, identifier: String) -> crate::Result<DeviceInformation>;
+
+    fn set_rssi_filter(
+        &self,
+        identifier: Option<String>,
+        config: RssiFilterConfig,
+    ) -> crate::Result<()>;
+
+    fn start_calibration(&self, identifier: String, reference_distance: f64) -> crate::Result<()>;
+
+    fn finish_calibration(&self, identifier: String) -> crate::Result<PathLossModel>;
+
+    fn cancel_calibration(&self, identifier: String) -> bool;
+
+    fn get_calibration(&self, identifier: String) -> Option<PathLossModel>;
+
+    fn list_devices(&self, filter: DeviceFilter) -> Vec<DeviceSnapshot>;
+
+    fn get_device(&self, identifier: String) -> Option<DeviceSnapshot>;
+
+    /// Trust a device and start monitoring its presence, the entry is saved with the calibration
+    /// and MAC address filled in when known.
+    fn add_trusted_device(&self, device: TrustedDevice) -> crate::Result<TrustedDevice>;
+
+    /// Forget a trusted device and stop monitoring it.
+    fn remove_trusted_device(&self, identifier: String) -> crate::Result<bool>;
+
+    fn list_trusted_devices(&self) -> Vec<TrustedDevice>;
 
-    fn disconnect_device(&self, identifier: String) -> bool;
+    /// How the presence of the trusted devices adds up, reported again if it flips.
+    fn set_presence_policy(&self, policy: PresencePolicy) -> crate::Result<()>;
 
-    fn read_rssi(&self, identifier: String);
+    /// The presence of the trusted devices added up under the presence policy.
+    fn get_presence(&self) -> AggregatePresence;
 
+    /// Add a delegate receiving every event, next to the ones already set.
     fn set_delegate<DELEGATE>(&self, delegate: DELEGATE)
     where
         DELEGATE: BLEDelegate + Sized + 'static;
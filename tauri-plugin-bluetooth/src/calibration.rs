@@ -0,0 +1,401 @@
+use crate::{store, Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Fewest readings kept per reference distance before a fit is attempted.
+const MIN_SAMPLES: usize = 5;
+
+/// Log-distance path loss model: `rssi = measured_power - 10 * n * log10(distance)`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PathLossModel {
+    /// RSSI at one meter, `A` on the Swift side.
+    pub measured_power: f64,
+    /// Path loss exponent `n`, 2.0 in free space and up to ~3.5 indoors.
+    pub path_loss_exponent: f64,
+    /// Standard deviation of the calibration readings around the fitted curve, in dB.
+    pub residual_std: f64,
+    pub calibrated: bool,
+}
+
+impl Default for PathLossModel {
+    /// The constants of the Swift side, with a spread covering the usual difference between
+    /// phones and watches.
+    fn default() -> Self {
+        PathLossModel {
+            measured_power: -54.0,
+            path_loss_exponent: 2.0,
+            residual_std: 6.0,
+            calibrated: false,
+        }
+    }
+}
+
+impl PathLossModel {
+    /// Distance in meters for an RSSI.
+    pub fn distance(&self, rssi: f64) -> f64 {
+        10f64.powf((self.measured_power - rssi) / (10.0 * self.path_loss_exponent))
+    }
+
+    /// Distance with a one sigma band, combining the reading variance and the fit residual.
+    pub fn estimate(&self, rssi: f64, variance: f64) -> DistanceEstimate {
+        let sigma = (variance.max(0.0) + self.residual_std.powi(2)).sqrt();
+        DistanceEstimate {
+            distance: self.distance(rssi),
+            min: self.distance(rssi + sigma),
+            max: self.distance(rssi - sigma),
+            calibrated: self.calibrated,
+        }
+    }
+
+    /// Reject a model giving no usable distance, the exponent must be positive.
+    pub fn validate(&self) -> Result<()> {
+        let finite = [
+            self.measured_power,
+            self.path_loss_exponent,
+            self.residual_std,
+        ]
+        .iter()
+        .all(|value| value.is_finite());
+        if !finite || self.path_loss_exponent <= 0.0 || self.residual_std < 0.0 {
+            return Err(Error::InvalidValue(format!(
+                "invalid path loss model {self:?}"
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Estimated distance in meters, the truth lies between `min` and `max` about two times out of three.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DistanceEstimate {
+    pub distance: f64,
+    pub min: f64,
+    pub max: f64,
+    /// Whether the device has been calibrated, the default model is used otherwise.
+    pub calibrated: bool,
+}
+
+#[derive(Default)]
+struct CalibrationSession {
+    reference_distance: f64,
+    samples: Vec<(f64, f64)>,
+}
+
+#[derive(Default)]
+struct CalibrationsInner {
+    path: Option<PathBuf>,
+    models: HashMap<String, PathLossModel>,
+    sessions: HashMap<String, CalibrationSession>,
+}
+
+/// Calibrated models per device, persisted as JSON, and the calibrations in progress.
+#[derive(Default)]
+pub struct Calibrations {
+    inner: Mutex<CalibrationsInner>,
+}
+
+impl Calibrations {
+    /// Use `path` for persistence and load the models stored there, see [`store::read`].
+    pub fn load(&self, path: PathBuf) -> Result<()> {
+        let models = store::read(&path)?;
+        let mut inner = self.lock();
+        inner.path = Some(path);
+        inner.models = models;
+        Ok(())
+    }
+
+    /// Start collecting readings of `identifier` taken at `reference_distance` meters.
+    ///
+    /// Calling it again for the same device adds another reference distance to the calibration,
+    /// which is needed to fit the path loss exponent as well.
+    pub fn start(&self, identifier: &str, reference_distance: f64) -> Result<()> {
+        if !(reference_distance.is_finite() && reference_distance > 0.0) {
+            return Err(Error::Calibration(format!(
+                "invalid reference distance {reference_distance}"
+            )));
+        }
+        let mut inner = self.lock();
+        let session = inner.sessions.entry(identifier.to_string()).or_default();
+        session.reference_distance = reference_distance;
+        Ok(())
+    }
+
+    /// Keep a reading if the device is being calibrated.
+    pub fn record(&self, identifier: &str, rssi: i32) {
+        let mut inner = self.lock();
+        if let Some(session) = inner.sessions.get_mut(identifier) {
+            session
+                .samples
+                .push((session.reference_distance, rssi as f64));
+        }
+    }
+
+    /// Fit the model over the collected readings, store it and end the calibration.
+    ///
+    /// The calibration goes on when the fit fails, so more readings can be added.
+    pub fn finish(&self, identifier: &str) -> Result<PathLossModel> {
+        let mut inner = self.lock();
+        let session = inner
+            .sessions
+            .get(identifier)
+            .ok_or_else(|| Error::Calibration(format!("{identifier} is not being calibrated")))?;
+        let exponent = inner
+            .models
+            .get(identifier)
+            .copied()
+            .unwrap_or_default()
+            .path_loss_exponent;
+        let model = fit(&session.samples, exponent)?;
+        let previous = inner.models.insert(identifier.to_string(), model);
+        if let Err(e) = save(&inner) {
+            restore(&mut inner.models, identifier, previous);
+            return Err(e);
+        }
+        inner.sessions.remove(identifier);
+        Ok(model)
+    }
+
+    pub fn cancel(&self, identifier: &str) -> bool {
+        self.lock().sessions.remove(identifier).is_some()
+    }
+
+    pub fn is_calibrating(&self, identifier: &str) -> bool {
+        self.lock().sessions.contains_key(identifier)
+    }
+
+    /// The calibrated model of a device.
+    pub fn model(&self, identifier: &str) -> Option<PathLossModel> {
+        self.lock().models.get(identifier).copied()
+    }
+
+    pub fn set_model(&self, identifier: &str, model: PathLossModel) -> Result<()> {
+        model.validate()?;
+        let mut inner = self.lock();
+        let previous = inner.models.insert(identifier.to_string(), model);
+        save(&inner).inspect_err(|_| restore(&mut inner.models, identifier, previous))
+    }
+
+    /// Distance estimate with the model of the device, or the default one.
+    pub fn estimate(&self, identifier: &str, rssi: f64, variance: f64) -> DistanceEstimate {
+        self.model(identifier)
+            .unwrap_or_default()
+            .estimate(rssi, variance)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, CalibrationsInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn save(inner: &CalibrationsInner) -> Result<()> {
+    match &inner.path {
+        Some(path) => store::write(path, &inner.models),
+        None => Ok(()),
+    }
+}
+
+/// Put back the model a failed save replaced.
+fn restore(
+    models: &mut HashMap<String, PathLossModel>,
+    identifier: &str,
+    previous: Option<PathLossModel>,
+) {
+    match previous {
+        Some(model) => models.insert(identifier.to_string(), model),
+        None => models.remove(identifier),
+    };
+}
+
+/// Least squares fit of `rssi = A + n * x` with `x = -10 * log10(distance)`.
+///
+/// A single reference distance only determines `A`, `n` is then kept at `exponent`.
+fn fit(samples: &[(f64, f64)], exponent: f64) -> Result<PathLossModel> {
+    let mut per_distance: HashMap<u64, usize> = HashMap::new();
+    for (distance, _) in samples {
+        *per_distance.entry(distance.to_bits()).or_default() += 1;
+    }
+    if per_distance.is_empty() || per_distance.values().any(|count| *count < MIN_SAMPLES) {
+        return Err(Error::Calibration(format!(
+            "at least {MIN_SAMPLES} readings are needed per reference distance"
+        )));
+    }
+
+    let points: Vec<(f64, f64)> = samples
+        .iter()
+        .map(|(distance, rssi)| (-10.0 * distance.log10(), *rssi))
+        .collect();
+    let count = points.len() as f64;
+    let mean_x = points.iter().map(|(x, _)| x).sum::<f64>() / count;
+    let mean_y = points.iter().map(|(_, y)| y).sum::<f64>() / count;
+
+    let path_loss_exponent = if per_distance.len() > 1 {
+        let covariance: f64 = points
+            .iter()
+            .map(|(x, y)| (x - mean_x) * (y - mean_y))
+            .sum();
+        let variance: f64 = points.iter().map(|(x, _)| (x - mean_x).powi(2)).sum();
+        let n = covariance / variance;
+        if !(n.is_finite() && n > 0.0) {
+            return Err(Error::Calibration(
+                "the readings don't get weaker with distance, retry with the distances further apart"
+                    .to_string(),
+            ));
+        }
+        n
+    } else {
+        exponent
+    };
+    let measured_power = mean_y - path_loss_exponent * mean_x;
+    let residual = points
+        .iter()
+        .map(|(x, y)| (y - (measured_power + path_loss_exponent * x)).powi(2))
+        .sum::<f64>()
+        / count;
+
+    Ok(PathLossModel {
+        measured_power,
+        path_loss_exponent,
+        residual_std: residual.sqrt(),
+        calibrated: true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Readings of a device following `rssi = a - 10 * n * log10(distance)` exactly.
+    fn readings(calibrations: &Calibrations, distance: f64, a: f64, n: f64, count: usize) {
+        calibrations.start("AA", distance).unwrap();
+        let rssi = (a - 10.0 * n * distance.log10()).round() as i32;
+        for _ in 0..count {
+            calibrations.record("AA", rssi);
+        }
+    }
+
+    fn close(actual: f64, expected: f64) -> bool {
+        (actual - expected).abs() < 1e-9
+    }
+
+    #[test]
+    fn a_single_distance_fits_the_measured_power() {
+        let samples: Vec<_> = [-61.0, -59.0, -60.0, -62.0, -58.0]
+            .into_iter()
+            .map(|rssi| (2.0, rssi))
+            .collect();
+        let model = fit(&samples, 2.5).unwrap();
+        // -60 at 2 m with n = 2.5: A = -60 + 25 * log10(2).
+        assert!(close(model.measured_power, -60.0 + 25.0 * 2f64.log10()));
+        assert!(close(model.path_loss_exponent, 2.5));
+        assert!(close(model.residual_std, 2f64.sqrt()));
+        assert!(model.calibrated);
+    }
+
+    #[test]
+    fn two_distances_fit_the_exponent_too() {
+        let calibrations = Calibrations::default();
+        readings(&calibrations, 1.0, -50.0, 3.0, 5);
+        readings(&calibrations, 10.0, -50.0, 3.0, 5);
+        let model = calibrations.finish("AA").unwrap();
+        assert!(close(model.measured_power, -50.0));
+        assert!(close(model.path_loss_exponent, 3.0));
+        assert!(close(model.residual_std, 0.0));
+        assert!(!calibrations.is_calibrating("AA"));
+        assert_eq!(calibrations.model("AA"), Some(model));
+        assert!(close(model.distance(-80.0), 10.0));
+    }
+
+    #[test]
+    fn readings_that_dont_decrease_are_rejected() {
+        let samples: Vec<_> = (0..5).flat_map(|_| [(1.0, -70.0), (4.0, -60.0)]).collect();
+        assert!(matches!(fit(&samples, 2.0), Err(Error::Calibration(_))));
+        let flat: Vec<_> = (0..5).flat_map(|_| [(1.0, -60.0), (4.0, -60.0)]).collect();
+        assert!(matches!(fit(&flat, 2.0), Err(Error::Calibration(_))));
+    }
+
+    #[test]
+    fn a_failed_fit_keeps_the_calibration_going() {
+        let calibrations = Calibrations::default();
+        readings(&calibrations, 1.0, -50.0, 2.0, MIN_SAMPLES - 1);
+        assert!(calibrations.finish("AA").is_err());
+        assert!(calibrations.is_calibrating("AA"));
+        assert_eq!(calibrations.model("AA"), None);
+
+        calibrations.record("AA", -50);
+        let model = calibrations.finish("AA").unwrap();
+        assert!(close(model.measured_power, -50.0));
+        assert!(calibrations.finish("AA").is_err());
+    }
+
+    #[test]
+    fn a_calibration_that_cant_be_saved_goes_on() {
+        let dir = std::env::temp_dir().join(format!(
+            "tauri-plugin-bluetooth-calibration-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("calibrations.json");
+        let calibrations = Calibrations::default();
+        calibrations.load(path.clone()).unwrap();
+        readings(&calibrations, 1.0, -50.0, 2.0, MIN_SAMPLES);
+        // a directory in the way of the file makes the save fail.
+        std::fs::create_dir_all(&path).unwrap();
+        assert!(matches!(calibrations.finish("AA"), Err(Error::Io(_))));
+        assert!(calibrations.is_calibrating("AA"));
+        assert_eq!(calibrations.model("AA"), None);
+        let model = PathLossModel::default();
+        assert!(matches!(
+            calibrations.set_model("AA", model),
+            Err(Error::Io(_))
+        ));
+        assert_eq!(calibrations.model("AA"), None);
+
+        std::fs::remove_dir(&path).unwrap();
+        let model = calibrations.finish("AA").unwrap();
+        let reloaded = Calibrations::default();
+        reloaded.load(path).unwrap();
+        assert_eq!(reloaded.model("AA"), Some(model));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn unusable_models_are_rejected() {
+        let calibrations = Calibrations::default();
+        let valid = PathLossModel::default();
+        let invalid = [
+            PathLossModel {
+                path_loss_exponent: 0.0,
+                ..valid
+            },
+            PathLossModel {
+                path_loss_exponent: -2.0,
+                ..valid
+            },
+            PathLossModel {
+                path_loss_exponent: f64::INFINITY,
+                ..valid
+            },
+            PathLossModel {
+                measured_power: f64::NAN,
+                ..valid
+            },
+            PathLossModel {
+                residual_std: -1.0,
+                ..valid
+            },
+        ];
+        for model in invalid {
+            assert!(matches!(
+                calibrations.set_model("AA", model),
+                Err(Error::InvalidValue(_))
+            ));
+        }
+        assert_eq!(calibrations.model("AA"), None);
+        calibrations.set_model("AA", valid).unwrap();
+        assert_eq!(calibrations.model("AA"), Some(valid));
+    }
+}
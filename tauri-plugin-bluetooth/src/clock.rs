@@ -0,0 +1,75 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// Source of time for the timeout driven parts of the plugin.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Instant;
+}
+
+/// The monotonic system clock.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves when told to, clones share the same time.
+#[derive(Debug, Clone)]
+pub struct ManualClock {
+    now: Arc<Mutex<Instant>>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        ManualClock {
+            now: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Default for ManualClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Instant {
+        *self.now.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Longest timeout or interval a configuration may ask for, a bit over 11 days: far enough for
+/// any use, and near enough that adding it to an [`Instant`] can't overflow.
+pub const MAX_SECONDS: f64 = 1_000_000.0;
+
+/// Check that `value` seconds, the `name` setting of a configuration, is a usable duration.
+pub(crate) fn check_seconds(name: &str, value: f64) -> crate::Result<()> {
+    if (0.0..=MAX_SECONDS).contains(&value) {
+        Ok(())
+    } else {
+        Err(crate::Error::InvalidValue(format!(
+            "{name} must be between 0 and {MAX_SECONDS} seconds, got {value}"
+        )))
+    }
+}
+
+/// `value` seconds as a duration, clamped to `0..=MAX_SECONDS`, NaN counting as 0.
+pub(crate) fn seconds(value: f64) -> Duration {
+    Duration::try_from_secs_f64(value.clamp(0.0, MAX_SECONDS)).unwrap_or_default()
+}
+
+/// Milliseconds since the Unix epoch, the timestamps handed to the webview.
+pub(crate) fn unix_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
@@ -1,12 +1,32 @@
+use crate::adapter::AdapterState;
+use crate::beacon::BeaconRegion;
 use crate::bridge::BluetoothApi;
+use crate::calibration::PathLossModel;
+use crate::events::BluetoothEvent;
+use crate::gatt::{GattCharacteristic, GattService};
 use crate::models::*;
+use crate::notifications::Notification;
+use crate::presence::{AggregatePresence, PresencePolicy};
+use crate::profiles::{self, DeviceInformation, ProfileValue};
+use crate::reconnect::ReconnectPolicy;
+use crate::recording::RecordingSummary;
+use crate::registry::{DeviceFilter, DeviceSnapshot};
+use crate::rssi::RssiFilterConfig;
+use crate::scan::ScanSummary;
+use crate::trusted::TrustedDevice;
 use crate::BluetoothExt;
 use crate::Result;
-use tauri::{command, AppHandle, Runtime};
+use std::path::PathBuf;
+use std::time::Duration;
+use tauri::ipc::{Channel, JavaScriptChannelId};
+use tauri::{command, AppHandle, Runtime, Webview};
+
+/// How long `connect`, `disconnect` and `wait_for_powered_on` wait when no timeout is given.
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[command]
 pub(crate) async fn echo<R: Runtime>(app: AppHandle<R>, data: EchoReq) -> Result<EchoResp> {
-    let val = data.value.unwrap_or(String::default());
+    let val = data.value.unwrap_or_default();
     let response = app.bluetooth().echo(val);
     Ok(EchoResp {
         value: Some(response),
@@ -14,21 +34,80 @@ pub(crate) async fn echo<R: Runtime>(app: AppHandle<R>, data: EchoReq) -> Result
 }
 
 #[command]
-pub(crate) async fn start_scanning<R: Runtime>(
+pub(crate) async fn get_adapter_state<R: Runtime>(app: AppHandle<R>) -> Result<AdapterState> {
+    Ok(app.bluetooth().adapter_state())
+}
+
+/// Wait up to `timeout` seconds for the adapter to be powered on, fails with the reason it isn't.
+#[command]
+pub(crate) async fn wait_for_powered_on<R: Runtime>(
     app: AppHandle<R>,
-    data: ConnectConf,
+    timeout: Option<f64>,
 ) -> Result<ConnectResp> {
-    let success = app.bluetooth().start_scanning();
-    Ok(ConnectResp { success })
+    let timeout = timeout_or_default(timeout)?;
+    app.bluetooth().wait_for_powered_on(timeout).await?;
+    Ok(ConnectResp { success: true })
 }
 
 #[command]
-pub(crate) async fn stop_scanning<R: Runtime>(
+pub(crate) async fn start_scanning<R: Runtime>(
     app: AppHandle<R>,
-    data: ConnectConf,
+    webview: Webview<R>,
+    options: Option<ScanOptions>,
+    channel: Option<JavaScriptChannelId>,
 ) -> Result<ConnectResp> {
-    let success = app.bluetooth().stop_scanning();
-    Ok(ConnectResp { success })
+    let bluetooth = app.bluetooth();
+    let options = options.unwrap_or_default();
+    match channel {
+        Some(channel) => bluetooth.start_scanning_with_channel(
+            options,
+            channel.channel_on::<R, BluetoothEvent>(webview),
+        )?,
+        None => bluetooth.start_scanning(options)?,
+    }
+    Ok(ConnectResp { success: true })
+}
+
+/// Open a scan session streaming the beacons of `region` to `channel`, every beacon if omitted.
+/// Returns the session ID, the session runs until `stop_scan_session`.
+#[command]
+pub(crate) async fn scan_beacons<R: Runtime>(
+    app: AppHandle<R>,
+    region: Option<BeaconRegion>,
+    channel: Channel<BluetoothEvent>,
+) -> Result<u32> {
+    app.bluetooth()
+        .scan_beacons(region.unwrap_or_default(), channel)
+}
+
+#[command]
+pub(crate) async fn stop_scanning<R: Runtime>(app: AppHandle<R>) -> Result<ConnectResp> {
+    // also drops the channels of the manual scan.
+    app.bluetooth().stop_scanning()?;
+    Ok(ConnectResp { success: true })
+}
+
+/// Scan for `duration` seconds alongside the other scans, the summary is emitted when it ends.
+#[command]
+pub(crate) async fn start_scan_session<R: Runtime>(
+    app: AppHandle<R>,
+    options: Option<ScanOptions>,
+    duration: f64,
+) -> Result<u32> {
+    let duration = Duration::try_from_secs_f64(duration)
+        .ok()
+        .filter(|duration| !duration.is_zero())
+        .ok_or_else(|| crate::Error::InvalidValue(format!("invalid duration {duration}")))?;
+    app.bluetooth()
+        .start_scan_session(options.unwrap_or_default(), duration)
+}
+
+#[command]
+pub(crate) async fn stop_scan_session<R: Runtime>(
+    app: AppHandle<R>,
+    session_id: u32,
+) -> Result<Option<ScanSummary>> {
+    Ok(app.bluetooth().stop_scan_session(session_id))
 }
 
 #[command]
@@ -45,7 +124,7 @@ pub(crate) async fn connect_device<R: Runtime>(
     app: AppHandle<R>,
     identifier: String,
 ) -> Result<ConnectResp> {
-    app.bluetooth().connect_device(identifier);
+    app.bluetooth().connect_device(identifier)?;
     Ok(ConnectResp { success: true })
 }
 
@@ -54,7 +133,54 @@ pub(crate) async fn disconnect_device<R: Runtime>(
     app: AppHandle<R>,
     identifier: String,
 ) -> Result<ConnectResp> {
-    app.bluetooth().disconnect_device(identifier);
+    app.bluetooth().disconnect_device(identifier)?;
+    Ok(ConnectResp { success: true })
+}
+
+/// Connect to `identifier` and wait for the connection, up to `timeout` seconds.
+#[command]
+pub(crate) async fn connect<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    timeout: Option<f64>,
+) -> Result<ConnectResp> {
+    let timeout = timeout_or_default(timeout)?;
+    app.bluetooth().connect(identifier, timeout).await?;
+    Ok(ConnectResp { success: true })
+}
+
+/// Disconnect from `identifier` and wait until the connection is closed, up to `timeout` seconds.
+#[command]
+pub(crate) async fn disconnect<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    timeout: Option<f64>,
+) -> Result<ConnectResp> {
+    let timeout = timeout_or_default(timeout)?;
+    app.bluetooth().disconnect(identifier, timeout).await?;
+    Ok(ConnectResp { success: true })
+}
+
+fn timeout_or_default(timeout: Option<f64>) -> Result<Duration> {
+    match timeout {
+        None => Ok(DEFAULT_TIMEOUT),
+        Some(timeout) if timeout.is_finite() && timeout > 0.0 => {
+            Ok(Duration::from_secs_f64(timeout))
+        }
+        Some(timeout) => Err(crate::Error::InvalidValue(format!(
+            "invalid timeout {timeout}"
+        ))),
+    }
+}
+
+/// Reconnect `identifier` whenever its connection drops, `policy` omitted to stop.
+#[command]
+pub(crate) async fn set_reconnect_policy<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    policy: Option<ReconnectPolicy>,
+) -> Result<ConnectResp> {
+    app.bluetooth().set_reconnect_policy(identifier, policy)?;
     Ok(ConnectResp { success: true })
 }
 
@@ -63,6 +189,1059 @@ pub(crate) async fn read_rssi<R: Runtime>(
     app: AppHandle<R>,
     identifier: String,
 ) -> Result<ConnectResp> {
-    app.bluetooth().read_rssi(identifier);
+    app.bluetooth().read_rssi(identifier)?;
     Ok(ConnectResp { success: true })
 }
+
+#[command]
+pub(crate) async fn discover_services<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<Vec<GattService>> {
+    // the discovery blocks until the peripheral answers.
+    tauri::async_runtime::spawn_blocking(move || app.bluetooth().discover_services(identifier))
+        .await
+        .map_err(|e| crate::Error::Gatt(e.to_string()))?
+}
+
+#[command]
+pub(crate) async fn discover_characteristics<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    service: String,
+) -> Result<Vec<GattCharacteristic>> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.bluetooth()
+            .discover_characteristics(identifier, service)
+    })
+    .await
+    .map_err(|e| crate::Error::Gatt(e.to_string()))?
+}
+
+#[command]
+pub(crate) async fn read_characteristic<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    service: String,
+    characteristic: String,
+    encoding: Option<ValueEncoding>,
+) -> Result<EncodedValue> {
+    let value = tauri::async_runtime::spawn_blocking(move || {
+        app.bluetooth()
+            .read_characteristic(identifier, service, characteristic)
+    })
+    .await
+    .map_err(|e| crate::Error::Gatt(e.to_string()))??;
+    Ok(EncodedValue::encode(value, encoding.unwrap_or_default()))
+}
+
+/// Read a standard characteristic and decode it, `service` defaults to the one defining it.
+#[command]
+pub(crate) async fn read_profile<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    service: Option<String>,
+    characteristic: String,
+) -> Result<ProfileValue> {
+    let service = match service.or_else(|| profiles::service_of(&characteristic).map(String::from))
+    {
+        Some(service) => service,
+        None => {
+            return Err(crate::Error::InvalidValue(format!(
+                "no decoder for characteristic {characteristic}"
+            )))
+        }
+    };
+    let uuid = characteristic.clone();
+    let value = tauri::async_runtime::spawn_blocking(move || {
+        app.bluetooth()
+            .read_characteristic(identifier, service, characteristic)
+    })
+    .await
+    .map_err(|e| crate::Error::Gatt(e.to_string()))??;
+    profiles::decode(&uuid, &value)
+}
+
+/// Read the Device Information service of a connected device and cache it in the registry.
+#[command]
+pub(crate) async fn read_device_info<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<DeviceInformation> {
+    tauri::async_runtime::spawn_blocking(move || app.bluetooth().read_device_info(identifier))
+        .await
+        .map_err(|e| crate::Error::Gatt(e.to_string()))?
+}
+
+#[command]
+pub(crate) async fn write_characteristic<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    service: String,
+    characteristic: String,
+    value: EncodedValue,
+    encoding: Option<ValueEncoding>,
+    with_response: Option<bool>,
+) -> Result<ConnectResp> {
+    let value = value.decode(encoding.unwrap_or_default())?;
+    tauri::async_runtime::spawn_blocking(move || {
+        app.bluetooth().write_characteristic(
+            identifier,
+            service,
+            characteristic,
+            value,
+            with_response.unwrap_or(true),
+        )
+    })
+    .await
+    .map_err(|e| crate::Error::Gatt(e.to_string()))??;
+    Ok(ConnectResp { success: true })
+}
+
+/// Enable the notifications of a characteristic and stream its values to `channel`.
+#[command]
+pub(crate) async fn subscribe<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    service: String,
+    characteristic: String,
+    encoding: Option<ValueEncoding>,
+    channel: Channel<Notification>,
+) -> Result<ConnectResp> {
+    tauri::async_runtime::spawn_blocking(move || {
+        app.bluetooth().subscribe(
+            identifier,
+            service,
+            characteristic,
+            encoding.unwrap_or_default(),
+            channel,
+        )
+    })
+    .await
+    .map_err(|e| crate::Error::Gatt(e.to_string()))??;
+    Ok(ConnectResp { success: true })
+}
+
+/// Close the channels of a characteristic, `success` is false when there were none.
+#[command]
+pub(crate) async fn unsubscribe<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    service: String,
+    characteristic: String,
+) -> Result<ConnectResp> {
+    let success = tauri::async_runtime::spawn_blocking(move || {
+        app.bluetooth()
+            .unsubscribe(identifier, service, characteristic)
+    })
+    .await
+    .map_err(|e| crate::Error::Gatt(e.to_string()))??;
+    Ok(ConnectResp { success })
+}
+
+#[command]
+pub(crate) async fn set_rssi_filter<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: Option<String>,
+    config: RssiFilterConfig,
+) -> Result<ConnectResp> {
+    app.bluetooth().set_rssi_filter(identifier, config)?;
+    Ok(ConnectResp { success: true })
+}
+
+#[command]
+pub(crate) async fn start_calibration<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+    reference_distance: f64,
+) -> Result<ConnectResp> {
+    app.bluetooth()
+        .start_calibration(identifier, reference_distance)?;
+    Ok(ConnectResp { success: true })
+}
+
+#[command]
+pub(crate) async fn finish_calibration<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<PathLossModel> {
+    app.bluetooth().finish_calibration(identifier)
+}
+
+#[command]
+pub(crate) async fn cancel_calibration<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<ConnectResp> {
+    let success = app.bluetooth().cancel_calibration(identifier);
+    Ok(ConnectResp { success })
+}
+
+#[command]
+pub(crate) async fn get_calibration<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<Option<PathLossModel>> {
+    Ok(app.bluetooth().get_calibration(identifier))
+}
+
+#[command]
+pub(crate) async fn list_devices<R: Runtime>(
+    app: AppHandle<R>,
+    filter: Option<DeviceFilter>,
+) -> Result<Vec<DeviceSnapshot>> {
+    Ok(app.bluetooth().list_devices(filter.unwrap_or_default()))
+}
+
+#[command]
+pub(crate) async fn get_device<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<Option<DeviceSnapshot>> {
+    Ok(app.bluetooth().get_device(identifier))
+}
+
+#[command]
+pub(crate) async fn add_trusted_device<R: Runtime>(
+    app: AppHandle<R>,
+    device: TrustedDevice,
+) -> Result<TrustedDevice> {
+    app.bluetooth().add_trusted_device(device)
+}
+
+#[command]
+pub(crate) async fn remove_trusted_device<R: Runtime>(
+    app: AppHandle<R>,
+    identifier: String,
+) -> Result<ConnectResp> {
+    let success = app.bluetooth().remove_trusted_device(identifier)?;
+    Ok(ConnectResp { success })
+}
+
+#[command]
+pub(crate) async fn list_trusted_devices<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Vec<TrustedDevice>> {
+    Ok(app.bluetooth().list_trusted_devices())
+}
+
+#[command]
+pub(crate) async fn set_presence_policy<R: Runtime>(
+    app: AppHandle<R>,
+    policy: PresencePolicy,
+) -> Result<ConnectResp> {
+    app.bluetooth().set_presence_policy(policy)?;
+    Ok(ConnectResp { success: true })
+}
+
+#[command]
+pub(crate) async fn get_presence<R: Runtime>(app: AppHandle<R>) -> Result<AggregatePresence> {
+    Ok(app.bluetooth().get_presence())
+}
+
+#[command]
+pub(crate) async fn start_recording<R: Runtime>(
+    app: AppHandle<R>,
+    name: Option<String>,
+) -> Result<PathBuf> {
+    app.bluetooth().start_recording(name)
+}
+
+#[command]
+pub(crate) async fn stop_recording<R: Runtime>(
+    app: AppHandle<R>,
+) -> Result<Option<RecordingSummary>> {
+    Ok(app.bluetooth().stop_recording())
+}
+
+#[command]
+pub(crate) async fn replay_recording<R: Runtime>(
+    app: AppHandle<R>,
+    name: String,
+    speed: Option<f64>,
+) -> Result<usize> {
+    let speed = speed.unwrap_or(1.0);
+    if !speed.is_finite() || speed < 0.0 {
+        return Err(crate::Error::InvalidValue(format!("invalid speed {speed}")));
+    }
+    // the replay keeps the pace of the recording.
+    tauri::async_runtime::spawn_blocking(move || app.bluetooth().replay_recording(&name, speed))
+        .await
+        .map_err(|e| crate::Error::Io(std::io::Error::other(e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actions::{ActionTrigger, PresenceAction, PresenceChange};
+    use crate::backend::{SimulatedBackend, VirtualPeripheral};
+    use crate::bridge::{BLEDelegate, Device};
+    use crate::gatt::CharacteristicProperties;
+    use crate::hub::EventFilter;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+    use tauri::async_runtime::block_on;
+    use tauri::ipc::InvokeResponseBody;
+    use tauri::test::{mock_builder, mock_context, noop_assets, MockRuntime};
+    use tauri::{App, Manager};
+
+    struct NoDelegate;
+
+    impl BLEDelegate for NoDelegate {
+        fn new_device(&self, _device: Device) {}
+        fn update_device(&self, _device: Device) {}
+        fn remove_device(&self, _device: Device) {}
+        fn update_rssi(&self, _rssi: i32, _estimated_rssi: i32, _active: bool) {}
+        fn update_presence(&self, _presence: bool, _reason: String) {}
+        fn bluetooth_power_warn(&self) {}
+    }
+
+    type Events = Arc<Mutex<Vec<BluetoothEvent>>>;
+
+    fn mock_app(backend: &SimulatedBackend) -> (App<MockRuntime>, Events) {
+        static APPS: AtomicUsize = AtomicUsize::new(0);
+        let name = format!("app-{}", APPS.fetch_add(1, Ordering::Relaxed));
+        mock_app_named(backend, &name)
+    }
+
+    /// A mock app whose data dir, where the trusted devices and calibrations are saved, is
+    /// shared with the other apps of the same `name` and emptied by [`remove_data_dir`].
+    fn mock_app_named(backend: &SimulatedBackend, name: &str) -> (App<MockRuntime>, Events) {
+        let mut context = mock_context(noop_assets());
+        context.config_mut().identifier =
+            format!("tauri-plugin-bluetooth-test-{}-{name}", std::process::id());
+        let app = mock_builder()
+            .plugin(crate::init_with_backend(NoDelegate, backend.clone()))
+            .build(context)
+            .expect("the plugin should initialize");
+        let events = Events::default();
+        let sink = events.clone();
+        app.bluetooth()
+            .add_listener(EventFilter::all(), move |event| {
+                sink.lock().unwrap().push(event.clone())
+            });
+        (app, events)
+    }
+
+    fn remove_data_dir(app: &App<MockRuntime>) {
+        let _ = std::fs::remove_dir_all(app.path().app_data_dir().unwrap());
+    }
+
+    fn take(events: &Events) -> Vec<BluetoothEvent> {
+        std::mem::take(&mut *events.lock().unwrap())
+    }
+
+    fn heart_rate_sensor(uuid: &str) -> VirtualPeripheral {
+        let notify = CharacteristicProperties {
+            notify: true,
+            ..Default::default()
+        };
+        let mut service = GattService::new("180D", true);
+        service
+            .characteristics
+            .push(GattCharacteristic::new("2A37", notify));
+        VirtualPeripheral::new(uuid).name("HRM").service(service)
+    }
+
+    #[test]
+    fn scanning_reports_the_peripherals_and_keeps_them_in_the_registry() {
+        let backend = SimulatedBackend::new();
+        let (app, events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA").name("Lamp").rssi(-50));
+        assert!(
+            take(&events).is_empty(),
+            "nothing is reported before a scan"
+        );
+
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        backend.add_peripheral(VirtualPeripheral::new("BB").rssi(-70));
+        let discovered: Vec<_> = take(&events)
+            .into_iter()
+            .filter_map(|event| match event {
+                BluetoothEvent::DeviceDiscovered(device) => Some(device.uuid),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(discovered, ["AA", "BB"]);
+
+        let devices = block_on(list_devices(app.handle().clone(), None)).unwrap();
+        assert_eq!(devices.len(), 2);
+        let lamp = block_on(get_device(app.handle().clone(), "AA".into()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(lamp.device.name.as_deref(), Some("Lamp"));
+        assert_eq!(lamp.device.rssi, -50);
+
+        backend.remove_peripheral("AA");
+        assert!(matches!(
+            take(&events).as_slice(),
+            [BluetoothEvent::DeviceLost(device)] if device.uuid == "AA"
+        ));
+        assert!(block_on(get_device(app.handle().clone(), "AA".into()))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn a_connection_reads_the_rssi_until_it_drops() {
+        let backend = SimulatedBackend::new();
+        let (app, events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA").rssi_curve(vec![-40, -45]));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+
+        let missing = block_on(connect(app.handle().clone(), "CC".into(), Some(1.0)));
+        assert_eq!(missing.unwrap_err().code(), "deviceNotFound");
+        let connected = block_on(connect(app.handle().clone(), "AA".into(), Some(1.0))).unwrap();
+        assert!(connected.success);
+        assert!(backend.is_connected("AA"));
+
+        take(&events);
+        backend.advance();
+        let rssi: Vec<_> = take(&events)
+            .into_iter()
+            .filter_map(|event| match event {
+                BluetoothEvent::Rssi(update) => Some((update.identifier, update.rssi)),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(rssi, [("AA".to_string(), -45)]);
+
+        assert!(backend.drop_connection("AA"));
+        assert!(matches!(
+            take(&events).as_slice(),
+            [BluetoothEvent::DeviceUpdated(device)]
+                if device.state.as_deref() == Some("disconnected")
+        ));
+        backend.advance();
+        assert!(
+            !take(&events)
+                .iter()
+                .any(|event| matches!(event, BluetoothEvent::Rssi(_))),
+            "no reading without a connection"
+        );
+        assert!(!backend.drop_connection("AA"));
+    }
+
+    /// Start connecting to a peripheral whose connections are held, returns once the attempt
+    /// reached the backend.
+    fn spawn_connect(
+        app: &App<MockRuntime>,
+        backend: &SimulatedBackend,
+        identifier: &str,
+        timeout: f64,
+    ) -> tauri::async_runtime::JoinHandle<Result<ConnectResp>> {
+        let connecting = tauri::async_runtime::spawn(connect(
+            app.handle().clone(),
+            identifier.into(),
+            Some(timeout),
+        ));
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while !backend.is_connecting(identifier) {
+            assert!(Instant::now() < deadline, "{identifier} is not connecting");
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        connecting
+    }
+
+    #[test]
+    fn a_timed_out_connect_leaves_the_others_waiting() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        backend.hold_connections(true);
+
+        let waiting = spawn_connect(&app, &backend, "AA", 5.0);
+        let timed_out = block_on(connect(app.handle().clone(), "AA".into(), Some(0.1)));
+        assert_eq!(timed_out.unwrap_err().code(), "timeout");
+        // the attempt goes on for the other caller.
+        assert!(backend.finish_connection("AA", None));
+        assert!(block_on(waiting).unwrap().unwrap().success);
+        assert!(backend.is_connected("AA"));
+    }
+
+    #[test]
+    fn a_connect_failing_to_start_leaves_the_others_waiting() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        backend.hold_connections(true);
+
+        let waiting = spawn_connect(&app, &backend, "AA", 5.0);
+        backend.set_connectable("AA", false);
+        let failed = block_on(connect(app.handle().clone(), "AA".into(), Some(5.0)));
+        assert_eq!(failed.unwrap_err().code(), "connection");
+        assert!(backend.finish_connection("AA", None));
+        assert!(block_on(waiting).unwrap().unwrap().success);
+    }
+
+    #[test]
+    fn passive_mode_gives_up_the_connections_and_attempts() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA"));
+        backend.add_peripheral(VirtualPeripheral::new("BB"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        block_on(connect(app.handle().clone(), "AA".into(), Some(1.0))).unwrap();
+        backend.hold_connections(true);
+        let connecting = spawn_connect(&app, &backend, "BB", 5.0);
+
+        block_on(set_passive_mode(app.handle().clone(), true)).unwrap();
+        assert!(!backend.is_connected("AA"));
+        assert!(!backend.is_connecting("BB"));
+        let error = block_on(connecting).unwrap().unwrap_err();
+        assert_eq!(error.to_string(), "could not connect to BB: passive mode");
+        // nothing is left to answer.
+        assert!(!backend.finish_connection("BB", None));
+    }
+
+    #[test]
+    fn a_failed_connection_reports_its_reason() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        backend.hold_connections(true);
+
+        let connecting = spawn_connect(&app, &backend, "AA", 5.0);
+        assert!(backend.finish_connection("AA", Some("peer removed pairing information")));
+        let error = block_on(connecting).unwrap().unwrap_err();
+        assert_eq!(error.code(), "connection");
+        assert_eq!(
+            error.to_string(),
+            "could not connect to AA: peer removed pairing information"
+        );
+        assert!(!backend.is_connected("AA"));
+    }
+
+    #[test]
+    fn waiting_for_the_adapter_follows_its_state() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        let powered_on = block_on(wait_for_powered_on(app.handle().clone(), Some(0.1)));
+        assert!(powered_on.unwrap().success);
+
+        backend.set_powered(false);
+        // the waiter checks the state once registered, it may start before or after the change.
+        let waiting =
+            tauri::async_runtime::spawn(wait_for_powered_on(app.handle().clone(), Some(5.0)));
+        backend.set_powered(true);
+        assert!(block_on(waiting).unwrap().unwrap().success);
+
+        backend.set_adapter_state(AdapterState::Unauthorized);
+        let unauthorized = block_on(wait_for_powered_on(app.handle().clone(), Some(0.1)));
+        assert_eq!(unauthorized.unwrap_err().code(), "unauthorized");
+    }
+
+    #[test]
+    fn device_info_leaves_out_the_characteristics_that_fail_to_read() {
+        let read = CharacteristicProperties {
+            read: true,
+            ..Default::default()
+        };
+        let mut service = GattService::new("180A", true);
+        for uuid in ["2A29", "2A24", "2A25"] {
+            service
+                .characteristics
+                .push(GattCharacteristic::new(uuid, read));
+        }
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(
+            VirtualPeripheral::new("AA")
+                .service(service)
+                .value("180A", "2A29", b"Acme".as_slice())
+                .value("180A", "2A24", b"L-1".as_slice())
+                .read_error("180A", "2A25", "insufficient authentication"),
+        );
+        backend.add_peripheral(heart_rate_sensor("BB"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        for identifier in ["AA", "BB"] {
+            block_on(connect(app.handle().clone(), identifier.into(), Some(1.0))).unwrap();
+        }
+
+        let info = block_on(read_device_info(app.handle().clone(), "AA".into())).unwrap();
+        assert_eq!(
+            info,
+            DeviceInformation {
+                manufacturer_name: Some("Acme".to_string()),
+                model_number: Some("L-1".to_string()),
+                ..Default::default()
+            }
+        );
+        let missing = block_on(read_device_info(app.handle().clone(), "BB".into()));
+        assert_eq!(missing.unwrap_err().code(), "gatt");
+    }
+
+    #[test]
+    fn subscriptions_stream_the_notified_values() {
+        let backend = SimulatedBackend::new();
+        let (app, events) = mock_app(&backend);
+        backend.add_peripheral(heart_rate_sensor("AA"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        block_on(connect(app.handle().clone(), "AA".into(), Some(1.0))).unwrap();
+
+        let received = Arc::new(Mutex::new(0));
+        let counter = received.clone();
+        let channel = Channel::new(move |_| {
+            *counter.lock().unwrap() += 1;
+            Ok(())
+        });
+        let subscribed = block_on(subscribe(
+            app.handle().clone(),
+            "AA".into(),
+            "180D".into(),
+            "2A37".into(),
+            None,
+            channel,
+        ))
+        .unwrap();
+        assert!(subscribed.success);
+
+        take(&events);
+        backend.notify("AA", "180D", "2A37", [0x00, 72]);
+        let notifications: Vec<_> = take(&events)
+            .into_iter()
+            .filter_map(|event| match event {
+                BluetoothEvent::Notification(notification) => Some(notification),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(notifications.len(), 1);
+        assert_eq!(notifications[0].value, EncodedValue::Bytes(vec![0x00, 72]));
+        assert!(matches!(
+            &notifications[0].profile,
+            Some(ProfileValue::HeartRate(measurement)) if measurement.bpm == 72
+        ));
+        assert_eq!(*received.lock().unwrap(), 1);
+
+        let unsubscribed = block_on(unsubscribe(
+            app.handle().clone(),
+            "AA".into(),
+            "180D".into(),
+            "2A37".into(),
+        ))
+        .unwrap();
+        assert!(unsubscribed.success);
+        backend.notify("AA", "180D", "2A37", [0x00, 80]);
+        assert!(take(&events).is_empty());
+        assert_eq!(*received.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn scan_channels_receive_the_devices_already_known() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA"));
+        let received = Arc::new(Mutex::new(0));
+        let channel = |received: &Arc<Mutex<u32>>| {
+            let counter = received.clone();
+            Channel::new(move |_| {
+                *counter.lock().unwrap() += 1;
+                Ok(())
+            })
+        };
+
+        // a scan failing to start drops its channel.
+        backend.set_powered(false);
+        let failed = app
+            .bluetooth()
+            .start_scanning_with_channel(ScanOptions::default(), channel(&received));
+        assert_eq!(failed.unwrap_err().code(), "adapterPoweredOff");
+        backend.set_powered(true);
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        assert_eq!(*received.lock().unwrap(), 0);
+        app.bluetooth().stop_scanning().unwrap();
+
+        backend.add_peripheral(VirtualPeripheral::new("BB"));
+        app.bluetooth()
+            .start_scanning_with_channel(ScanOptions::default(), channel(&received))
+            .unwrap();
+        // BB is reported while the scan starts.
+        assert_eq!(*received.lock().unwrap(), 1);
+        backend.add_peripheral(VirtualPeripheral::new("CC"));
+        assert_eq!(*received.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn scan_channels_only_receive_the_reports_of_their_session() {
+        let backend = SimulatedBackend::new();
+        let (app, events) = mock_app(&backend);
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        let channel = Channel::new(move |body| {
+            if let InvokeResponseBody::Json(json) = body {
+                sink.lock().unwrap().push(json);
+            }
+            Ok(())
+        });
+        let lamps = ScanOptions {
+            name_prefix: Some("lamp".to_string()),
+            ..Default::default()
+        };
+        app.bluetooth()
+            .start_scanning_with_channel(lamps, channel)
+            .unwrap();
+        block_on(start_scan_session(app.handle().clone(), None, 60.0)).unwrap();
+
+        backend.add_peripheral(VirtualPeripheral::new("AA").name("Lamp"));
+        backend.add_peripheral(VirtualPeripheral::new("BB").name("Fan"));
+        // the listeners get the reports of every session, the channel those of the manual one.
+        let discovered = take(&events)
+            .into_iter()
+            .filter(|event| matches!(event, BluetoothEvent::DeviceDiscovered(_)))
+            .count();
+        assert_eq!(discovered, 2);
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 1);
+        assert!(received[0].contains(r#""AA""#));
+    }
+
+    #[test]
+    fn beacons_stream_in_their_own_session() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        let mut ibeacon = vec![0x02, 0x15];
+        ibeacon.extend([0x11; 16]);
+        ibeacon.extend([0x00, 0x01, 0x00, 0x02, 0xC5]);
+        backend.add_peripheral(VirtualPeripheral::new("AA").manufacturer_data(0x004C, ibeacon));
+
+        let beacons = Arc::new(Mutex::new(0));
+        let counter = beacons.clone();
+        let channel = Channel::new(move |_| {
+            *counter.lock().unwrap() += 1;
+            Ok(())
+        });
+        let session_id = block_on(scan_beacons(app.handle().clone(), None, channel)).unwrap();
+        assert_ne!(session_id, crate::scan::MANUAL_SESSION);
+        assert!(backend.is_scanning());
+        assert_eq!(*beacons.lock().unwrap(), 1);
+
+        // the manual scan isn't running, stopping it leaves the beacon session alone.
+        block_on(stop_scanning(app.handle().clone())).unwrap();
+        assert!(backend.is_scanning());
+        backend.advance();
+        assert_eq!(*beacons.lock().unwrap(), 2);
+
+        let summary = block_on(stop_scan_session(app.handle().clone(), session_id))
+            .unwrap()
+            .unwrap();
+        assert_eq!(summary.devices.len(), 1);
+        assert!(!backend.is_scanning());
+        backend.advance();
+        assert_eq!(*beacons.lock().unwrap(), 2);
+    }
+
+    #[test]
+    fn timed_scan_sessions_end_once() {
+        let backend = SimulatedBackend::new();
+        let (app, events) = mock_app(&backend);
+        for duration in [0.0, -1.0, f64::NAN, f64::INFINITY, 1e300] {
+            let error = block_on(start_scan_session(app.handle().clone(), None, duration));
+            assert_eq!(error.unwrap_err().code(), "invalidValue");
+        }
+
+        let summaries = |events: &Events| -> Vec<u32> {
+            take(events)
+                .into_iter()
+                .filter_map(|event| match event {
+                    BluetoothEvent::ScanSummary(summary) => Some(summary.session_id),
+                    _ => None,
+                })
+                .collect()
+        };
+        let stopped = block_on(start_scan_session(app.handle().clone(), None, 5.0)).unwrap();
+        let timed = block_on(start_scan_session(app.handle().clone(), None, 0.1)).unwrap();
+        assert!(backend.is_scanning());
+        block_on(stop_scan_session(app.handle().clone(), stopped)).unwrap();
+        assert_eq!(summaries(&events), [stopped]);
+
+        // the expiry itself is tested on a manual clock in the scan module.
+        let deadline = Instant::now() + Duration::from_secs(5);
+        let expired = loop {
+            let expired = summaries(&events);
+            if !expired.is_empty() || Instant::now() >= deadline {
+                break expired;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        assert_eq!(expired, [timed]);
+        assert!(!backend.is_scanning());
+        let ended = block_on(stop_scan_session(app.handle().clone(), timed)).unwrap();
+        assert!(ended.is_none());
+    }
+
+    #[test]
+    fn identifiers_are_matched_whatever_their_case() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("aa-01"));
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        block_on(connect(app.handle().clone(), "aa-01".into(), Some(1.0))).unwrap();
+        assert!(backend.is_connected("AA-01"));
+        let device = block_on(get_device(app.handle().clone(), " Aa-01 ".into()))
+            .unwrap()
+            .unwrap();
+        assert_eq!(device.device.uuid, "AA-01");
+
+        let device: TrustedDevice = serde_json::from_str(r#"{"identifier": "aa-01"}"#).unwrap();
+        let trusted = block_on(add_trusted_device(app.handle().clone(), device)).unwrap();
+        assert_eq!(trusted.identifier, "AA-01");
+        let presence = block_on(get_presence(app.handle().clone())).unwrap();
+        assert_eq!(presence.monitored, 1);
+        let removed = block_on(remove_trusted_device(app.handle().clone(), "aA-01".into()));
+        assert!(removed.unwrap().success);
+        assert!(block_on(list_trusted_devices(app.handle().clone()))
+            .unwrap()
+            .is_empty());
+
+        remove_data_dir(&app);
+    }
+
+    #[test]
+    fn the_rust_api_matches_identifiers_whatever_their_case() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA-01"));
+        let bluetooth = app.bluetooth();
+        let received = Arc::new(Mutex::new(0));
+        let counter = received.clone();
+        bluetooth.add_listener(EventFilter::all().device("aa-01"), move |_| {
+            *counter.lock().unwrap() += 1
+        });
+        bluetooth.start_scanning(ScanOptions::default()).unwrap();
+        assert_eq!(*received.lock().unwrap(), 1);
+        block_on(bluetooth.connect("aa-01".into(), Duration::from_secs(1))).unwrap();
+        assert!(backend.is_connected("AA-01"));
+
+        let model = PathLossModel::default();
+        let device = TrustedDevice {
+            calibration: Some(model),
+            ..serde_json::from_str(r#"{"identifier": " aa-01"}"#).unwrap()
+        };
+        assert_eq!(
+            bluetooth.add_trusted_device(device).unwrap().identifier,
+            "AA-01"
+        );
+        assert_eq!(bluetooth.get_calibration("Aa-01".into()), Some(model));
+        assert_eq!(bluetooth.get_presence().monitored, 1);
+        assert!(bluetooth.remove_trusted_device("aa-01".into()).unwrap());
+        assert!(bluetooth.list_trusted_devices().is_empty());
+        assert_eq!(bluetooth.get_presence().monitored, 0);
+
+        remove_data_dir(&app);
+    }
+
+    struct SendChange(std::sync::mpsc::Sender<PresenceChange>);
+
+    impl PresenceAction for SendChange {
+        fn run(&self, change: &PresenceChange) -> Result<()> {
+            let _ = self.0.send(change.clone());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn presence_actions_follow_their_device_whatever_its_case() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        let bluetooth = app.bluetooth();
+        let device: TrustedDevice = serde_json::from_str(r#"{"identifier": "AA-01"}"#).unwrap();
+        bluetooth.add_trusted_device(device).unwrap();
+        let (sender, changes) = std::sync::mpsc::channel();
+        let trigger = ActionTrigger {
+            device: Some("aa-01".into()),
+            ..ActionTrigger::default()
+        };
+        bluetooth
+            .add_presence_action(trigger, Arc::new(SendChange(sender)))
+            .unwrap();
+
+        backend.set_powered(false);
+        let change = changes.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(change.identifier.as_deref(), Some("AA-01"));
+        assert!(!change.presence);
+        remove_data_dir(&app);
+    }
+
+    #[test]
+    fn trusted_devices_are_monitored_again_at_startup() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app_named(&backend, "resume");
+        remove_data_dir(&app);
+        let model = PathLossModel {
+            measured_power: -50.0,
+            ..PathLossModel::default()
+        };
+        let device = TrustedDevice {
+            calibration: Some(model),
+            ..serde_json::from_str(r#"{"identifier": "AA-01"}"#).unwrap()
+        };
+        block_on(add_trusted_device(app.handle().clone(), device)).unwrap();
+        drop(app);
+
+        let (app, _events) = mock_app_named(&backend, "resume");
+        let trusted = block_on(list_trusted_devices(app.handle().clone())).unwrap();
+        assert_eq!(trusted.len(), 1);
+        assert_eq!(trusted[0].identifier, "AA-01");
+        assert_eq!(trusted[0].calibration, Some(model));
+        let presence = block_on(get_presence(app.handle().clone())).unwrap();
+        assert_eq!(presence.monitored, 1);
+        remove_data_dir(&app);
+    }
+
+    #[test]
+    fn advertisements_feed_the_calibration() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        backend.add_peripheral(VirtualPeripheral::new("AA").rssi(-60));
+        let reference_distance = 2.0;
+        block_on(start_calibration(
+            app.handle().clone(),
+            "AA".into(),
+            reference_distance,
+        ))
+        .unwrap();
+        app.bluetooth()
+            .start_scanning(ScanOptions {
+                allow_duplicates: true,
+                ..Default::default()
+            })
+            .unwrap();
+        // the discovery and four more advertisements, never connected.
+        backend.advance_by(4);
+
+        let model = block_on(finish_calibration(app.handle().clone(), "AA".into())).unwrap();
+        assert!((model.distance(-60.0) - reference_distance).abs() < 1e-9);
+
+        remove_data_dir(&app);
+    }
+
+    #[test]
+    fn invalid_rssi_filters_are_rejected() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        let kalman = RssiFilterConfig {
+            stages: vec![crate::rssi::FilterConfig::Kalman {
+                process_noise: 0.0,
+                measurement_noise: 0.0,
+            }],
+        };
+        let error = block_on(set_rssi_filter(app.handle().clone(), None, kalman));
+        assert_eq!(error.unwrap_err().code(), "invalidValue");
+        let default = block_on(set_rssi_filter(
+            app.handle().clone(),
+            Some("aa".into()),
+            RssiFilterConfig::default(),
+        ));
+        assert!(default.unwrap().success);
+    }
+
+    #[test]
+    fn a_quorum_of_zero_is_rejected() {
+        let backend = SimulatedBackend::new();
+        let (app, _events) = mock_app(&backend);
+        let error = block_on(set_presence_policy(
+            app.handle().clone(),
+            PresencePolicy::Quorum(0),
+        ));
+        assert_eq!(error.unwrap_err().code(), "invalidValue");
+        let quorum = block_on(set_presence_policy(
+            app.handle().clone(),
+            PresencePolicy::Quorum(1),
+        ));
+        assert!(quorum.unwrap().success);
+        let presence = block_on(get_presence(app.handle().clone())).unwrap();
+        assert_eq!(presence.policy, PresencePolicy::Quorum(1));
+    }
+
+    #[test]
+    fn a_replay_reports_the_events_of_the_recording() {
+        let backend = SimulatedBackend::new();
+        let (app, events) = mock_app_named(&backend, "replay");
+        remove_data_dir(&app);
+        app.bluetooth()
+            .start_scanning(ScanOptions::default())
+            .unwrap();
+        block_on(start_recording(
+            app.handle().clone(),
+            Some("walk.jsonl".to_string()),
+        ))
+        .unwrap();
+        backend.add_peripheral(VirtualPeripheral::new("AA").name("Lamp").rssi(-50));
+        backend.advance();
+        backend.add_peripheral(VirtualPeripheral::new("BB").rssi(-70));
+        backend.advance();
+        backend.remove_peripheral("BB");
+        let summary = block_on(stop_recording(app.handle().clone()))
+            .unwrap()
+            .unwrap();
+        let recorded = serde_json::to_value(take(&events)).unwrap();
+        assert_eq!(summary.events, 6);
+
+        for speed in [0.0, 100.0] {
+            // reports reach the listeners while a scan runs, as when recording.
+            let (replay, replayed) = mock_app_named(&SimulatedBackend::new(), "replay");
+            replay
+                .bluetooth()
+                .start_scanning(ScanOptions::default())
+                .unwrap();
+            let count = block_on(replay_recording(
+                replay.handle().clone(),
+                "walk.jsonl".to_string(),
+                Some(speed),
+            ))
+            .unwrap();
+            assert_eq!(count, summary.events);
+            assert_eq!(
+                serde_json::to_value(take(&replayed)).unwrap(),
+                recorded,
+                "speed {speed}"
+            );
+        }
+        remove_data_dir(&app);
+    }
+
+    #[test]
+    fn each_instance_keeps_its_own_state() {
+        let first = SimulatedBackend::new();
+        let second = SimulatedBackend::new();
+        let (first_app, first_events) = mock_app(&first);
+        let (second_app, second_events) = mock_app(&second);
+        for app in [&first_app, &second_app] {
+            app.bluetooth()
+                .start_scanning(ScanOptions::default())
+                .unwrap();
+        }
+
+        first.add_peripheral(VirtualPeripheral::new("AA"));
+        assert_eq!(take(&first_events).len(), 1);
+        assert!(take(&second_events).is_empty());
+        let devices = block_on(list_devices(second_app.handle().clone(), None)).unwrap();
+        assert!(devices.is_empty());
+        let devices = block_on(list_devices(first_app.handle().clone(), None)).unwrap();
+        assert_eq!(devices.len(), 1);
+    }
+}
@@ -1,99 +1,1176 @@
-use crate::bridge;
-use crate::bridge::{set_delegate, BLEDelegate, BluetoothApi, Device};
-use once_cell::sync::OnceCell;
-use serde::de::DeserializeOwned;
-use std::ffi::{c_char, CStr, CString};
-use std::fmt::Debug;
-use tauri::{plugin::PluginApi, AppHandle, Runtime};
+use crate::actions::{ActionTrigger, PresenceAction, PresenceActions};
+use crate::adapter::AdapterState;
+use crate::backend::BluetoothBackend;
+use crate::beacon::{self, BeaconRegion, BeaconSighting};
+use crate::bridge::{normalize_identifier, BLEDelegate, BluetoothApi, Device};
+use crate::calibration::{Calibrations, PathLossModel};
+use crate::clock::{check_seconds, seconds, unix_millis};
+use crate::events::{BluetoothEvent, PresenceUpdate};
+use crate::gatt::{GattCharacteristic, GattService};
+use crate::hub::{EventFilter, EventHub, EventKind, ListenerId};
+use crate::notifications::{Notification, Subscriptions};
+use crate::pending::PendingRequests;
+use crate::presence::{AggregatePresence, Monitors, PresencePolicy, PresenceTransition};
+use crate::profiles::{DeviceInformation, DEVICE_INFORMATION_SERVICE};
+use crate::reconnect::{ReconnectPolicy, ReconnectUpdate, Reconnects};
+use crate::recording::{self, RecordedEvent, Recorder, RecordingSummary};
+use crate::registry::{DeviceFilter, DeviceRegistry, DeviceSnapshot};
+use crate::rssi::{RssiFilterConfig, RssiFilters, RssiUpdate};
+use crate::scan::{ScanSessions, ScanSummary, MANUAL_SESSION};
+use crate::trusted::{TrustedDevice, TrustedDevices};
+use crate::{Config, EncodedValue, Error, ScanOptions, ValueEncoding};
+#[cfg(native_bridge)]
+use std::ffi::{c_char, CStr};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::Receiver;
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
+use tauri::ipc::Channel;
+use tauri::{plugin::PluginApi, AppHandle, Manager, Runtime};
 
-pub(crate) static GLOBAL_BLE_DELEGATE: OnceCell<Option<Box<dyn BLEDelegate>>> = OnceCell::new();
+const CALIBRATIONS_FILE: &str = "bluetooth-calibrations.json";
+const TRUSTED_DEVICES_FILE: &str = "bluetooth-trusted-devices.json";
+/// Directory of the app data dir holding the recordings.
+const RECORDINGS_DIR: &str = "bluetooth-recordings";
+/// How often the due reconnections are checked.
+const RECONNECT_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the presence timeouts and RSSI reads of the monitored devices are checked.
+const MONITOR_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the debounced presence actions are checked.
+const ACTION_POLL_INTERVAL: Duration = Duration::from_millis(100);
+/// How often the timed scan sessions are checked.
+const SCAN_POLL_INTERVAL: Duration = Duration::from_millis(100);
 
-pub fn init<R: Runtime, C: DeserializeOwned, DELEGATE: BLEDelegate + Sized + 'static>(
+/// What an instance of the plugin keeps between calls, reached by its backend through a
+/// [`Dispatcher`].
+#[derive(Default)]
+pub(crate) struct State {
+    /// The listeners of every event: the delegate passed to `init`, the webview and the app
+    /// modules.
+    hub: EventHub,
+    rssi_filters: RssiFilters,
+    calibrations: Calibrations,
+    registry: DeviceRegistry,
+    subscriptions: Subscriptions,
+    reconnects: Reconnects,
+    /// Set once the reconnect worker is started, with the first reconnect policy.
+    reconnect_worker: AtomicBool,
+    trusted_devices: TrustedDevices,
+    /// The presence of the trusted devices.
+    monitors: Monitors,
+    /// Set once the monitor worker is started, with the first monitored device.
+    monitor_worker: AtomicBool,
+    /// Whether the native scan was started for the monitored devices since the adapter powered on.
+    monitor_scanning: AtomicBool,
+    /// Run when the user arrives or leaves, fed by a listener of the hub.
+    presence_actions: PresenceActions,
+    /// Set once the action worker is started, with the first presence action.
+    action_worker: AtomicBool,
+    /// Taps the events handed to the `dispatch_*` methods.
+    recorder: Recorder,
+    /// The running scan sessions, checked before a report is dispatched.
+    scan_sessions: ScanSessions,
+    /// Set once the scan worker is started, with the first timed scan session.
+    scan_worker: AtomicBool,
+    /// Callers of `wait_for_powered_on`, answered when the adapter is powered on.
+    powered_on_requests: PendingRequests<()>,
+    /// Connections and disconnections waiting for the backend, keyed by `connection_request_key`,
+    /// answered with the reason of a failure.
+    connection_requests: PendingRequests<Result<(), String>>,
+    /// Discoveries waiting for the Swift side, keyed by `gatt_request_key`, answered with the JSON
+    /// result or the error.
+    #[cfg(native_bridge)]
+    pub(crate) gatt_requests: PendingRequests<Result<String, String>>,
+    /// Channels handed to `start_scanning` and `scan_beacons`, they receive events until their
+    /// session ends.
+    scan_channels: Mutex<Vec<ScanChannel>>,
+}
+
+/// Handed to [`BluetoothBackend::initialize`] for the backend to report its events to its
+/// plugin instance, events reported once the plugin is dropped are ignored.
+#[derive(Clone)]
+pub struct Dispatcher(Weak<State>);
+
+impl Dispatcher {
+    pub(crate) fn state(&self) -> Option<Arc<State>> {
+        self.0.upgrade()
+    }
+}
+
+/// The plugin the Swift callbacks report to, set when the native backend initializes: the
+/// callbacks carry no context of their own.
+#[cfg(native_bridge)]
+static NATIVE_DISPATCHER: Mutex<Option<Dispatcher>> = Mutex::new(None);
+
+#[cfg(native_bridge)]
+pub(crate) fn set_native_dispatcher(dispatcher: Dispatcher) {
+    *NATIVE_DISPATCHER.lock().unwrap_or_else(|e| e.into_inner()) = Some(dispatcher);
+}
+
+/// Run `f` on the state of the plugin the native backend reports to, if it is still there.
+#[cfg(native_bridge)]
+pub(crate) fn with_native_state<T>(f: impl FnOnce(&State) -> T) -> Option<T> {
+    let dispatcher = NATIVE_DISPATCHER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone();
+    let state = dispatcher?.state()?;
+    Some(f(&state))
+}
+
+/// A channel receiving the device events, or the beacons of a region, while its scan session is
+/// open.
+struct ScanChannel {
+    session_id: u32,
+    channel: Channel<BluetoothEvent>,
+    region: Option<BeaconRegion>,
+}
+
+impl ScanChannel {
+    /// Whether to send `event` to the channel, `sessions` are the sessions a scan report is for
+    /// and `None` for the other events.
+    fn wants(&self, event: &BluetoothEvent, sessions: Option<&[u32]>) -> bool {
+        if sessions.is_some_and(|sessions| !sessions.contains(&self.session_id)) {
+            return false;
+        }
+        match (&self.region, event) {
+            (Some(region), BluetoothEvent::Beacon(sighting)) => {
+                region.matches(&sighting.beacon.frame)
+            }
+            (Some(_), _) => false,
+            (None, event) => event.is_device_event(),
+        }
+    }
+}
+
+pub fn init<
+    R: Runtime,
+    DELEGATE: BLEDelegate + Sized + 'static,
+    BACKEND: BluetoothBackend + 'static,
+>(
     app: &AppHandle<R>,
-    _api: PluginApi<R, C>,
+    api: PluginApi<R, Option<Config>>,
     delegate: DELEGATE,
+    backend: BACKEND,
 ) -> crate::Result<Bluetooth<R>> {
-    let bluetooth = Bluetooth(app.clone());
-
-    unsafe {
-        set_delegate(
-            on_device_new,
-            on_device_update,
-            on_device_removed,
-            on_rssi_updated,
-            presence_update,
-            bluetooth_power_warn,
-        );
-    }
+    let bluetooth = Bluetooth {
+        app: app.clone(),
+        backend: Arc::new(backend),
+        state: Arc::default(),
+    };
     bluetooth.set_delegate(delegate);
+    let event_app = app.clone();
+    bluetooth.state.hub.add(EventFilter::all(), move |event| {
+        let _ = event.emit(&event_app);
+    });
+    if let Some(ttl) = api.config().as_ref().and_then(|config| config.device_ttl) {
+        check_seconds("deviceTtl", ttl)?;
+        bluetooth.state.registry.set_ttl(seconds(ttl));
+    }
+    if let Some(policy) = api
+        .config()
+        .as_ref()
+        .and_then(|config| config.presence_policy)
+    {
+        policy.validate()?;
+        bluetooth.state.monitors.set_policy(policy);
+    }
+    // the hub belongs to the state, a strong reference would keep it alive.
+    let state = Arc::downgrade(&bluetooth.state);
+    bluetooth.state.hub.add(
+        EventFilter::all()
+            .kind(EventKind::Presence)
+            .kind(EventKind::AggregatePresence),
+        move |event| {
+            if let Some(state) = state.upgrade() {
+                state.presence_actions.observe(event);
+            }
+        },
+    );
+    for config in api
+        .config()
+        .iter()
+        .flat_map(|config| &config.presence_actions)
+    {
+        bluetooth.add_presence_action(config.trigger.clone(), config.build(app)?)?;
+    }
+    if let Ok(dir) = app.path().app_data_dir() {
+        bluetooth
+            .state
+            .calibrations
+            .load(dir.join(CALIBRATIONS_FILE))?;
+        bluetooth
+            .state
+            .trusted_devices
+            .load(dir.join(TRUSTED_DEVICES_FILE))?;
+    }
+    bluetooth.resume_monitoring();
     Ok(bluetooth)
 }
 
 /// Access to the bluetooth APIs.
-pub struct Bluetooth<R: Runtime>(AppHandle<R>);
+pub struct Bluetooth<R: Runtime> {
+    app: AppHandle<R>,
+    backend: Arc<dyn BluetoothBackend>,
+    state: Arc<State>,
+}
+
+impl<R: Runtime> Bluetooth<R> {
+    fn apply_scan_sessions(&self) -> crate::Result<()> {
+        self.state.apply_scan_sessions(&*self.backend)
+    }
+
+    /// Monitor the trusted devices stored by a previous run, a model that can't be saved is
+    /// still used for this run.
+    fn resume_monitoring(&self) {
+        for device in self.state.trusted_devices.list() {
+            // a calibration done after the device was trusted is the newer one.
+            if let Some(model) = device.calibration {
+                if self.state.calibrations.model(&device.identifier).is_none() {
+                    let identifier = &device.identifier;
+                    if let Err(e) = self.state.calibrations.set_model(identifier, model) {
+                        log::error!("failed to save the calibration of {identifier}: {e}");
+                    }
+                }
+            }
+            self.start_monitoring(&device);
+        }
+    }
+
+    fn start_monitoring(&self, device: &TrustedDevice) {
+        self.state
+            .monitors
+            .start(&device.identifier, device.presence.clone());
+        self.state.dispatch_aggregate_presence(None);
+        if self.state.monitor_worker.swap(true, Ordering::Relaxed) {
+            return;
+        }
+        let state = Arc::downgrade(&self.state);
+        let backend = self.backend.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(MONITOR_POLL_INTERVAL);
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            state.monitor_due(&*backend);
+        });
+    }
+
+    fn end_scan_session(&self, session_id: u32) -> Option<ScanSummary> {
+        self.state.end_scan_session(&*self.backend, session_id)
+    }
+}
+
+/// The work of the background workers, which only hold the state weakly and end with it.
+impl State {
+    /// Run the native scan with the options of the open sessions, stop it once none is left and
+    /// no device is monitored.
+    fn apply_scan_sessions(&self, backend: &dyn BluetoothBackend) -> crate::Result<()> {
+        if !self.monitors.is_empty() {
+            // the monitored devices are heard through every advertisement, whatever they offer.
+            backend.start_scanning(&ScanOptions::default())
+        } else if self.scan_sessions.is_empty() {
+            backend.stop_scanning()
+        } else {
+            backend.start_scanning(&self.scan_sessions.native_options())
+        }
+    }
+
+    /// Connect to the devices whose reconnection is due.
+    fn reconnect_due(&self, backend: &dyn BluetoothBackend) {
+        let (updates, due) = self.reconnects.poll();
+        updates
+            .into_iter()
+            .for_each(|update| self.dispatch_reconnect(update));
+        // a refused connection counts as failed once the attempt times out.
+        for identifier in due {
+            let _ = backend.connect_device(&identifier);
+        }
+    }
+
+    /// Fire the presence timeouts, and read the RSSI of the monitored devices in active mode.
+    fn monitor_due(&self, backend: &dyn BluetoothBackend) {
+        if self.monitors.is_empty() {
+            return;
+        }
+        let scanning = &self.monitor_scanning;
+        if !scanning.load(Ordering::Relaxed) && backend.adapter_state().is_powered_on() {
+            scanning.store(self.apply_scan_sessions(backend).is_ok(), Ordering::Relaxed);
+        }
+        let (transitions, due) = self.monitors.poll();
+        for (identifier, transition) in transitions {
+            self.dispatch_device_presence(&identifier, transition);
+        }
+        for identifier in due {
+            match self.registry.get(&identifier) {
+                Some(snapshot) if is_connected(&snapshot.device) => {
+                    let _ = backend.read_rssi(&identifier);
+                }
+                // only a connected device can be read, the reads start once it is.
+                Some(_) => {
+                    let _ = backend
+                        .adapter_state()
+                        .check()
+                        .and_then(|()| backend.connect_device(&identifier));
+                }
+                None => {}
+            }
+        }
+    }
+
+    fn end_scan_session(
+        &self,
+        backend: &dyn BluetoothBackend,
+        session_id: u32,
+    ) -> Option<ScanSummary> {
+        let summary = self.scan_sessions.end(session_id)?;
+        self.lock_scan_channels()
+            .retain(|scan| scan.session_id != session_id);
+        let _ = self.apply_scan_sessions(backend);
+        self.emit(BluetoothEvent::ScanSummary(summary.clone()));
+        Some(summary)
+    }
+}
 
 impl<R: Runtime> BluetoothApi<R> for Bluetooth<R> {
     fn echo(&self, value: String) -> String {
-        unsafe {
-            let value = CString::new(value.as_str()).unwrap();
-            let ret = bridge::echo(value.as_ptr());
-            CStr::from_ptr(ret).to_str().unwrap().to_string()
-        }
+        self.backend.echo(&value)
     }
 
     fn initialize(&self) {
-        unsafe {
-            bridge::initialize();
-        }
+        self.backend
+            .initialize(Dispatcher(Arc::downgrade(&self.state)));
     }
 
-    fn start_scanning(&self) -> bool {
-        unsafe { bridge::start_scanning() }
+    fn adapter_state(&self) -> AdapterState {
+        self.backend.adapter_state()
+    }
+
+    fn start_scanning(&self, options: ScanOptions) -> crate::Result<()> {
+        self.adapter_state().check()?;
+        self.state.scan_sessions.start_manual(options)?;
+        self.apply_scan_sessions().inspect_err(|_| {
+            self.state.scan_sessions.end(MANUAL_SESSION);
+            let _ = self.apply_scan_sessions();
+        })
+    }
+
+    fn stop_scanning(&self) -> crate::Result<()> {
+        // the native scan keeps running for the timed sessions still open.
+        self.end_scan_session(MANUAL_SESSION);
+        Ok(())
+    }
+
+    fn start_scan_session(&self, options: ScanOptions, duration: Duration) -> crate::Result<u32> {
+        self.adapter_state().check()?;
+        let session_id = self.state.scan_sessions.start(options, Some(duration))?;
+        if let Err(error) = self.apply_scan_sessions() {
+            self.state.scan_sessions.end(session_id);
+            let _ = self.apply_scan_sessions();
+            return Err(error);
+        }
+        if self.state.scan_worker.swap(true, Ordering::Relaxed) {
+            return Ok(session_id);
+        }
+        // ends the sessions that are due, a session stopped early is gone by then.
+        let state = Arc::downgrade(&self.state);
+        let backend = self.backend.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(SCAN_POLL_INTERVAL);
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            for session_id in state.scan_sessions.expired() {
+                state.end_scan_session(&*backend, session_id);
+            }
+        });
+        Ok(session_id)
     }
 
-    fn stop_scanning(&self) -> bool {
-        unsafe { bridge::stop_scanning() }
+    fn stop_scan_session(&self, session_id: u32) -> Option<ScanSummary> {
+        self.end_scan_session(session_id)
     }
 
     fn set_passive_mode(&self, mode: bool) {
-        unsafe { bridge::set_passive_mode(mode) }
+        self.state.monitors.set_passive_mode(mode);
+        self.backend.set_passive_mode(mode)
+    }
+
+    fn connect_device(&self, identifier: String) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        self.adapter_state().check()?;
+        self.backend.connect_device(&identifier)
+    }
+
+    fn disconnect_device(&self, identifier: String) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        // also cancels a reconnection in progress.
+        self.state.reconnects.disconnecting(&identifier);
+        self.backend.disconnect_device(&identifier)?;
+        // the backend forgets the notifications of a device disconnected on purpose.
+        self.state.subscriptions.remove_device(&identifier);
+        Ok(())
     }
 
-    fn connect_device(&self, identifier: String) -> bool {
-        unsafe {
-            let value = CString::new(identifier.as_str()).unwrap();
-            bridge::connect_device(value.as_ptr())
+    fn set_reconnect_policy(
+        &self,
+        identifier: String,
+        policy: Option<ReconnectPolicy>,
+    ) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        if let Some(policy) = &policy {
+            policy.validate()?;
         }
+        let connected = self
+            .state
+            .registry
+            .get(&identifier)
+            .is_some_and(|snapshot| is_connected(&snapshot.device));
+        self.state
+            .reconnects
+            .set_policy(&identifier, policy, connected);
+        if self.state.reconnect_worker.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let state = Arc::downgrade(&self.state);
+        let backend = self.backend.clone();
+        std::thread::spawn(move || loop {
+            std::thread::sleep(RECONNECT_POLL_INTERVAL);
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            state.reconnect_due(&*backend);
+        });
+        Ok(())
+    }
+
+    fn read_rssi(&self, identifier: String) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        self.backend.read_rssi(&identifier)
+    }
+
+    fn discover_services(&self, identifier: String) -> crate::Result<Vec<GattService>> {
+        let identifier = normalize_identifier(&identifier);
+        self.backend.discover_services(&identifier)
+    }
+
+    fn discover_characteristics(
+        &self,
+        identifier: String,
+        service: String,
+    ) -> crate::Result<Vec<GattCharacteristic>> {
+        let identifier = normalize_identifier(&identifier);
+        self.backend.discover_characteristics(&identifier, &service)
+    }
+
+    fn read_characteristic(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+    ) -> crate::Result<Vec<u8>> {
+        let identifier = normalize_identifier(&identifier);
+        self.backend
+            .read_characteristic(&identifier, &service, &characteristic)
+    }
+
+    fn write_characteristic(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+        value: Vec<u8>,
+        with_response: bool,
+    ) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        self.backend.write_characteristic(
+            &identifier,
+            &service,
+            &characteristic,
+            &value,
+            with_response,
+        )
+    }
+
+    fn subscribe(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+        encoding: ValueEncoding,
+        channel: Channel<Notification>,
+    ) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        self.backend
+            .set_notify(&identifier, &service, &characteristic, true)?;
+        self.state
+            .subscriptions
+            .add(&identifier, &service, &characteristic, encoding, channel);
+        Ok(())
+    }
+
+    fn unsubscribe(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+    ) -> crate::Result<bool> {
+        let identifier = normalize_identifier(&identifier);
+        let removed = self
+            .state
+            .subscriptions
+            .remove(&identifier, &service, &characteristic);
+        match self
+            .backend
+            .set_notify(&identifier, &service, &characteristic, false)
+        {
+            // nothing to turn off on a peripheral that went away.
+            Ok(()) | Err(Error::NotConnected(_)) => Ok(removed),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read_device_info(&self, identifier: String) -> crate::Result<DeviceInformation> {
+        let identifier = normalize_identifier(&identifier);
+        let services = self.backend.discover_services(&identifier)?;
+        if !services
+            .iter()
+            .any(|service| crate::gatt::same_uuid(&service.uuid, DEVICE_INFORMATION_SERVICE))
+        {
+            return Err(Error::Gatt(format!(
+                "{identifier} has no Device Information service"
+            )));
+        }
+        let characteristics = self
+            .backend
+            .discover_characteristics(&identifier, DEVICE_INFORMATION_SERVICE)?;
+        let mut info = DeviceInformation::default();
+        for characteristic in characteristics.iter().filter(|characteristic| {
+            characteristic.properties.read
+                && DeviceInformation::CHARACTERISTICS
+                    .iter()
+                    .any(|uuid| crate::gatt::same_uuid(uuid, &characteristic.uuid))
+        }) {
+            // a characteristic that can't be read is left out, like one the device lacks.
+            let Ok(value) = self.backend.read_characteristic(
+                &identifier,
+                DEVICE_INFORMATION_SERVICE,
+                &characteristic.uuid,
+            ) else {
+                continue;
+            };
+            info.set(&characteristic.uuid, &value);
+        }
+        self.state.registry.record_device_info(&identifier, &info);
+        Ok(info)
+    }
+
+    fn set_rssi_filter(
+        &self,
+        identifier: Option<String>,
+        config: RssiFilterConfig,
+    ) -> crate::Result<()> {
+        let identifier = identifier.as_deref().map(normalize_identifier);
+        config.validate()?;
+        self.state
+            .rssi_filters
+            .configure(identifier.as_deref(), config);
+        Ok(())
+    }
+
+    fn start_calibration(&self, identifier: String, reference_distance: f64) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        self.state
+            .calibrations
+            .start(&identifier, reference_distance)
+    }
+
+    fn finish_calibration(&self, identifier: String) -> crate::Result<PathLossModel> {
+        let identifier = normalize_identifier(&identifier);
+        self.state.calibrations.finish(&identifier)
+    }
+
+    fn cancel_calibration(&self, identifier: String) -> bool {
+        let identifier = normalize_identifier(&identifier);
+        self.state.calibrations.cancel(&identifier)
+    }
+
+    fn get_calibration(&self, identifier: String) -> Option<PathLossModel> {
+        let identifier = normalize_identifier(&identifier);
+        self.state.calibrations.model(&identifier)
     }
 
-    fn disconnect_device(&self, identifier: String) -> bool {
-        unsafe {
-            let value = CString::new(identifier.as_str()).unwrap();
-            bridge::disconnect_device(value.as_ptr())
+    fn list_devices(&self, filter: DeviceFilter) -> Vec<DeviceSnapshot> {
+        self.state.evict_stale_devices();
+        self.state.registry.list(&filter)
+    }
+
+    fn get_device(&self, identifier: String) -> Option<DeviceSnapshot> {
+        let identifier = normalize_identifier(&identifier);
+        self.state.evict_stale_devices();
+        self.state.registry.get(&identifier)
+    }
+
+    fn add_trusted_device(&self, mut device: TrustedDevice) -> crate::Result<TrustedDevice> {
+        device.identifier = normalize_identifier(&device.identifier);
+        device.presence.validate()?;
+        let calibration = device.calibration;
+        match calibration {
+            Some(model) => model.validate()?,
+            None => device.calibration = self.state.calibrations.model(&device.identifier),
+        }
+        if device.mac_addr.is_none() {
+            device.mac_addr = self
+                .state
+                .registry
+                .get(&device.identifier)
+                .and_then(|snapshot| snapshot.device.mac_addr);
         }
+        let previous = self.state.trusted_devices.get(&device.identifier);
+        self.state.trusted_devices.add(device.clone())?;
+        if let Some(model) = calibration {
+            if let Err(error) = self.state.calibrations.set_model(&device.identifier, model) {
+                // the device stays as it was, like the calibration.
+                let _ = match previous {
+                    Some(previous) => self.state.trusted_devices.add(previous),
+                    None => self
+                        .state
+                        .trusted_devices
+                        .remove(&device.identifier)
+                        .map(drop),
+                };
+                return Err(error);
+            }
+        }
+        self.start_monitoring(&device);
+        Ok(device)
     }
 
-    fn read_rssi(&self, identifier: String) {
-        unsafe {
-            let value = CString::new(identifier.as_str()).unwrap();
-            bridge::read_rssi(value.as_ptr())
+    fn remove_trusted_device(&self, identifier: String) -> crate::Result<bool> {
+        let identifier = normalize_identifier(&identifier);
+        let removed = self.state.trusted_devices.remove(&identifier)?;
+        if self.state.monitors.stop(&identifier) {
+            self.state.dispatch_aggregate_presence(None);
+            if self.state.monitors.is_empty() {
+                self.state.monitor_scanning.store(false, Ordering::Relaxed);
+                let _ = self.apply_scan_sessions();
+            }
         }
+        Ok(removed)
+    }
+
+    fn list_trusted_devices(&self) -> Vec<TrustedDevice> {
+        self.state
+            .trusted_devices
+            .list()
+            .into_iter()
+            .map(|mut device| {
+                device.calibration = self
+                    .state
+                    .calibrations
+                    .model(&device.identifier)
+                    .or(device.calibration);
+                device
+            })
+            .collect()
+    }
+
+    fn set_presence_policy(&self, policy: PresencePolicy) -> crate::Result<()> {
+        policy.validate()?;
+        self.state.monitors.set_policy(policy);
+        self.state.dispatch_aggregate_presence(None);
+        Ok(())
+    }
+
+    fn get_presence(&self) -> AggregatePresence {
+        self.state.monitors.aggregate()
     }
 
     fn set_delegate<DELEGATE>(&self, delegate: DELEGATE)
     where
         DELEGATE: BLEDelegate + Sized + 'static,
     {
-        // binding the new bluetooth event delegate.
-        let _ = GLOBAL_BLE_DELEGATE.set(Some(Box::new(delegate)));
+        self.add_delegate(delegate);
+    }
+}
+
+impl<R: Runtime> Bluetooth<R> {
+    /// Call `listener` with the events passing `filter` until it is removed.
+    pub fn add_listener<F: Fn(&BluetoothEvent) + Send + Sync + 'static>(
+        &self,
+        filter: EventFilter,
+        listener: F,
+    ) -> ListenerId {
+        self.state.hub.add(filter, listener)
+    }
+
+    /// Add a delegate next to the one passed to `init`, until it is removed.
+    pub fn add_delegate<DELEGATE: BLEDelegate + 'static>(&self, delegate: DELEGATE) -> ListenerId {
+        self.state.hub.add_delegate(delegate)
+    }
+
+    /// Run `action` when the presence followed by `trigger` changes, next to the actions of the
+    /// plugin configuration.
+    pub fn add_presence_action(
+        &self,
+        mut trigger: ActionTrigger,
+        action: Arc<dyn PresenceAction>,
+    ) -> crate::Result<()> {
+        trigger.validate()?;
+        trigger.device = trigger.device.as_deref().map(normalize_identifier);
+        self.state.presence_actions.add(trigger, action);
+        if self.state.action_worker.swap(true, Ordering::Relaxed) {
+            return Ok(());
+        }
+        let state = Arc::downgrade(&self.state);
+        std::thread::spawn(move || loop {
+            std::thread::sleep(ACTION_POLL_INTERVAL);
+            let Some(state) = state.upgrade() else {
+                return;
+            };
+            for (action, change) in state.presence_actions.poll() {
+                // a failed action is not retried, the next change runs it again.
+                std::thread::spawn(move || action.run(&change));
+            }
+        });
+        Ok(())
+    }
+
+    /// Record the events reported by the backend to the JSONL file `name`, by default a new file,
+    /// in the recordings directory of the app data dir. Returns the path recorded to.
+    pub fn start_recording(&self, name: Option<String>) -> crate::Result<PathBuf> {
+        let name = name.unwrap_or_else(|| format!("bluetooth-{}.jsonl", unix_millis()));
+        let path = recording::recording_path(&self.recordings_dir()?, &name)?;
+        self.state.recorder.start(path.clone())?;
+        Ok(path)
+    }
+
+    pub fn stop_recording(&self) -> Option<RecordingSummary> {
+        self.state.recorder.stop()
+    }
+
+    /// Feed the recording `name` back through the plugin at `speed` times the original pace, see
+    /// [`recording::replay`]. Blocks until done, returns the number of events replayed.
+    pub fn replay_recording(&self, name: &str, speed: f64) -> crate::Result<usize> {
+        let path = recording::recording_path(&self.recordings_dir()?, name)?;
+        let lines = recording::read_recording(&path)?;
+        Ok(recording::replay(lines, speed, &self.state))
+    }
+
+    fn recordings_dir(&self) -> crate::Result<PathBuf> {
+        Ok(self
+            .app
+            .path()
+            .app_data_dir()
+            .map_err(|e| Error::Io(std::io::Error::other(e)))?
+            .join(RECORDINGS_DIR))
+    }
+
+    /// Start the manual scan and stream device events to `channel` while it runs. The channel is
+    /// added first, the devices already known are reported as soon as the scan starts.
+    pub(crate) fn start_scanning_with_channel(
+        &self,
+        options: ScanOptions,
+        channel: Channel<BluetoothEvent>,
+    ) -> crate::Result<()> {
+        let channel_id = channel.id();
+        self.state.lock_scan_channels().push(ScanChannel {
+            session_id: MANUAL_SESSION,
+            channel,
+            region: None,
+        });
+        self.start_scanning(options).inspect_err(|_| {
+            self.state.lock_scan_channels().retain(|scan| {
+                scan.session_id != MANUAL_SESSION || scan.channel.id() != channel_id
+            });
+        })
+    }
+
+    /// Open a scan session streaming the beacons of `region` to `channel`, returns its ID. The
+    /// session runs until [`BluetoothApi::stop_scan_session`].
+    pub fn scan_beacons(
+        &self,
+        region: BeaconRegion,
+        channel: Channel<BluetoothEvent>,
+    ) -> crate::Result<u32> {
+        self.adapter_state().check()?;
+        let session_id = self
+            .state
+            .scan_sessions
+            .start(ScanOptions::default(), None)?;
+        // in place before the scan starts, for the devices it reports right away.
+        self.state.lock_scan_channels().push(ScanChannel {
+            session_id,
+            channel,
+            region: Some(region),
+        });
+        if let Err(error) = self.apply_scan_sessions() {
+            self.state.scan_sessions.end(session_id);
+            self.state
+                .lock_scan_channels()
+                .retain(|scan| scan.session_id != session_id);
+            let _ = self.apply_scan_sessions();
+            return Err(error);
+        }
+        Ok(session_id)
+    }
+
+    /// Remove a listener or a delegate, returns whether it was still there.
+    pub fn remove_listener(&self, id: ListenerId) -> bool {
+        self.state.hub.remove(id)
+    }
+
+    /// Connect to a device, resolves once it is connected or the connection failed.
+    ///
+    /// The attempt is cancelled if it takes longer than `timeout`.
+    pub async fn connect(&self, identifier: String, timeout: Duration) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        let key = connection_request_key(true, &identifier);
+        let (token, receiver) = self.state.connection_requests.register(&key);
+        if let Err(error) = self.connect_device(identifier.clone()) {
+            self.state.connection_requests.cancel(&key, token);
+            return Err(error);
+        }
+        match wait_request(receiver, timeout).await {
+            Some(Ok(())) => Ok(()),
+            Some(Err(reason)) => Err(Error::Connection(format!("{identifier}: {reason}"))),
+            None => {
+                // the attempt goes on for the callers still waiting on it.
+                if !self.state.connection_requests.cancel(&key, token) {
+                    let _ = self.backend.disconnect_device(&identifier);
+                }
+                Err(Error::Timeout(identifier))
+            }
+        }
+    }
+
+    /// Disconnect from a device, resolves once the connection is closed.
+    pub async fn disconnect(&self, identifier: String, timeout: Duration) -> crate::Result<()> {
+        let identifier = normalize_identifier(&identifier);
+        let key = connection_request_key(false, &identifier);
+        let (token, receiver) = self.state.connection_requests.register(&key);
+        if let Err(error) = self.disconnect_device(identifier.clone()) {
+            self.state.connection_requests.cancel(&key, token);
+            return Err(error);
+        }
+        match wait_request(receiver, timeout).await {
+            Some(_) => Ok(()),
+            None => {
+                self.state.connection_requests.cancel(&key, token);
+                Err(Error::Timeout(identifier))
+            }
+        }
+    }
+
+    /// Resolves once the adapter is powered on, or fails after `timeout` with the reason it isn't.
+    pub async fn wait_for_powered_on(&self, timeout: Duration) -> crate::Result<()> {
+        let (token, receiver) = self.state.powered_on_requests.register(POWERED_ON_REQUEST);
+        if !self.adapter_state().is_powered_on() {
+            wait_request(receiver, timeout).await;
+        }
+        self.state
+            .powered_on_requests
+            .cancel(POWERED_ON_REQUEST, token);
+        self.adapter_state().check()
+    }
+}
+
+const POWERED_ON_REQUEST: &str = "PoweredOn";
+
+fn connection_request_key(connect: bool, identifier: &str) -> String {
+    let operation = if connect { "Connect" } else { "Disconnect" };
+    format!("{operation}:{identifier}")
+}
+
+/// The answer to a pending request, `None` if it didn't come within `timeout`.
+async fn wait_request<T: Send + 'static>(receiver: Receiver<T>, timeout: Duration) -> Option<T> {
+    tauri::async_runtime::spawn_blocking(move || receiver.recv_timeout(timeout).ok())
+        .await
+        .ok()
+        .flatten()
+}
+
+fn is_connected(device: &Device) -> bool {
+    device.state.as_deref() == Some("connected")
+}
+
+/// Decode the beacons of a device heard while scanning.
+fn with_beacons(mut device: Device) -> Device {
+    device.beacons = beacon::beacons(&device.advertisement, device.rssi);
+    device
+}
+
+impl State {
+    fn lock_scan_channels(&self) -> std::sync::MutexGuard<'_, Vec<ScanChannel>> {
+        self.scan_channels.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The scan sessions whose filter a report passes, `None` if it is for nobody. Connected and
+    /// monitored devices are always reported to the listeners, if to no session.
+    fn wanted(&self, device: &Device) -> Option<Vec<u32>> {
+        let known = self.registry.get(&device.uuid);
+        let sessions = self
+            .scan_sessions
+            .record(device, known.as_ref().map(|snapshot| &snapshot.device));
+        let followed = is_connected(device) || self.monitors.is_monitoring(&device.uuid);
+        (followed || !sessions.is_empty()).then_some(sessions)
+    }
+
+    fn emit(&self, event: BluetoothEvent) {
+        self.send(event, None);
+    }
+
+    /// Emit a scan report, only the channels of `sessions` get it.
+    fn emit_report(&self, event: BluetoothEvent, sessions: &[u32]) {
+        self.send(event, Some(sessions));
+    }
+
+    fn send(&self, event: BluetoothEvent, sessions: Option<&[u32]>) {
+        // a channel whose webview went away fails to send and is dropped.
+        self.lock_scan_channels().retain(|scan| {
+            !scan.wants(&event, sessions) || scan.channel.send(event.clone()).is_ok()
+        });
+        self.hub.dispatch(&event);
+    }
+
+    /// Report the devices that outlived the registry TTL as lost.
+    fn evict_stale_devices(&self) {
+        for device in self.registry.evict_stale() {
+            self.dispatch_remove_device(device);
+        }
+    }
+
+    fn emit_beacons(&self, device: &Device, sessions: &[u32]) {
+        for beacon in &device.beacons {
+            let sighting = BeaconSighting {
+                identifier: device.uuid.clone(),
+                rssi: device.rssi,
+                beacon: beacon.clone(),
+            };
+            self.emit_report(BluetoothEvent::Beacon(sighting), sessions);
+        }
+    }
+
+    pub(crate) fn dispatch_new_device(&self, device: Device) {
+        self.recorder
+            .record(|| RecordedEvent::NewDevice(device.clone()));
+        self.observe_connection(&device);
+        self.observe_advertisement(&device);
+        if let Some(sessions) = self.wanted(&device) {
+            self.discover(device, &sessions);
+        }
+    }
+
+    /// Report a wanted device the listeners don't know yet.
+    fn discover(&self, device: Device, sessions: &[u32]) {
+        let device = with_beacons(device);
+        self.registry.record(&device);
+        self.emit_report(BluetoothEvent::DeviceDiscovered(device.clone()), sessions);
+        self.emit_beacons(&device, sessions);
+        self.evict_stale_devices();
+    }
+
+    pub(crate) fn dispatch_update_device(&self, device: Device) {
+        self.recorder
+            .record(|| RecordedEvent::UpdateDevice(device.clone()));
+        self.observe_connection(&device);
+        self.observe_advertisement(&device);
+        let Some(sessions) = self.wanted(&device) else {
+            return;
+        };
+        // a device filtered out so far is new to the listeners.
+        if self.registry.get(&device.uuid).is_none() {
+            return self.discover(device, &sessions);
+        }
+        let device = with_beacons(device);
+        self.registry.record(&device);
+        self.emit_report(BluetoothEvent::DeviceUpdated(device.clone()), &sessions);
+        self.emit_beacons(&device, &sessions);
+        self.evict_stale_devices();
+    }
+
+    pub(crate) fn dispatch_remove_device(&self, device: Device) {
+        self.recorder
+            .record(|| RecordedEvent::RemoveDevice(device.clone()));
+        self.registry.remove(&device.uuid);
+        self.emit(BluetoothEvent::DeviceLost(device));
+    }
+
+    pub(crate) fn dispatch_rssi(
+        &self,
+        identifier: String,
+        rssi: i32,
+        estimated_rssi: i32,
+        active: bool,
+    ) {
+        self.recorder.record(|| RecordedEvent::Rssi {
+            identifier: identifier.clone(),
+            rssi,
+            estimated_rssi,
+            active,
+        });
+        self.registry.record_rssi(&identifier, rssi);
+        self.calibrations.record(&identifier, rssi);
+        if let Some(transition) = self.monitors.on_rssi(&identifier, rssi) {
+            self.dispatch_device_presence(&identifier, transition);
+        }
+        let filtered = self.rssi_filters.apply(&identifier, rssi);
+        let distance = self
+            .calibrations
+            .estimate(&identifier, filtered.value, filtered.variance);
+        let update = RssiUpdate {
+            identifier,
+            rssi,
+            estimated_rssi,
+            filtered_rssi: filtered.value,
+            variance: filtered.variance,
+            rejected: filtered.rejected,
+            active,
+            distance,
+        };
+        self.emit(BluetoothEvent::Rssi(update));
+    }
+
+    pub(crate) fn dispatch_presence(&self, presence: bool, reason: String) {
+        self.recorder.record(|| RecordedEvent::Presence {
+            presence,
+            reason: reason.clone(),
+        });
+        self.emit(BluetoothEvent::Presence(PresenceUpdate {
+            presence,
+            reason,
+            identifier: None,
+        }));
+    }
+
+    /// Report the transition of a monitored device, then the aggregate presence if it flipped.
+    fn dispatch_device_presence(&self, identifier: &str, transition: PresenceTransition) {
+        self.emit(BluetoothEvent::Presence(PresenceUpdate {
+            presence: transition.presence,
+            reason: transition.reason.to_string(),
+            identifier: Some(identifier.to_string()),
+        }));
+        self.dispatch_aggregate_presence(Some(identifier));
+    }
+
+    fn dispatch_aggregate_presence(&self, identifier: Option<&str>) {
+        if let Some(aggregate) = self.monitors.update_aggregate(identifier) {
+            self.emit(BluetoothEvent::AggregatePresence(aggregate));
+        }
+    }
+
+    pub(crate) fn dispatch_notification(
+        &self,
+        identifier: String,
+        service: String,
+        characteristic: String,
+        value: Vec<u8>,
+    ) {
+        self.recorder.record(|| RecordedEvent::Notification {
+            identifier: identifier.clone(),
+            service: service.clone(),
+            characteristic: characteristic.clone(),
+            value: value.clone(),
+        });
+        let notification = Notification {
+            identifier,
+            service: crate::gatt::normalize_uuid(&service),
+            characteristic: crate::gatt::normalize_uuid(&characteristic),
+            profile: crate::profiles::decode(&characteristic, &value).ok(),
+            value: EncodedValue::Bytes(value.clone()),
+            timestamp: unix_millis(),
+        };
+        self.subscriptions.send(&notification, &value);
+        self.emit(BluetoothEvent::Notification(notification));
+    }
+
+    pub(crate) fn dispatch_reconnect(&self, update: ReconnectUpdate) {
+        self.emit(BluetoothEvent::Reconnect(update));
+    }
+
+    /// Track the connection of a device for its reconnect policy, whether the report is wanted or
+    /// not.
+    fn observe_connection(&self, device: &Device) {
+        self.reconnects
+            .observe(&device.uuid, is_connected(device))
+            .into_iter()
+            .for_each(|update| self.dispatch_reconnect(update));
+    }
+
+    /// Feed the advertised RSSI of a device to its presence and calibration, a connected device
+    /// is followed through its RSSI reads instead.
+    fn observe_advertisement(&self, device: &Device) {
+        if is_connected(device) {
+            return;
+        }
+        self.calibrations.record(&device.uuid, device.rssi);
+        if let Some(transition) = self.monitors.on_rssi(&device.uuid, device.rssi) {
+            self.dispatch_device_presence(&device.uuid, transition);
+        }
+    }
+
+    /// A device connected, failed to connect or disconnected, `error` tells why a connection failed
+    /// or dropped.
+    pub(crate) fn dispatch_connection_state(
+        &self,
+        identifier: &str,
+        connected: bool,
+        error: Option<String>,
+    ) {
+        self.recorder.record(|| RecordedEvent::ConnectionState {
+            identifier: identifier.to_string(),
+            connected,
+            error: error.clone(),
+        });
+        if connected {
+            self.connection_requests
+                .resolve(&connection_request_key(true, identifier), Ok(()));
+            return;
+        }
+        self.monitors.on_disconnect(identifier);
+        // the readings of the next connection start from scratch.
+        self.rssi_filters.reset(identifier);
+        self.connection_requests
+            .resolve(&connection_request_key(false, identifier), Ok(()));
+        self.connection_requests.resolve(
+            &connection_request_key(true, identifier),
+            Err(error.unwrap_or_else(|| "disconnected while connecting".to_string())),
+        );
+    }
+
+    pub(crate) fn dispatch_adapter_state(&self, state: AdapterState) {
+        self.recorder.record(|| RecordedEvent::AdapterState(state));
+        self.reconnects
+            .set_powered(state.is_powered_on())
+            .into_iter()
+            .for_each(|update| self.dispatch_reconnect(update));
+        if state.is_powered_on() {
+            self.powered_on_requests.resolve(POWERED_ON_REQUEST, ());
+        } else {
+            // the scan is started again once the adapter is back.
+            self.monitor_scanning.store(false, Ordering::Relaxed);
+        }
+        if state == AdapterState::PoweredOff {
+            for (identifier, transition) in self.monitors.on_power_off() {
+                self.dispatch_device_presence(&identifier, transition);
+            }
+        }
+        self.emit(BluetoothEvent::AdapterState(state));
+    }
+
+    pub(crate) fn dispatch_power_warn(&self) {
+        self.recorder.record(|| RecordedEvent::PowerWarning);
+        self.emit(BluetoothEvent::PowerWarning);
     }
 }
 
-extern "C" fn on_device_new(
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_device_new(
     uuid: *const c_char,
     manufacture: *const c_char,
     model: *const c_char,
-    adv_data: i64,
+    advertisement: *const c_char,
     rssi: i32,
     mac_addr: *const c_char,
     bl_name: *const c_char,
@@ -104,23 +1181,22 @@ extern "C" fn on_device_new(
         uuid,
         manufacture,
         model,
-        adv_data,
+        advertisement,
         rssi,
         mac_addr,
         bl_name,
         name,
         state,
     );
-    if let Some(Some(delegate)) = GLOBAL_BLE_DELEGATE.get() {
-        delegate.new_device(device)
-    }
+    with_native_state(|state| state.dispatch_new_device(device));
 }
 
-extern "C" fn on_device_update(
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_device_update(
     uuid: *const c_char,
     manufacture: *const c_char,
     model: *const c_char,
-    adv_data: i64,
+    advertisement: *const c_char,
     rssi: i32,
     mac_addr: *const c_char,
     bl_name: *const c_char,
@@ -131,23 +1207,22 @@ extern "C" fn on_device_update(
         uuid,
         manufacture,
         model,
-        adv_data,
+        advertisement,
         rssi,
         mac_addr,
         bl_name,
         name,
         state,
     );
-    if let Some(Some(delegate)) = GLOBAL_BLE_DELEGATE.get() {
-        delegate.update_device(device)
-    }
+    with_native_state(|state| state.dispatch_update_device(device));
 }
 
-extern "C" fn on_device_removed(
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_device_removed(
     uuid: *const c_char,
     manufacture: *const c_char,
     model: *const c_char,
-    adv_data: i64,
+    advertisement: *const c_char,
     rssi: i32,
     mac_addr: *const c_char,
     bl_name: *const c_char,
@@ -158,55 +1233,233 @@ extern "C" fn on_device_removed(
         uuid,
         manufacture,
         model,
-        adv_data,
+        advertisement,
         rssi,
         mac_addr,
         bl_name,
         name,
         state,
     );
-    if let Some(Some(delegate)) = GLOBAL_BLE_DELEGATE.get() {
-        delegate.remove_device(device)
-    }
+    with_native_state(|state| state.dispatch_remove_device(device));
 }
 
-extern "C" fn on_rssi_updated(rssi: i32, estimated_rssi: i32, active: bool) {
-    if let Some(Some(delegate)) = GLOBAL_BLE_DELEGATE.get() {
-        delegate.update_rssi(rssi, estimated_rssi, active);
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_rssi_updated(
+    uuid: *const c_char,
+    rssi: i32,
+    estimated_rssi: i32,
+    active: bool,
+) {
+    let identifier = unsafe { take_string(uuid) };
+    with_native_state(|state| state.dispatch_rssi(identifier, rssi, estimated_rssi, active));
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn presence_update(presence: bool, reason: *const c_char) {
+    if reason.is_null() {
+        return;
     }
+    let reason = unsafe { take_string(reason) };
+    with_native_state(|state| state.dispatch_presence(presence, reason));
 }
 
-extern "C" fn presence_update(presence: bool, reason: *const c_char) {
-    if let Some(Some(delegate)) = GLOBAL_BLE_DELEGATE.get() {
-        unsafe {
-            if !reason.is_null() {
-                let reason_val = take_string(reason);
-                delegate.update_presence(presence, reason_val);
-            }
-        }
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn bluetooth_power_warn() {
+    with_native_state(|state| state.dispatch_power_warn());
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_adapter_state(state: i32) {
+    let adapter = AdapterState::from_raw(state);
+    with_native_state(|state| state.dispatch_adapter_state(adapter));
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_connection_state(
+    uuid: *const c_char,
+    connected: bool,
+    error: *const c_char,
+) {
+    let (identifier, error) = unsafe { (take_string(uuid), take_string(error)) };
+    let error = Some(error).filter(|e| !e.is_empty());
+    with_native_state(|state| state.dispatch_connection_state(&identifier, connected, error));
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_gatt_result(
+    uuid: *const c_char,
+    service: *const c_char,
+    error: *const c_char,
+    result: *const c_char,
+) {
+    let (uuid, service, error, result) = unsafe {
+        (
+            take_string(uuid),
+            take_string(service),
+            take_string(error),
+            take_string(result),
+        )
+    };
+    let key = if service.is_empty() {
+        gatt_request_key(GattOperation::Services, &uuid, &[])
+    } else {
+        gatt_request_key(GattOperation::Characteristics, &uuid, &[&service])
+    };
+    resolve_gatt_request(&key, error, result);
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_characteristic_read(
+    uuid: *const c_char,
+    service: *const c_char,
+    characteristic: *const c_char,
+    error: *const c_char,
+    value: *const c_char,
+) {
+    on_characteristic_result(
+        GattOperation::Read,
+        uuid,
+        service,
+        characteristic,
+        error,
+        value,
+    );
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_characteristic_write(
+    uuid: *const c_char,
+    service: *const c_char,
+    characteristic: *const c_char,
+    error: *const c_char,
+    value: *const c_char,
+) {
+    on_characteristic_result(
+        GattOperation::Write,
+        uuid,
+        service,
+        characteristic,
+        error,
+        value,
+    );
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_notification_state(
+    uuid: *const c_char,
+    service: *const c_char,
+    characteristic: *const c_char,
+    error: *const c_char,
+    value: *const c_char,
+) {
+    on_characteristic_result(
+        GattOperation::Notify,
+        uuid,
+        service,
+        characteristic,
+        error,
+        value,
+    );
+}
+
+#[cfg(native_bridge)]
+pub(crate) extern "C" fn on_notification(
+    uuid: *const c_char,
+    service: *const c_char,
+    characteristic: *const c_char,
+    _error: *const c_char,
+    value: *const c_char,
+) {
+    let (uuid, service, characteristic, value) = unsafe {
+        (
+            take_string(uuid),
+            take_string(service),
+            take_string(characteristic),
+            take_string(value),
+        )
+    };
+    if let Some(value) = crate::hex::decode(&value) {
+        with_native_state(|state| {
+            state.dispatch_notification(uuid, service, characteristic, value)
+        });
     }
 }
 
-extern "C" fn bluetooth_power_warn() {
-    if let Some(Some(delegate)) = GLOBAL_BLE_DELEGATE.get() {
-        delegate.bluetooth_power_warn();
+#[cfg(native_bridge)]
+fn on_characteristic_result(
+    operation: GattOperation,
+    uuid: *const c_char,
+    service: *const c_char,
+    characteristic: *const c_char,
+    error: *const c_char,
+    value: *const c_char,
+) {
+    let (uuid, service, characteristic, error, value) = unsafe {
+        (
+            take_string(uuid),
+            take_string(service),
+            take_string(characteristic),
+            take_string(error),
+            take_string(value),
+        )
+    };
+    let key = gatt_request_key(operation, &uuid, &[&service, &characteristic]);
+    resolve_gatt_request(&key, error, value);
+}
+
+#[cfg(native_bridge)]
+fn resolve_gatt_request(key: &str, error: String, result: String) {
+    let result = if error.is_empty() {
+        Ok(result)
+    } else {
+        Err(error)
+    };
+    with_native_state(|state| state.gatt_requests.resolve(key, result));
+}
+
+/// The GATT operations answered through a callback of the Swift side.
+#[cfg(native_bridge)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) enum GattOperation {
+    Services,
+    Characteristics,
+    Read,
+    Write,
+    Notify,
+}
+
+/// Key of a pending GATT operation on the attributes at `path`.
+#[cfg(native_bridge)]
+pub(crate) fn gatt_request_key(
+    operation: GattOperation,
+    identifier: &str,
+    path: &[&str],
+) -> String {
+    let mut key = format!("{operation:?}:{}", identifier.to_ascii_uppercase());
+    for uuid in path {
+        key.push('/');
+        key.push_str(&crate::gatt::normalize_uuid(uuid));
     }
+    key
 }
 
-/// 把 `*const c_char` 转成 Rust String，并在转换后释放内存
+/// 把 `*const c_char` 复制成 Rust String，指针只在回调期间有效
+#[cfg(native_bridge)]
 unsafe fn take_string(ptr: *const c_char) -> String {
     if ptr.is_null() {
         return String::new();
     }
-    // 只读取，不接管所有权
+    // 只读取，不接管所有权：Swift 传入的是临时字符串，由 Swift 释放
     CStr::from_ptr(ptr).to_string_lossy().into_owned()
 }
 
+#[cfg(native_bridge)]
+#[allow(clippy::too_many_arguments)]
 fn extract_device(
     uuid: *const c_char,
     manufacture: *const c_char,
     model: *const c_char,
-    adv_data: i64,
+    advertisement: *const c_char,
     rssi: i32,
     mac_addr: *const c_char,
     bl_name: *const c_char,
@@ -217,21 +1470,22 @@ fn extract_device(
         let uuid = take_string(uuid);
         let manufacture = take_string(manufacture);
         let model = take_string(model);
+        let advertisement = serde_json::from_str(&take_string(advertisement)).unwrap_or_default();
         let mac_addr = take_string(mac_addr);
         let bl_name = take_string(bl_name);
         let name = take_string(name);
         let state = take_string(state);
-        let device = Device {
+        Device {
             uuid,
             manufacture: (!manufacture.is_empty()).then_some(manufacture),
             model: (!model.is_empty()).then_some(model),
-            adv_data,
+            advertisement,
             rssi,
             mac_addr: (!mac_addr.is_empty()).then_some(mac_addr),
             bl_name: (!bl_name.is_empty()).then_some(bl_name),
             name: (!name.is_empty()).then_some(name),
             state: (!state.is_empty()).then_some(state),
-        };
-        device
+            beacons: vec![],
+        }
     }
 }
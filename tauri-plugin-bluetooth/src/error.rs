@@ -6,16 +6,152 @@ pub type Result<T> = std::result::Result<T, Error>;
 pub enum Error {
   #[error(transparent)]
   Io(#[from] std::io::Error),
+  #[error(transparent)]
+  Json(#[from] serde_json::Error),
+  #[error("bluetooth is powered off")]
+  AdapterPoweredOff,
+  #[error("bluetooth is not ready yet")]
+  AdapterNotReady,
+  #[error("bluetooth access is not authorized")]
+  Unauthorized,
+  #[error("device {0} not found")]
+  DeviceNotFound(String),
+  #[error("invalid device identifier {0:?}")]
+  InvalidIdentifier(String),
+  #[error("{0} is not connected")]
+  NotConnected(String),
+  #[error("could not connect to {0}")]
+  Connection(String),
+  #[error("{0} did not answer in time")]
+  Timeout(String),
+  /// An ATT error returned by the peripheral.
+  #[error("gatt error {:#04x}: {}", .0, att_error_name(*.0))]
+  GattError(u8),
+  #[error("gatt operation failed: {0}")]
+  Gatt(String),
+  #[error("unsupported: {0}")]
+  Unsupported(String),
+  #[error("calibration failed: {0}")]
+  Calibration(String),
+  #[error("invalid value: {0}")]
+  InvalidValue(String),
+  #[error("presence action failed: {0}")]
+  PresenceAction(String),
   #[cfg(mobile)]
   #[error(transparent)]
   PluginInvoke(#[from] tauri::plugin::mobile::PluginInvokeError),
 }
 
+impl Error {
+  /// Stable identifier of the variant, the `code` the frontend receives.
+  pub fn code(&self) -> &'static str {
+    match self {
+      Error::Io(_) => "io",
+      Error::Json(_) => "json",
+      Error::AdapterPoweredOff => "adapterPoweredOff",
+      Error::AdapterNotReady => "adapterNotReady",
+      Error::Unauthorized => "unauthorized",
+      Error::DeviceNotFound(_) => "deviceNotFound",
+      Error::InvalidIdentifier(_) => "invalidIdentifier",
+      Error::NotConnected(_) => "notConnected",
+      Error::Connection(_) => "connection",
+      Error::Timeout(_) => "timeout",
+      Error::GattError(_) => "gattError",
+      Error::Gatt(_) => "gatt",
+      Error::Unsupported(_) => "unsupported",
+      Error::Calibration(_) => "calibration",
+      Error::InvalidValue(_) => "invalidValue",
+      Error::PresenceAction(_) => "presenceAction",
+      #[cfg(mobile)]
+      Error::PluginInvoke(_) => "pluginInvoke",
+    }
+  }
+}
+
+/// Name of an ATT error code from the Bluetooth Core specification.
+fn att_error_name(code: u8) -> &'static str {
+  match code {
+    0x01 => "invalid handle",
+    0x02 => "read not permitted",
+    0x03 => "write not permitted",
+    0x04 => "invalid pdu",
+    0x05 => "insufficient authentication",
+    0x06 => "request not supported",
+    0x07 => "invalid offset",
+    0x08 => "insufficient authorization",
+    0x09 => "prepare queue full",
+    0x0A => "attribute not found",
+    0x0B => "attribute not long",
+    0x0C => "insufficient encryption key size",
+    0x0D => "invalid attribute value length",
+    0x0E => "unlikely error",
+    0x0F => "insufficient encryption",
+    0x10 => "unsupported group type",
+    0x11 => "insufficient resources",
+    0x80..=0x9F => "application error",
+    0xFC..=0xFF => "profile error",
+    _ => "reserved",
+  }
+}
+
+/// Sent to the frontend as `{ code, message }`.
 impl Serialize for Error {
   fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
   where
     S: Serializer,
   {
-    serializer.serialize_str(self.to_string().as_ref())
+    use serde::ser::SerializeStruct;
+    let mut error = serializer.serialize_struct("Error", 2)?;
+    error.serialize_field("code", self.code())?;
+    error.serialize_field("message", &self.to_string())?;
+    error.end()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use serde_json::json;
+
+  #[test]
+  fn errors_serialize_as_code_and_message() {
+    let cases = [
+      (Error::AdapterPoweredOff, "adapterPoweredOff", "bluetooth is powered off"),
+      (Error::AdapterNotReady, "adapterNotReady", "bluetooth is not ready yet"),
+      (Error::Unauthorized, "unauthorized", "bluetooth access is not authorized"),
+      (Error::DeviceNotFound("AA".into()), "deviceNotFound", "device AA not found"),
+      (Error::InvalidIdentifier("a b".into()), "invalidIdentifier", "invalid device identifier \"a b\""),
+      (Error::NotConnected("AA".into()), "notConnected", "AA is not connected"),
+      (Error::Connection("AA".into()), "connection", "could not connect to AA"),
+      (Error::Timeout("AA".into()), "timeout", "AA did not answer in time"),
+      (Error::GattError(0x0A), "gattError", "gatt error 0x0a: attribute not found"),
+      (Error::Gatt("lost".into()), "gatt", "gatt operation failed: lost"),
+      (Error::Unsupported("notify".into()), "unsupported", "unsupported: notify"),
+      (Error::Calibration("no samples".into()), "calibration", "calibration failed: no samples"),
+      (Error::InvalidValue("zz".into()), "invalidValue", "invalid value: zz"),
+      (Error::PresenceAction("lock".into()), "presenceAction", "presence action failed: lock"),
+    ];
+    for (error, code, message) in cases {
+      assert_eq!(
+        serde_json::to_value(&error).unwrap(),
+        json!({ "code": code, "message": message })
+      );
+    }
+    let io = Error::from(std::io::Error::other("disk"));
+    assert_eq!(serde_json::to_value(&io).unwrap(), json!({ "code": "io", "message": "disk" }));
+    let parse = Error::from(serde_json::from_str::<u8>("x").unwrap_err());
+    assert_eq!(serde_json::to_value(&parse).unwrap()["code"], "json");
+  }
+
+  #[test]
+  fn att_codes_are_named_by_range() {
+    assert_eq!(att_error_name(0x01), "invalid handle");
+    assert_eq!(att_error_name(0x11), "insufficient resources");
+    assert_eq!(att_error_name(0x12), "reserved");
+    assert_eq!(att_error_name(0x80), "application error");
+    assert_eq!(att_error_name(0x9F), "application error");
+    assert_eq!(att_error_name(0xA0), "reserved");
+    assert_eq!(att_error_name(0xFC), "profile error");
+    assert_eq!(att_error_name(0x00), "reserved");
   }
 }
@@ -0,0 +1,101 @@
+use crate::adapter::AdapterState;
+use crate::beacon::BeaconSighting;
+use crate::bridge::Device;
+use crate::notifications::Notification;
+use crate::presence::AggregatePresence;
+use crate::reconnect::ReconnectUpdate;
+use crate::rssi::RssiUpdate;
+use crate::scan::ScanSummary;
+use serde::{Deserialize, Serialize};
+use tauri::{AppHandle, Emitter, Runtime};
+
+pub const DEVICE_DISCOVERED: &str = "bluetooth://device-discovered";
+pub const DEVICE_UPDATED: &str = "bluetooth://device-updated";
+pub const DEVICE_LOST: &str = "bluetooth://device-lost";
+pub const BEACON: &str = "bluetooth://beacon";
+pub const RSSI: &str = "bluetooth://rssi";
+pub const PRESENCE: &str = "bluetooth://presence";
+pub const POWER_WARNING: &str = "bluetooth://power-warning";
+pub const NOTIFICATION: &str = "bluetooth://notification";
+pub const SCAN_SUMMARY: &str = "bluetooth://scan-summary";
+pub const RECONNECT: &str = "bluetooth://reconnect";
+pub const ADAPTER_STATE: &str = "bluetooth://adapter-state";
+pub const AGGREGATE_PRESENCE: &str = "bluetooth://aggregate-presence";
+
+/// Payload of the presence event.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PresenceUpdate {
+    pub presence: bool,
+    pub reason: String,
+    /// The monitored device whose presence changed, none for the monitoring of the Swift side.
+    #[serde(default)]
+    pub identifier: Option<String>,
+}
+
+/// Everything the plugin reports to the webview, each variant is emitted as its own Tauri event
+/// with the inner value as payload, and sent as a whole over channels.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum BluetoothEvent {
+    DeviceDiscovered(Device),
+    DeviceUpdated(Device),
+    DeviceLost(Device),
+    Beacon(BeaconSighting),
+    Rssi(RssiUpdate),
+    Presence(PresenceUpdate),
+    PowerWarning,
+    Notification(Notification),
+    ScanSummary(ScanSummary),
+    Reconnect(ReconnectUpdate),
+    AdapterState(AdapterState),
+    AggregatePresence(AggregatePresence),
+}
+
+impl BluetoothEvent {
+    /// Name of the Tauri event carrying this variant.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BluetoothEvent::DeviceDiscovered(_) => DEVICE_DISCOVERED,
+            BluetoothEvent::DeviceUpdated(_) => DEVICE_UPDATED,
+            BluetoothEvent::DeviceLost(_) => DEVICE_LOST,
+            BluetoothEvent::Beacon(_) => BEACON,
+            BluetoothEvent::Rssi(_) => RSSI,
+            BluetoothEvent::Presence(_) => PRESENCE,
+            BluetoothEvent::PowerWarning => POWER_WARNING,
+            BluetoothEvent::Notification(_) => NOTIFICATION,
+            BluetoothEvent::ScanSummary(_) => SCAN_SUMMARY,
+            BluetoothEvent::Reconnect(_) => RECONNECT,
+            BluetoothEvent::AdapterState(_) => ADAPTER_STATE,
+            BluetoothEvent::AggregatePresence(_) => AGGREGATE_PRESENCE,
+        }
+    }
+
+    /// Whether the event is about a device seen while scanning.
+    pub fn is_device_event(&self) -> bool {
+        matches!(
+            self,
+            BluetoothEvent::DeviceDiscovered(_)
+                | BluetoothEvent::DeviceUpdated(_)
+                | BluetoothEvent::DeviceLost(_)
+        )
+    }
+
+    pub fn emit<R: Runtime>(&self, app: &AppHandle<R>) -> tauri::Result<()> {
+        let name = self.name();
+        match self {
+            BluetoothEvent::DeviceDiscovered(device)
+            | BluetoothEvent::DeviceUpdated(device)
+            | BluetoothEvent::DeviceLost(device) => app.emit(name, device),
+            BluetoothEvent::Beacon(sighting) => app.emit(name, sighting),
+            BluetoothEvent::Rssi(update) => app.emit(name, update),
+            BluetoothEvent::Presence(update) => app.emit(name, update),
+            BluetoothEvent::PowerWarning => app.emit(name, ()),
+            BluetoothEvent::Notification(notification) => app.emit(name, notification),
+            BluetoothEvent::ScanSummary(summary) => app.emit(name, summary),
+            BluetoothEvent::Reconnect(update) => app.emit(name, update),
+            BluetoothEvent::AdapterState(state) => app.emit(name, state),
+            BluetoothEvent::AggregatePresence(aggregate) => app.emit(name, aggregate),
+        }
+    }
+}
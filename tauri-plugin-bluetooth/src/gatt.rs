@@ -0,0 +1,391 @@
+use serde::{Deserialize, Serialize};
+
+/// Bluetooth SIG base UUID, 16-bit UUIDs are the `xxxx` of `0000xxxx-0000-1000-8000-00805F9B34FB`.
+const BASE_UUID_SUFFIX: &str = "-0000-1000-8000-00805F9B34FB";
+
+/// Assigned numbers of the common GATT services.
+const SERVICE_NAMES: &[(&str, &str)] = &[
+    ("1800", "Generic Access"),
+    ("1801", "Generic Attribute"),
+    ("1802", "Immediate Alert"),
+    ("1803", "Link Loss"),
+    ("1804", "Tx Power"),
+    ("1805", "Current Time"),
+    ("1806", "Reference Time Update"),
+    ("1807", "Next DST Change"),
+    ("1808", "Glucose"),
+    ("1809", "Health Thermometer"),
+    ("180A", "Device Information"),
+    ("180D", "Heart Rate"),
+    ("180E", "Phone Alert Status"),
+    ("180F", "Battery Service"),
+    ("1810", "Blood Pressure"),
+    ("1811", "Alert Notification"),
+    ("1812", "Human Interface Device"),
+    ("1813", "Scan Parameters"),
+    ("1814", "Running Speed and Cadence"),
+    ("1815", "Automation IO"),
+    ("1816", "Cycling Speed and Cadence"),
+    ("1818", "Cycling Power"),
+    ("1819", "Location and Navigation"),
+    ("181A", "Environmental Sensing"),
+    ("181B", "Body Composition"),
+    ("181C", "User Data"),
+    ("181D", "Weight Scale"),
+    ("181E", "Bond Management"),
+    ("181F", "Continuous Glucose Monitoring"),
+    ("1820", "Internet Protocol Support"),
+    ("1821", "Indoor Positioning"),
+    ("1822", "Pulse Oximeter"),
+    ("1823", "HTTP Proxy"),
+    ("1824", "Transport Discovery"),
+    ("1825", "Object Transfer"),
+];
+
+/// Assigned numbers of the common GATT characteristics.
+const CHARACTERISTIC_NAMES: &[(&str, &str)] = &[
+    ("2A00", "Device Name"),
+    ("2A01", "Appearance"),
+    ("2A02", "Peripheral Privacy Flag"),
+    ("2A03", "Reconnection Address"),
+    ("2A04", "Peripheral Preferred Connection Parameters"),
+    ("2A05", "Service Changed"),
+    ("2A0F", "Local Time Information"),
+    ("2A14", "Reference Time Information"),
+    ("2A19", "Battery Level"),
+    ("2A1C", "Temperature Measurement"),
+    ("2A1D", "Temperature Type"),
+    ("2A1E", "Intermediate Temperature"),
+    ("2A23", "System ID"),
+    ("2A24", "Model Number String"),
+    ("2A25", "Serial Number String"),
+    ("2A26", "Firmware Revision String"),
+    ("2A27", "Hardware Revision String"),
+    ("2A28", "Software Revision String"),
+    ("2A29", "Manufacturer Name String"),
+    ("2A2B", "Current Time"),
+    ("2A35", "Blood Pressure Measurement"),
+    ("2A36", "Intermediate Cuff Pressure"),
+    ("2A37", "Heart Rate Measurement"),
+    ("2A38", "Body Sensor Location"),
+    ("2A39", "Heart Rate Control Point"),
+    ("2A49", "Blood Pressure Feature"),
+    ("2A50", "PnP ID"),
+    ("2A53", "RSC Measurement"),
+    ("2A54", "RSC Feature"),
+    ("2A63", "Cycling Power Measurement"),
+    ("2A64", "Cycling Power Vector"),
+    ("2A65", "Cycling Power Feature"),
+    ("2A66", "Cycling Power Control Point"),
+    ("2A99", "User Index"),
+    ("2A9D", "Weight Measurement"),
+    ("2A9E", "Weight Scale Feature"),
+    ("2A9F", "User Control Point"),
+];
+
+/// Assigned numbers of the GATT descriptors.
+const DESCRIPTOR_NAMES: &[(&str, &str)] = &[
+    ("2900", "Characteristic Extended Properties"),
+    ("2901", "Characteristic User Description"),
+    ("2902", "Client Characteristic Configuration"),
+    ("2903", "Server Characteristic Configuration"),
+    ("2904", "Characteristic Presentation Format"),
+    ("2905", "Characteristic Aggregate Format"),
+    ("2906", "Valid Range"),
+    ("2907", "External Report Reference"),
+    ("2908", "Report Reference"),
+];
+
+/// A service of a connected peripheral.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GattService {
+    pub uuid: String,
+    /// SIG name of the service, for assigned UUIDs.
+    pub name: Option<String>,
+    pub primary: bool,
+    /// Empty until the characteristics of the service are discovered.
+    pub characteristics: Vec<GattCharacteristic>,
+}
+
+impl GattService {
+    pub fn new(uuid: &str, primary: bool) -> Self {
+        GattService {
+            uuid: normalize_uuid(uuid),
+            name: service_name(uuid).map(str::to_string),
+            primary,
+            characteristics: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GattCharacteristic {
+    pub uuid: String,
+    pub name: Option<String>,
+    pub properties: CharacteristicProperties,
+    pub descriptors: Vec<GattDescriptor>,
+}
+
+impl GattCharacteristic {
+    pub fn new(uuid: &str, properties: CharacteristicProperties) -> Self {
+        GattCharacteristic {
+            uuid: normalize_uuid(uuid),
+            name: characteristic_name(uuid).map(str::to_string),
+            properties,
+            descriptors: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GattDescriptor {
+    pub uuid: String,
+    pub name: Option<String>,
+}
+
+impl GattDescriptor {
+    pub fn new(uuid: &str) -> Self {
+        GattDescriptor {
+            uuid: normalize_uuid(uuid),
+            name: descriptor_name(uuid).map(str::to_string),
+        }
+    }
+}
+
+/// What a characteristic supports, the bits of `CBCharacteristicProperties`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CharacteristicProperties {
+    pub broadcast: bool,
+    pub read: bool,
+    pub write_without_response: bool,
+    pub write: bool,
+    pub notify: bool,
+    pub indicate: bool,
+    pub authenticated_signed_writes: bool,
+    pub extended_properties: bool,
+}
+
+impl CharacteristicProperties {
+    pub fn from_bits(bits: u32) -> Self {
+        CharacteristicProperties {
+            broadcast: bits & 0x01 != 0,
+            read: bits & 0x02 != 0,
+            write_without_response: bits & 0x04 != 0,
+            write: bits & 0x08 != 0,
+            notify: bits & 0x10 != 0,
+            indicate: bits & 0x20 != 0,
+            authenticated_signed_writes: bits & 0x40 != 0,
+            extended_properties: bits & 0x80 != 0,
+        }
+    }
+}
+
+/// A service as described by the Swift side.
+#[cfg(native_bridge)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NativeService {
+    pub(crate) uuid: String,
+    pub(crate) primary: bool,
+}
+
+#[cfg(native_bridge)]
+impl From<NativeService> for GattService {
+    fn from(service: NativeService) -> Self {
+        GattService::new(&service.uuid, service.primary)
+    }
+}
+
+/// A characteristic as described by the Swift side, `properties` holds the raw bits.
+#[cfg(native_bridge)]
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct NativeCharacteristic {
+    pub(crate) uuid: String,
+    pub(crate) properties: u32,
+    #[serde(default)]
+    pub(crate) descriptors: Vec<String>,
+}
+
+#[cfg(native_bridge)]
+impl From<NativeCharacteristic> for GattCharacteristic {
+    fn from(characteristic: NativeCharacteristic) -> Self {
+        GattCharacteristic {
+            descriptors: characteristic
+                .descriptors
+                .iter()
+                .map(|uuid| GattDescriptor::new(uuid))
+                .collect(),
+            ..GattCharacteristic::new(
+                &characteristic.uuid,
+                CharacteristicProperties::from_bits(characteristic.properties),
+            )
+        }
+    }
+}
+
+/// Upper case UUID, UUIDs built on the SIG base UUID are shortened to 32 bits, and to 16 bits
+/// when they fit, like CoreBluetooth does.
+pub fn normalize_uuid(uuid: &str) -> String {
+    let uuid = uuid.trim().to_ascii_uppercase();
+    let short = match uuid.strip_suffix(BASE_UUID_SUFFIX) {
+        Some(prefix) if prefix.len() == 8 => prefix,
+        _ => &uuid,
+    };
+    match short.strip_prefix("0000") {
+        Some(short) if short.len() == 4 => short.to_string(),
+        _ if short.len() == 8 => short.to_string(),
+        _ => uuid,
+    }
+}
+
+/// Whether `uuid` is a 16-bit, 32-bit or 128-bit UUID, the forms `CBUUID` accepts.
+pub fn is_valid_uuid(uuid: &str) -> bool {
+    let uuid = uuid.trim();
+    let hex = |part: &str| part.chars().all(|c| c.is_ascii_hexdigit());
+    match uuid.len() {
+        4 | 8 => hex(uuid),
+        36 => {
+            let parts: Vec<&str> = uuid.split('-').collect();
+            parts.iter().map(|part| part.len()).eq([8, 4, 4, 4, 12]) && parts.into_iter().all(hex)
+        }
+        _ => false,
+    }
+}
+
+/// Whether two UUIDs designate the same attribute.
+pub fn same_uuid(a: &str, b: &str) -> bool {
+    normalize_uuid(a) == normalize_uuid(b)
+}
+
+pub fn service_name(uuid: &str) -> Option<&'static str> {
+    lookup(SERVICE_NAMES, uuid)
+}
+
+pub fn characteristic_name(uuid: &str) -> Option<&'static str> {
+    lookup(CHARACTERISTIC_NAMES, uuid)
+}
+
+pub fn descriptor_name(uuid: &str) -> Option<&'static str> {
+    lookup(DESCRIPTOR_NAMES, uuid)
+}
+
+fn lookup(table: &[(&str, &'static str)], uuid: &str) -> Option<&'static str> {
+    let uuid = normalize_uuid(uuid);
+    table
+        .iter()
+        .find(|(assigned, _)| *assigned == uuid)
+        .map(|(_, name)| *name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uuids_on_the_base_uuid_are_shortened() {
+        assert_eq!(normalize_uuid(" 180a "), "180A");
+        assert_eq!(normalize_uuid("0000180A"), "180A");
+        assert_eq!(
+            normalize_uuid("0000180a-0000-1000-8000-00805f9b34fb"),
+            "180A"
+        );
+        assert_eq!(
+            normalize_uuid("1234ABCD-0000-1000-8000-00805F9B34FB"),
+            "1234ABCD"
+        );
+        assert_eq!(normalize_uuid("1234abcd"), "1234ABCD");
+        // off the base UUID, only the case changes.
+        assert_eq!(
+            normalize_uuid("0000180a-0000-1000-8000-00805f9b34fc"),
+            "0000180A-0000-1000-8000-00805F9B34FC"
+        );
+    }
+
+    #[test]
+    fn uuids_compare_in_any_form() {
+        assert!(same_uuid("0000180A", "180A"));
+        assert!(same_uuid("180a", "0000180A-0000-1000-8000-00805F9B34FB"));
+        assert!(same_uuid(" 2A37", "2a37 "));
+        assert!(!same_uuid("180A", "180D"));
+        assert!(!same_uuid("180A", "0001180A"));
+    }
+
+    #[test]
+    fn only_the_forms_cbuuid_accepts_are_valid() {
+        for valid in [
+            "180A",
+            " 180a ",
+            "1234ABCD",
+            "0000180A-0000-1000-8000-00805F9B34FB",
+        ] {
+            assert!(is_valid_uuid(valid), "{valid:?}");
+        }
+        for invalid in [
+            "",
+            "18A",
+            "180G",
+            "12345",
+            "0000180A00001000800000805F9B34FB",
+            "0000180A-0000-1000-8000-00805F9B34F",
+            "0000180A-00000-1000-800-00805F9B34FB",
+        ] {
+            assert!(!is_valid_uuid(invalid), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn properties_follow_their_bits() {
+        assert_eq!(
+            CharacteristicProperties::from_bits(0),
+            CharacteristicProperties::default()
+        );
+        assert_eq!(
+            CharacteristicProperties::from_bits(0x12),
+            CharacteristicProperties {
+                read: true,
+                notify: true,
+                ..Default::default()
+            }
+        );
+        let all = CharacteristicProperties::from_bits(0xFF);
+        assert!(
+            all.broadcast
+                && all.read
+                && all.write_without_response
+                && all.write
+                && all.notify
+                && all.indicate
+                && all.authenticated_signed_writes
+                && all.extended_properties
+        );
+        // bits past the eight properties are ignored.
+        assert_eq!(
+            CharacteristicProperties::from_bits(0x100),
+            Default::default()
+        );
+    }
+
+    #[test]
+    fn assigned_numbers_are_named_in_any_form() {
+        assert_eq!(service_name("180a"), Some("Device Information"));
+        assert_eq!(
+            service_name("0000180D-0000-1000-8000-00805F9B34FB"),
+            Some("Heart Rate")
+        );
+        assert_eq!(
+            characteristic_name("00002A37"),
+            Some("Heart Rate Measurement")
+        );
+        assert_eq!(
+            descriptor_name("2902"),
+            Some("Client Characteristic Configuration")
+        );
+        assert_eq!(service_name("2A37"), None);
+        assert_eq!(characteristic_name("FFF0"), None);
+    }
+}
@@ -0,0 +1,89 @@
+//! Hex encoding of raw bytes, the form they take across the FFI boundary and in JSON.
+
+/// Upper case digits, as CoreBluetooth prints bytes.
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    ::hex::encode_upper(bytes)
+}
+
+/// Digits of either case, `None` unless `value` is only pairs of them.
+pub(crate) fn decode(value: &str) -> Option<Vec<u8>> {
+    ::hex::decode(value).ok()
+}
+
+/// Maps whose values are byte strings.
+pub(crate) mod map {
+    use serde::ser::SerializeMap;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::BTreeMap;
+
+    pub(crate) fn serialize<K, S>(
+        map: &BTreeMap<K, Vec<u8>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error>
+    where
+        K: Serialize,
+        S: Serializer,
+    {
+        let mut out = serializer.serialize_map(Some(map.len()))?;
+        for (key, bytes) in map {
+            out.serialize_entry(key, &super::encode(bytes))?;
+        }
+        out.end()
+    }
+
+    pub(crate) fn deserialize<'de, K, D>(deserializer: D) -> Result<BTreeMap<K, Vec<u8>>, D::Error>
+    where
+        K: Deserialize<'de> + Ord,
+        D: Deserializer<'de>,
+    {
+        BTreeMap::<K, String>::deserialize(deserializer)?
+            .into_iter()
+            .map(|(key, value)| match super::decode(&value) {
+                Some(bytes) => Ok((key, bytes)),
+                None => Err(de::Error::custom(format!("invalid hex string {value:?}"))),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::{Deserialize, Serialize};
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        let encoded = encode(&bytes);
+        assert_eq!(&encoded[..8], "00010203");
+        assert_eq!(&encoded[encoded.len() - 4..], "FEFF");
+        assert_eq!(decode(&encoded), Some(bytes));
+        assert_eq!(decode("0aFf"), Some(vec![0x0A, 0xFF]));
+        assert_eq!(decode(""), Some(vec![]));
+    }
+
+    #[test]
+    fn anything_but_pairs_of_digits_is_rejected() {
+        for value in ["+1", "-1", "0x01", "1", "0G", " 01", "01 ", "é1"] {
+            assert_eq!(decode(value), None, "{value:?}");
+        }
+    }
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Data {
+        #[serde(with = "map")]
+        values: BTreeMap<u16, Vec<u8>>,
+    }
+
+    #[test]
+    fn maps_hold_hex_strings() {
+        let data = Data {
+            values: BTreeMap::from([(76, vec![0x02, 0x15])]),
+        };
+        let json = serde_json::to_string(&data).unwrap();
+        assert_eq!(json, r#"{"values":{"76":"0215"}}"#);
+        assert_eq!(serde_json::from_str::<Data>(&json).unwrap(), data);
+        assert!(serde_json::from_str::<Data>(r#"{"values":{"76":"+1"}}"#).is_err());
+    }
+}
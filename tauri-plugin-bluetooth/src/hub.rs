@@ -0,0 +1,347 @@
+use crate::bridge::{normalize_identifier, BLEDelegate};
+use crate::events::BluetoothEvent;
+use serde::{Deserialize, Serialize};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+
+/// The kinds of [`BluetoothEvent`], to pick the events a listener receives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EventKind {
+    DeviceDiscovered,
+    DeviceUpdated,
+    DeviceLost,
+    Beacon,
+    Rssi,
+    Presence,
+    PowerWarning,
+    Notification,
+    ScanSummary,
+    Reconnect,
+    AdapterState,
+    AggregatePresence,
+}
+
+impl BluetoothEvent {
+    pub fn kind(&self) -> EventKind {
+        match self {
+            BluetoothEvent::DeviceDiscovered(_) => EventKind::DeviceDiscovered,
+            BluetoothEvent::DeviceUpdated(_) => EventKind::DeviceUpdated,
+            BluetoothEvent::DeviceLost(_) => EventKind::DeviceLost,
+            BluetoothEvent::Beacon(_) => EventKind::Beacon,
+            BluetoothEvent::Rssi(_) => EventKind::Rssi,
+            BluetoothEvent::Presence(_) => EventKind::Presence,
+            BluetoothEvent::PowerWarning => EventKind::PowerWarning,
+            BluetoothEvent::Notification(_) => EventKind::Notification,
+            BluetoothEvent::ScanSummary(_) => EventKind::ScanSummary,
+            BluetoothEvent::Reconnect(_) => EventKind::Reconnect,
+            BluetoothEvent::AdapterState(_) => EventKind::AdapterState,
+            BluetoothEvent::AggregatePresence(_) => EventKind::AggregatePresence,
+        }
+    }
+
+    /// The device the event is about, if any.
+    pub fn identifier(&self) -> Option<&str> {
+        match self {
+            BluetoothEvent::DeviceDiscovered(device)
+            | BluetoothEvent::DeviceUpdated(device)
+            | BluetoothEvent::DeviceLost(device) => Some(&device.uuid),
+            BluetoothEvent::Beacon(sighting) => Some(&sighting.identifier),
+            BluetoothEvent::Rssi(update) => Some(&update.identifier),
+            BluetoothEvent::Notification(notification) => Some(&notification.identifier),
+            BluetoothEvent::Reconnect(update) => Some(&update.identifier),
+            BluetoothEvent::Presence(update) => update.identifier.as_deref(),
+            BluetoothEvent::AggregatePresence(aggregate) => aggregate.identifier.as_deref(),
+            BluetoothEvent::PowerWarning
+            | BluetoothEvent::ScanSummary(_)
+            | BluetoothEvent::AdapterState(_) => None,
+        }
+    }
+}
+
+/// The events a listener receives, every event by default.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventFilter {
+    kinds: Vec<EventKind>,
+    identifiers: Vec<String>,
+}
+
+impl EventFilter {
+    pub fn all() -> Self {
+        Self::default()
+    }
+
+    /// Only the events of `kind`, may be called once per kind.
+    pub fn kind(mut self, kind: EventKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Only the events about `identifier`, whatever its case, may be called once per device.
+    /// Events about no device in particular, like the power warning, no longer pass.
+    pub fn device(mut self, identifier: impl AsRef<str>) -> Self {
+        self.identifiers
+            .push(normalize_identifier(identifier.as_ref()));
+        self
+    }
+
+    pub fn matches(&self, event: &BluetoothEvent) -> bool {
+        (self.kinds.is_empty() || self.kinds.contains(&event.kind()))
+            && (self.identifiers.is_empty()
+                || event.identifier().is_some_and(|identifier| {
+                    self.identifiers.iter().any(|wanted| wanted == identifier)
+                }))
+    }
+}
+
+/// Handle of a listener, to remove it from the hub.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ListenerId(u64);
+
+type Listener = Arc<dyn Fn(&BluetoothEvent) + Send + Sync>;
+
+/// Hands every event to the listeners whose filter it passes.
+///
+/// Listeners are called in the order they were added, on the thread that produced the event. A
+/// listener that panics is skipped for that event only, and may add or remove listeners.
+#[derive(Default)]
+pub struct EventHub {
+    next_id: AtomicU64,
+    listeners: RwLock<Vec<(ListenerId, EventFilter, Listener)>>,
+}
+
+impl EventHub {
+    pub fn add<F: Fn(&BluetoothEvent) + Send + Sync + 'static>(
+        &self,
+        filter: EventFilter,
+        listener: F,
+    ) -> ListenerId {
+        let id = ListenerId(self.next_id.fetch_add(1, Ordering::Relaxed));
+        self.listeners
+            .write()
+            .unwrap_or_else(|e| e.into_inner())
+            .push((id, filter, Arc::new(listener)));
+        id
+    }
+
+    /// Add a [`BLEDelegate`], each event is passed to the method matching it.
+    pub fn add_delegate<DELEGATE: BLEDelegate + 'static>(&self, delegate: DELEGATE) -> ListenerId {
+        self.add(EventFilter::all(), move |event| deliver(&delegate, event))
+    }
+
+    /// Returns whether the listener was still there.
+    pub fn remove(&self, id: ListenerId) -> bool {
+        let mut listeners = self.listeners.write().unwrap_or_else(|e| e.into_inner());
+        let count = listeners.len();
+        listeners.retain(|(listener_id, _, _)| *listener_id != id);
+        listeners.len() != count
+    }
+
+    pub fn dispatch(&self, event: &BluetoothEvent) {
+        // released before calling out, so listeners may change the hub.
+        let listeners: Vec<Listener> = self
+            .listeners
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(_, filter, _)| filter.matches(event))
+            .map(|(_, _, listener)| listener.clone())
+            .collect();
+        for listener in listeners {
+            let _ = catch_unwind(AssertUnwindSafe(|| listener(event)));
+        }
+    }
+}
+
+fn deliver(delegate: &dyn BLEDelegate, event: &BluetoothEvent) {
+    match event {
+        BluetoothEvent::DeviceDiscovered(device) => delegate.new_device(device.clone()),
+        BluetoothEvent::DeviceUpdated(device) => delegate.update_device(device.clone()),
+        BluetoothEvent::DeviceLost(device) => delegate.remove_device(device.clone()),
+        BluetoothEvent::Rssi(update) => {
+            delegate.update_rssi(update.rssi, update.estimated_rssi, update.active);
+            delegate.update_filtered_rssi(update.clone());
+        }
+        // the legacy callback only hears about the presence of the user, not of each device.
+        BluetoothEvent::Presence(update) => match &update.identifier {
+            Some(identifier) => delegate.update_device_presence(
+                identifier.clone(),
+                update.presence,
+                update.reason.clone(),
+            ),
+            None => delegate.update_presence(update.presence, update.reason.clone()),
+        },
+        BluetoothEvent::PowerWarning => delegate.bluetooth_power_warn(),
+        BluetoothEvent::Notification(notification) => {
+            delegate.characteristic_changed(notification.clone())
+        }
+        BluetoothEvent::Reconnect(update) => delegate.reconnect_update(update.clone()),
+        BluetoothEvent::AdapterState(state) => delegate.adapter_state_changed(*state),
+        BluetoothEvent::AggregatePresence(aggregate) => {
+            delegate.update_aggregate_presence(aggregate.clone())
+        }
+        BluetoothEvent::Beacon(_) | BluetoothEvent::ScanSummary(_) => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::adapter::AdapterState;
+    use crate::bridge::Device;
+    use crate::events::PresenceUpdate;
+    use std::sync::Mutex;
+
+    fn presence(identifier: Option<&str>) -> BluetoothEvent {
+        BluetoothEvent::Presence(PresenceUpdate {
+            presence: true,
+            reason: "rssi".to_string(),
+            identifier: identifier.map(str::to_string),
+        })
+    }
+
+    /// A listener recording the kinds of the events it receives.
+    fn recorder(hub: &EventHub, filter: EventFilter) -> (ListenerId, Arc<Mutex<Vec<EventKind>>>) {
+        let received = Arc::new(Mutex::new(vec![]));
+        let sink = received.clone();
+        let id = hub.add(filter, move |event| sink.lock().unwrap().push(event.kind()));
+        (id, received)
+    }
+
+    #[test]
+    fn filters_pick_kinds_and_devices() {
+        let events = [
+            presence(Some("AA")),
+            presence(Some("BB")),
+            presence(None),
+            BluetoothEvent::PowerWarning,
+            BluetoothEvent::AdapterState(AdapterState::PoweredOn),
+        ];
+        let passing = |filter: EventFilter| -> Vec<usize> {
+            (0..events.len())
+                .filter(|&index| filter.matches(&events[index]))
+                .collect()
+        };
+        assert_eq!(passing(EventFilter::all()), [0, 1, 2, 3, 4]);
+        assert_eq!(
+            passing(EventFilter::all().kind(EventKind::Presence)),
+            [0, 1, 2]
+        );
+        assert_eq!(
+            passing(
+                EventFilter::all()
+                    .kind(EventKind::PowerWarning)
+                    .kind(EventKind::AdapterState)
+            ),
+            [3, 4]
+        );
+        // events about no device don't pass a device filter.
+        assert_eq!(passing(EventFilter::all().device("aa")), [0]);
+        assert_eq!(
+            passing(EventFilter::all().device("AA").device("BB")),
+            [0, 1]
+        );
+        assert!(passing(EventFilter::all().kind(EventKind::Rssi).device("AA")).is_empty());
+    }
+
+    #[test]
+    fn listeners_receive_the_events_passing_their_filter_until_removed() {
+        let hub = EventHub::default();
+        let (all, everything) = recorder(&hub, EventFilter::all());
+        let (_, warnings) = recorder(&hub, EventFilter::all().kind(EventKind::PowerWarning));
+
+        hub.dispatch(&presence(None));
+        hub.dispatch(&BluetoothEvent::PowerWarning);
+        assert_eq!(
+            *everything.lock().unwrap(),
+            [EventKind::Presence, EventKind::PowerWarning]
+        );
+        assert_eq!(*warnings.lock().unwrap(), [EventKind::PowerWarning]);
+
+        assert!(hub.remove(all));
+        assert!(!hub.remove(all));
+        hub.dispatch(&BluetoothEvent::PowerWarning);
+        assert_eq!(everything.lock().unwrap().len(), 2);
+        assert_eq!(warnings.lock().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn a_panicking_listener_does_not_stop_the_others() {
+        let hub = EventHub::default();
+        let (_, before) = recorder(&hub, EventFilter::all());
+        hub.add(EventFilter::all(), |event| {
+            if matches!(event, BluetoothEvent::PowerWarning) {
+                panic!("listener failure");
+            }
+        });
+        let (_, after) = recorder(&hub, EventFilter::all());
+
+        hub.dispatch(&BluetoothEvent::PowerWarning);
+        hub.dispatch(&presence(None));
+        let expected = [EventKind::PowerWarning, EventKind::Presence];
+        assert_eq!(*before.lock().unwrap(), expected);
+        assert_eq!(*after.lock().unwrap(), expected);
+    }
+
+    /// The device of each presence callback, `None` for the legacy one.
+    type PresenceCalls = Arc<Mutex<Vec<(Option<String>, bool)>>>;
+
+    /// A delegate recording the presence callbacks it receives.
+    #[derive(Default)]
+    struct PresenceDelegate(PresenceCalls);
+
+    impl BLEDelegate for PresenceDelegate {
+        fn new_device(&self, _device: Device) {}
+        fn update_device(&self, _device: Device) {}
+        fn remove_device(&self, _device: Device) {}
+        fn update_rssi(&self, _rssi: i32, _estimated_rssi: i32, _active: bool) {}
+        fn update_presence(&self, presence: bool, _reason: String) {
+            self.0.lock().unwrap().push((None, presence));
+        }
+        fn bluetooth_power_warn(&self) {}
+        fn update_device_presence(&self, identifier: String, presence: bool, _reason: String) {
+            self.0.lock().unwrap().push((Some(identifier), presence));
+        }
+    }
+
+    #[test]
+    fn delegates_hear_the_presence_of_a_device_only_through_its_callback() {
+        let hub = EventHub::default();
+        let delegate = PresenceDelegate::default();
+        let received = delegate.0.clone();
+        hub.add_delegate(delegate);
+
+        hub.dispatch(&presence(Some("AA")));
+        hub.dispatch(&presence(None));
+        assert_eq!(
+            *received.lock().unwrap(),
+            [(Some("AA".to_string()), true), (None, true)]
+        );
+    }
+
+    #[test]
+    fn listeners_may_change_the_hub_while_called() {
+        let hub = Arc::new(EventHub::default());
+        let added = Arc::new(Mutex::new(0));
+        let weak = Arc::downgrade(&hub);
+        let counter = added.clone();
+        let id = Arc::new(Mutex::new(None));
+        let own_id = id.clone();
+        let listener = hub.add(EventFilter::all(), move |_| {
+            let hub = weak.upgrade().unwrap();
+            let counter = counter.clone();
+            hub.add(EventFilter::all(), move |_| *counter.lock().unwrap() += 1);
+            // removes itself, it runs once.
+            hub.remove(own_id.lock().unwrap().unwrap());
+        });
+        *id.lock().unwrap() = Some(listener);
+
+        hub.dispatch(&BluetoothEvent::PowerWarning);
+        // the listener added during a dispatch gets the next events.
+        assert_eq!(*added.lock().unwrap(), 0);
+        hub.dispatch(&BluetoothEvent::PowerWarning);
+        assert_eq!(*added.lock().unwrap(), 1);
+        assert!(!hub.remove(listener));
+    }
+}
@@ -5,20 +5,59 @@ use tauri::{
 
 pub use models::*;
 
+#[cfg(desktop)]
+pub mod backend;
 #[cfg(desktop)]
 mod desktop;
 #[cfg(mobile)]
 mod mobile;
 
+#[cfg(desktop)]
+pub mod actions;
+pub mod adapter;
+pub mod advertisement;
+pub mod beacon;
 pub mod bridge;
+pub mod calibration;
+pub mod clock;
 mod commands;
 mod error;
+#[cfg(desktop)]
+pub mod events;
+pub mod gatt;
+mod hex;
+#[cfg(desktop)]
+pub mod hub;
 mod models;
+pub mod notifications;
+#[cfg(desktop)]
+mod pending;
+pub mod presence;
+pub mod profiles;
+pub mod reconnect;
+#[cfg(desktop)]
+pub mod recording;
+pub mod registry;
+pub mod rssi;
+pub mod scan;
+mod store;
+pub mod trusted;
 
 pub use error::{Error, Result};
 
+#[cfg(desktop)]
+use crate::backend::{BluetoothBackend, DefaultBackend};
 use crate::bridge::{BLEDelegate, BluetoothApi};
-use crate::commands::{set_passive_mode, start_scanning, stop_scanning, connect_device, disconnect_device, read_rssi};
+use crate::commands::{
+    add_trusted_device, cancel_calibration, connect, connect_device, disconnect, disconnect_device,
+    discover_characteristics, discover_services, echo, finish_calibration, get_adapter_state,
+    get_calibration, get_device, get_presence, list_devices, list_trusted_devices,
+    read_characteristic, read_device_info, read_profile, read_rssi, remove_trusted_device,
+    replay_recording, scan_beacons, set_passive_mode, set_presence_policy, set_reconnect_policy,
+    set_rssi_filter, start_calibration, start_recording, start_scan_session, start_scanning,
+    stop_recording, stop_scan_session, stop_scanning, subscribe, unsubscribe, wait_for_powered_on,
+    write_characteristic,
+};
 #[cfg(desktop)]
 use desktop::Bluetooth;
 #[cfg(mobile)]
@@ -36,21 +75,67 @@ impl<R: Runtime, T: Manager<R>> crate::BluetoothExt<R> for T {
 }
 
 /// Initializes the plugin.
-pub fn init<R: Runtime, DELEGATE: BLEDelegate + 'static>(delegate: DELEGATE) -> TauriPlugin<R> {
-    Builder::new("bluetooth")
+pub fn init<R: Runtime, DELEGATE: BLEDelegate + 'static>(
+    delegate: DELEGATE,
+) -> TauriPlugin<R, Option<Config>> {
+    init_with_backend(delegate, DefaultBackend::new())
+}
+
+/// Initializes the plugin on top of the given backend, e.g. a [`backend::SimulatedBackend`].
+pub fn init_with_backend<
+    R: Runtime,
+    DELEGATE: BLEDelegate + 'static,
+    BACKEND: BluetoothBackend + 'static,
+>(
+    delegate: DELEGATE,
+    backend: BACKEND,
+) -> TauriPlugin<R, Option<Config>> {
+    Builder::<R, Option<Config>>::new("bluetooth")
         .invoke_handler(tauri::generate_handler![
+            echo,
+            get_adapter_state,
+            wait_for_powered_on,
             start_scanning,
+            scan_beacons,
             stop_scanning,
+            start_scan_session,
+            stop_scan_session,
             set_passive_mode,
             connect_device,
             disconnect_device,
+            connect,
+            disconnect,
+            set_reconnect_policy,
             read_rssi,
+            discover_services,
+            discover_characteristics,
+            read_characteristic,
+            read_profile,
+            read_device_info,
+            write_characteristic,
+            subscribe,
+            unsubscribe,
+            set_rssi_filter,
+            start_calibration,
+            finish_calibration,
+            cancel_calibration,
+            get_calibration,
+            list_devices,
+            get_device,
+            add_trusted_device,
+            remove_trusted_device,
+            list_trusted_devices,
+            set_presence_policy,
+            get_presence,
+            start_recording,
+            stop_recording,
+            replay_recording,
         ])
         .setup(|app, api| {
             #[cfg(mobile)]
             let bluetooth = mobile::init(app, api)?;
             #[cfg(desktop)]
-            let bluetooth = desktop::init(app, api, delegate)?;
+            let bluetooth = desktop::init(app, api, delegate, backend)?;
             bluetooth.initialize();
             app.manage(bluetooth);
             Ok(())
@@ -1,3 +1,8 @@
+#[cfg(desktop)]
+use crate::actions::PresenceActionConfig;
+use crate::presence::PresencePolicy;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -24,12 +29,148 @@ pub struct EchoResp {
     pub value: Option<String>,
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct ConnectConf {}
+/// What a scan reports, every criterion that is set must match.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ScanOptions {
+    /// Service UUIDs the devices advertise, handed to the native scanner.
+    pub services: Vec<String>,
+    /// Case insensitive prefix of the name.
+    pub name_prefix: Option<String>,
+    /// Regular expression the name matches.
+    pub name_pattern: Option<String>,
+    /// Company identifiers of the manufacturer data.
+    pub company_ids: Vec<u16>,
+    pub min_rssi: Option<i32>,
+    /// Report every advertisement of a device rather than only the first, on by default.
+    pub allow_duplicates: bool,
+}
+
+impl Default for ScanOptions {
+    fn default() -> Self {
+        ScanOptions {
+            services: vec![],
+            name_prefix: None,
+            name_pattern: None,
+            company_ids: vec![],
+            min_rssi: None,
+            allow_duplicates: true,
+        }
+    }
+}
 
 #[derive(Debug, Clone, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ConnectResp {
     pub success: bool,
 }
+
+/// Plugin configuration, read from `plugins.bluetooth` in `tauri.conf.json`.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    /// Seconds after which a device that is no longer heard from leaves the registry.
+    pub device_ttl: Option<f64>,
+    /// How the presence of the trusted devices adds up, `any` by default.
+    pub presence_policy: Option<PresencePolicy>,
+    /// Actions run when the user arrives or leaves.
+    #[cfg(desktop)]
+    pub presence_actions: Vec<PresenceActionConfig>,
+}
+
+/// How characteristic values are passed to and from the webview.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ValueEncoding {
+    /// An array of numbers.
+    #[default]
+    Bytes,
+    Hex,
+    /// Standard base64 with padding.
+    Base64,
+}
+
+/// A characteristic value, as bytes or as a string in the requested [`ValueEncoding`].
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum EncodedValue {
+    Bytes(Vec<u8>),
+    Text(String),
+}
+
+impl EncodedValue {
+    pub fn encode(bytes: Vec<u8>, encoding: ValueEncoding) -> Self {
+        match encoding {
+            ValueEncoding::Bytes => EncodedValue::Bytes(bytes),
+            ValueEncoding::Hex => EncodedValue::Text(crate::hex::encode(&bytes)),
+            ValueEncoding::Base64 => EncodedValue::Text(BASE64.encode(&bytes)),
+        }
+    }
+
+    /// The raw bytes, a string is decoded with `encoding`.
+    pub fn decode(self, encoding: ValueEncoding) -> crate::Result<Vec<u8>> {
+        let text = match self {
+            EncodedValue::Bytes(bytes) => return Ok(bytes),
+            EncodedValue::Text(text) => text,
+        };
+        let bytes = match encoding {
+            ValueEncoding::Bytes => None,
+            ValueEncoding::Hex => crate::hex::decode(&text),
+            ValueEncoding::Base64 => BASE64.decode(&text).ok(),
+        };
+        bytes.ok_or_else(|| {
+            crate::Error::InvalidValue(format!("{text:?} is not valid {encoding:?} data"))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_round_trip_in_every_encoding() {
+        let bytes: Vec<u8> = (0..=255).collect();
+        for encoding in [
+            ValueEncoding::Bytes,
+            ValueEncoding::Hex,
+            ValueEncoding::Base64,
+        ] {
+            let encoded = EncodedValue::encode(bytes.clone(), encoding);
+            assert_eq!(encoded.decode(encoding).unwrap(), bytes, "{encoding:?}");
+        }
+        let text = |value: &str| EncodedValue::Text(value.to_string());
+        assert_eq!(
+            EncodedValue::encode(vec![0x01, 0xAB], ValueEncoding::Hex),
+            text("01AB")
+        );
+        assert_eq!(
+            EncodedValue::encode(vec![0xFB, 0xFF], ValueEncoding::Base64),
+            text("+/8=")
+        );
+        assert_eq!(text("AQ==").decode(ValueEncoding::Base64).unwrap(), [0x01]);
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        let rejected = [
+            (ValueEncoding::Hex, "+1"),
+            (ValueEncoding::Hex, "ABC"),
+            (ValueEncoding::Hex, "0x01"),
+            // the trailing bits of the last digit must be zero.
+            (ValueEncoding::Base64, "AR=="),
+            (ValueEncoding::Base64, "AQ"),
+            (ValueEncoding::Base64, "AQ="),
+            (ValueEncoding::Base64, "A"),
+            (ValueEncoding::Base64, "AQ==AQ=="),
+            (ValueEncoding::Base64, "-_8="),
+            (ValueEncoding::Bytes, "01"),
+        ];
+        for (encoding, value) in rejected {
+            let error = EncodedValue::Text(value.to_string())
+                .decode(encoding)
+                .unwrap_err();
+            assert_eq!(error.code(), "invalidValue", "{encoding:?} {value:?}");
+        }
+    }
+}
@@ -0,0 +1,103 @@
+use crate::gatt;
+use crate::profiles::ProfileValue;
+use crate::{EncodedValue, ValueEncoding};
+use serde::{Deserialize, Serialize};
+use std::sync::{Mutex, MutexGuard};
+use tauri::ipc::Channel;
+
+/// A value pushed by a peripheral through a notification or an indication.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Notification {
+    pub identifier: String,
+    pub service: String,
+    pub characteristic: String,
+    pub value: EncodedValue,
+    /// Milliseconds since the Unix epoch at which the value was received.
+    pub timestamp: u64,
+    /// The value decoded, for the characteristics [`profiles`] knows.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<ProfileValue>,
+}
+
+struct Subscription {
+    identifier: String,
+    service: String,
+    characteristic: String,
+    encoding: ValueEncoding,
+    channel: Channel<Notification>,
+}
+
+impl Subscription {
+    fn is_for(&self, identifier: &str, service: &str, characteristic: &str) -> bool {
+        self.identifier == identifier
+            && gatt::same_uuid(&self.service, service)
+            && gatt::same_uuid(&self.characteristic, characteristic)
+    }
+}
+
+/// The channels streaming the values of subscribed characteristics.
+///
+/// Subscriptions outlive a lost connection, the backend enables the notifications again once
+/// the peripheral is reconnected. They end with `unsubscribe` or an explicit disconnection.
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    subscriptions: Mutex<Vec<Subscription>>,
+}
+
+impl Subscriptions {
+    pub(crate) fn add(
+        &self,
+        identifier: &str,
+        service: &str,
+        characteristic: &str,
+        encoding: ValueEncoding,
+        channel: Channel<Notification>,
+    ) {
+        self.lock().push(Subscription {
+            identifier: identifier.to_string(),
+            service: gatt::normalize_uuid(service),
+            characteristic: gatt::normalize_uuid(characteristic),
+            encoding,
+            channel,
+        });
+    }
+
+    /// Drop the channels of a characteristic, returns whether there were any.
+    pub(crate) fn remove(&self, identifier: &str, service: &str, characteristic: &str) -> bool {
+        let mut subscriptions = self.lock();
+        let count = subscriptions.len();
+        subscriptions
+            .retain(|subscription| !subscription.is_for(identifier, service, characteristic));
+        subscriptions.len() != count
+    }
+
+    /// Drop every channel of a device.
+    pub(crate) fn remove_device(&self, identifier: &str) {
+        self.lock()
+            .retain(|subscription| subscription.identifier != identifier);
+    }
+
+    /// Send a notification to the channels of its characteristic, `value` holds its raw bytes
+    /// which each channel receives in the encoding it asked for.
+    pub(crate) fn send(&self, notification: &Notification, value: &[u8]) {
+        // a channel whose webview went away fails to send and is dropped.
+        self.lock().retain(|subscription| {
+            !subscription.is_for(
+                &notification.identifier,
+                &notification.service,
+                &notification.characteristic,
+            ) || subscription
+                .channel
+                .send(Notification {
+                    value: EncodedValue::encode(value.to_vec(), subscription.encoding),
+                    ..notification.clone()
+                })
+                .is_ok()
+        });
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Subscription>> {
+        self.subscriptions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,103 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::sync::Mutex;
+
+/// Identifies one registered request, to cancel it without touching the others on its key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct RequestToken(u64);
+
+/// The requests waiting on each key.
+type Waiters<T> = HashMap<String, Vec<(RequestToken, Sender<T>)>>;
+
+/// Requests waiting for an answer delivered by a native callback, keyed by what they are about.
+///
+/// Several requests for the same key share the answer.
+pub(crate) struct PendingRequests<T> {
+    waiters: Mutex<Waiters<T>>,
+    next_token: AtomicU64,
+}
+
+impl<T> Default for PendingRequests<T> {
+    fn default() -> Self {
+        PendingRequests {
+            waiters: Mutex::new(HashMap::new()),
+            next_token: AtomicU64::new(0),
+        }
+    }
+}
+
+impl<T: Clone> PendingRequests<T> {
+    /// Register a request, to be done before starting the operation so the answer can't be missed.
+    pub(crate) fn register(&self, key: &str) -> (RequestToken, Receiver<T>) {
+        let token = RequestToken(self.next_token.fetch_add(1, Ordering::Relaxed));
+        let (sender, receiver) = channel();
+        self.lock()
+            .entry(key.to_string())
+            .or_default()
+            .push((token, sender));
+        (token, receiver)
+    }
+
+    /// Hand the answer to every request waiting on `key`.
+    pub(crate) fn resolve(&self, key: &str, value: T) {
+        let waiters = self.lock().remove(key).unwrap_or_default();
+        for (_, waiter) in waiters {
+            let _ = waiter.send(value.clone());
+        }
+    }
+
+    /// Forget the request `token` on `key`, e.g. when its operation could not be started or timed
+    /// out. Returns whether other requests still wait on `key`.
+    pub(crate) fn cancel(&self, key: &str, token: RequestToken) -> bool {
+        let mut waiters = self.lock();
+        let Some(requests) = waiters.get_mut(key) else {
+            return false;
+        };
+        requests.retain(|(registered, _)| *registered != token);
+        if requests.is_empty() {
+            waiters.remove(key);
+            return false;
+        }
+        true
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Waiters<T>> {
+        self.waiters.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_on_a_key_share_the_answer() {
+        let requests = PendingRequests::default();
+        let (_, first) = requests.register("Connect:AA");
+        let (_, second) = requests.register("Connect:AA");
+        let (_, other) = requests.register("Connect:BB");
+        requests.resolve("Connect:AA", 1);
+        assert_eq!(first.try_recv(), Ok(1));
+        assert_eq!(second.try_recv(), Ok(1));
+        assert!(other.try_recv().is_err());
+    }
+
+    #[test]
+    fn cancelling_a_request_leaves_the_others_waiting() {
+        let requests = PendingRequests::default();
+        let (first, cancelled) = requests.register("Connect:AA");
+        let (second, waiting) = requests.register("Connect:AA");
+        assert!(requests.cancel("Connect:AA", first));
+        // a cancelled request is disconnected, not answered.
+        assert!(cancelled.try_recv().is_err());
+        requests.resolve("Connect:AA", 1);
+        assert_eq!(waiting.try_recv(), Ok(1));
+
+        // cancelling after the answer is a no-op.
+        assert!(!requests.cancel("Connect:AA", second));
+        let (last, _receiver) = requests.register("Connect:AA");
+        assert!(!requests.cancel("Connect:AA", last));
+        assert!(requests.lock().is_empty());
+    }
+}
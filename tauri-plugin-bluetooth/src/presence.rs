@@ -0,0 +1,747 @@
+use crate::clock::{check_seconds, seconds, Clock, SystemClock};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
+
+/// `unlock_rssi` value that never unlocks, same as `UNLOCK_DISABLED` on the Swift side.
+pub const UNLOCK_DISABLED: i32 = 1;
+/// `lock_rssi` value that never locks, same as `LOCK_DISABLED` on the Swift side.
+pub const LOCK_DISABLED: i32 = -100;
+/// RSSI values a controller can report, in dBm.
+const RSSI_RANGE: std::ops::RangeInclusive<i32> = -127..=20;
+
+/// Thresholds and timeouts of the presence state machine, the defaults match the Swift `BLE` class.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct PresenceConfig {
+    /// A reading below this starts the proximity timeout.
+    pub lock_rssi: i32,
+    /// A reading at or above this marks the user as present again.
+    pub unlock_rssi: i32,
+    /// Seconds the signal may stay below `lock_rssi` before the user is considered away.
+    pub proximity_timeout: f64,
+    /// Seconds without any reading before the device is considered lost.
+    pub signal_timeout: f64,
+    /// Readings are ignored until the device has been seen at or above this once, just like
+    /// weaker devices are never picked up while scanning.
+    pub threshold_rssi: i32,
+    /// Seconds between RSSI reads in active mode.
+    pub active_read_interval: f64,
+}
+
+impl PresenceConfig {
+    /// Check the thresholds and timeouts, to be done on the configurations coming from the
+    /// outside. `lock_rssi` above `unlock_rssi` would lock and unlock on the same readings.
+    pub fn validate(&self) -> crate::Result<()> {
+        for (name, rssi) in [
+            ("lockRssi", self.lock_rssi),
+            ("unlockRssi", self.unlock_rssi),
+            ("thresholdRssi", self.threshold_rssi),
+        ] {
+            if !RSSI_RANGE.contains(&rssi) {
+                return Err(crate::Error::InvalidValue(format!("invalid {name} {rssi}")));
+            }
+        }
+        if self.lock_rssi > self.unlock_rssi {
+            return Err(crate::Error::InvalidValue(format!(
+                "lockRssi {} is above unlockRssi {}",
+                self.lock_rssi, self.unlock_rssi
+            )));
+        }
+        check_seconds("proximityTimeout", self.proximity_timeout)?;
+        check_seconds("signalTimeout", self.signal_timeout)?;
+        check_seconds("activeReadInterval", self.active_read_interval)
+    }
+}
+
+impl Default for PresenceConfig {
+    fn default() -> Self {
+        PresenceConfig {
+            lock_rssi: -75,
+            unlock_rssi: -60,
+            proximity_timeout: 5.0,
+            signal_timeout: 60.0,
+            threshold_rssi: -70,
+            active_read_interval: 2.0,
+        }
+    }
+}
+
+/// Why the presence changed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresenceReason {
+    /// The signal came back above `unlock_rssi`.
+    Close,
+    /// The signal stayed below `lock_rssi` for `proximity_timeout`.
+    Away,
+    /// Nothing was heard from the device for `signal_timeout`.
+    Lost,
+    /// The connection dropped and did not come back within `proximity_timeout`.
+    Disconnected,
+    /// The adapter was powered off.
+    PoweredOff,
+}
+
+/// The same spelling as the serialized reason.
+impl Display for PresenceReason {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            PresenceReason::Close => "close",
+            PresenceReason::Away => "away",
+            PresenceReason::Lost => "lost",
+            PresenceReason::Disconnected => "disconnected",
+            PresenceReason::PoweredOff => "poweredOff",
+        };
+        f.write_str(reason)
+    }
+}
+
+/// A change of presence emitted by [`PresenceMonitor`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PresenceTransition {
+    pub presence: bool,
+    pub reason: PresenceReason,
+    pub at: Instant,
+    /// The reading that caused the transition, if any.
+    pub rssi: Option<i32>,
+}
+
+/// How the presence of the monitored devices adds up to the presence of the user.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PresencePolicy {
+    /// Present while one of the devices is.
+    #[default]
+    Any,
+    /// Present while every device is.
+    All,
+    /// Present while at least this many devices are.
+    Quorum(usize),
+}
+
+impl PresencePolicy {
+    /// A quorum of 0 would hold the user present with no device around.
+    pub fn validate(self) -> crate::Result<()> {
+        match self {
+            PresencePolicy::Quorum(0) => Err(crate::Error::InvalidValue(
+                "the quorum must be at least 1".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn is_met(self, present: usize, monitored: usize) -> bool {
+        match self {
+            PresencePolicy::Any => present > 0,
+            PresencePolicy::All => monitored > 0 && present == monitored,
+            PresencePolicy::Quorum(quorum) => present >= quorum,
+        }
+    }
+}
+
+/// The presence of the user, out of the presence of every monitored device.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AggregatePresence {
+    pub presence: bool,
+    pub policy: PresencePolicy,
+    /// The monitored devices present, sorted.
+    pub present: Vec<String>,
+    pub monitored: usize,
+    /// The device whose change decided it, none when the policy or the monitored devices changed.
+    pub identifier: Option<String>,
+}
+
+/// An RSSI reading of the monitored device.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RssiSample {
+    pub rssi: i32,
+    pub at: Instant,
+}
+
+/// The lock/unlock logic of the Swift `BLE` class as a pure state machine.
+///
+/// Feed it readings and disconnects, and call [`poll`](Self::poll) regularly so the timeouts can
+/// fire. Like `startMonitor`, monitoring starts with the user present.
+pub struct PresenceMonitor<C: Clock = SystemClock> {
+    config: PresenceConfig,
+    clock: C,
+    presence: bool,
+    acquired: bool,
+    active: bool,
+    proximity_deadline: Option<(Instant, PresenceReason)>,
+    signal_deadline: Option<Instant>,
+    last_read_at: Option<Instant>,
+    last_read_request: Option<Instant>,
+}
+
+impl PresenceMonitor<SystemClock> {
+    pub fn new(config: PresenceConfig) -> Self {
+        Self::with_clock(config, SystemClock)
+    }
+}
+
+impl<C: Clock> PresenceMonitor<C> {
+    /// A device that is never heard is lost once the signal timeout passes, the same as one
+    /// that goes silent.
+    pub fn with_clock(config: PresenceConfig, clock: C) -> Self {
+        let signal_deadline = clock.now() + seconds(config.signal_timeout);
+        PresenceMonitor {
+            config,
+            clock,
+            presence: true,
+            acquired: false,
+            active: true,
+            proximity_deadline: None,
+            signal_deadline: Some(signal_deadline),
+            last_read_at: None,
+            last_read_request: None,
+        }
+    }
+
+    pub fn config(&self) -> &PresenceConfig {
+        &self.config
+    }
+
+    /// Replace the thresholds, pending timeouts keep their deadline.
+    pub fn set_config(&mut self, config: PresenceConfig) {
+        self.config = config;
+    }
+
+    pub fn presence(&self) -> bool {
+        self.presence
+    }
+
+    /// When the last accepted reading was taken.
+    pub fn last_read_at(&self) -> Option<Instant> {
+        self.last_read_at
+    }
+
+    /// In passive mode the device is only heard through its advertisements, so no reads are due.
+    pub fn set_passive_mode(&mut self, passive: bool) {
+        self.active = !passive;
+        self.last_read_request = None;
+    }
+
+    /// Record a reading taken now.
+    pub fn on_rssi(&mut self, rssi: i32) -> Option<PresenceTransition> {
+        let at = self.clock.now();
+        self.on_sample(RssiSample { rssi, at })
+    }
+
+    /// Record a timestamped reading.
+    pub fn on_sample(&mut self, sample: RssiSample) -> Option<PresenceTransition> {
+        let RssiSample { rssi, at } = sample;
+        if !self.acquired {
+            if rssi < self.config.threshold_rssi {
+                return None;
+            }
+            self.acquired = true;
+        }
+        self.last_read_at = Some(at);
+        self.signal_deadline = Some(at + seconds(self.config.signal_timeout));
+
+        if rssi >= self.config.lock_rssi {
+            self.proximity_deadline = None;
+        } else if self.presence && self.proximity_deadline.is_none() {
+            let deadline = at + seconds(self.config.proximity_timeout);
+            self.proximity_deadline = Some((deadline, PresenceReason::Away));
+        }
+
+        if rssi >= self.config.unlock_rssi && !self.presence {
+            self.presence = true;
+            self.proximity_deadline = None;
+            return Some(PresenceTransition {
+                presence: true,
+                reason: PresenceReason::Close,
+                at,
+                rssi: Some(rssi),
+            });
+        }
+        None
+    }
+
+    /// The connection to the device dropped, the user is gone unless a good reading shows up
+    /// within the proximity timeout.
+    pub fn on_disconnect(&mut self) {
+        let at = self.clock.now();
+        if self.presence && self.proximity_deadline.is_none() {
+            let deadline = at + seconds(self.config.proximity_timeout);
+            self.proximity_deadline = Some((deadline, PresenceReason::Disconnected));
+        }
+    }
+
+    /// The adapter went off, presence is lost right away.
+    pub fn on_power_off(&mut self) -> Option<PresenceTransition> {
+        let at = self.clock.now();
+        self.proximity_deadline = None;
+        self.signal_deadline = None;
+        self.leave(PresenceReason::PoweredOff, at)
+    }
+
+    /// Fire the timeouts that expired.
+    pub fn poll(&mut self) -> Option<PresenceTransition> {
+        let now = self.clock.now();
+        if let Some((deadline, reason)) = self.proximity_deadline {
+            if deadline <= now {
+                self.proximity_deadline = None;
+                if let Some(transition) = self.leave(reason, now) {
+                    return Some(transition);
+                }
+            }
+        }
+        if let Some(deadline) = self.signal_deadline {
+            if deadline <= now {
+                self.signal_deadline = None;
+                return self.leave(PresenceReason::Lost, now);
+            }
+        }
+        None
+    }
+
+    /// Whether an RSSI read should be issued now in active mode, a `true` answer counts as the
+    /// read being requested.
+    pub fn take_read_due(&mut self) -> bool {
+        if !self.active {
+            return false;
+        }
+        let now = self.clock.now();
+        let due = self.last_read_request.map_or(true, |at| {
+            at + seconds(self.config.active_read_interval) <= now
+        });
+        if due {
+            self.last_read_request = Some(now);
+        }
+        due
+    }
+
+    /// The earliest instant at which [`poll`](Self::poll) may produce a transition.
+    pub fn next_deadline(&self) -> Option<Instant> {
+        let proximity = self.proximity_deadline.map(|(deadline, _)| deadline);
+        match (proximity, self.signal_deadline) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+
+    fn leave(&mut self, reason: PresenceReason, at: Instant) -> Option<PresenceTransition> {
+        if !self.presence {
+            return None;
+        }
+        self.presence = false;
+        Some(PresenceTransition {
+            presence: false,
+            reason,
+            at,
+            rssi: None,
+        })
+    }
+}
+
+struct MonitorsState<C: Clock> {
+    passive: bool,
+    policy: PresencePolicy,
+    /// The aggregate presence last returned by `update_aggregate`.
+    aggregate: bool,
+    monitors: HashMap<String, PresenceMonitor<C>>,
+}
+
+/// A [`PresenceMonitor`] per monitored device, each with its own thresholds, adding up to an
+/// [`AggregatePresence`] under a [`PresencePolicy`].
+///
+/// Feed it the readings and drops of every device and the adapter power, and call
+/// [`poll`](Self::poll) regularly: it returns the transitions and the devices to read. After a
+/// transition or a change of the monitored devices, [`update_aggregate`](Self::update_aggregate)
+/// tells whether the aggregate presence changed.
+pub struct Monitors<C: Clock + Clone = SystemClock> {
+    clock: C,
+    state: Mutex<MonitorsState<C>>,
+}
+
+impl Default for Monitors<SystemClock> {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock + Clone> Monitors<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Monitors {
+            clock,
+            state: Mutex::new(MonitorsState {
+                passive: false,
+                policy: PresencePolicy::default(),
+                aggregate: false,
+                monitors: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Start monitoring a device with the user present, or replace the thresholds of a device
+    /// already monitored.
+    pub fn start(&self, identifier: &str, config: PresenceConfig) {
+        let mut state = self.lock();
+        let passive = state.passive;
+        match state.monitors.get_mut(identifier) {
+            Some(monitor) => monitor.set_config(config),
+            None => {
+                let mut monitor = PresenceMonitor::with_clock(config, self.clock.clone());
+                monitor.set_passive_mode(passive);
+                state.monitors.insert(identifier.to_string(), monitor);
+            }
+        }
+    }
+
+    pub fn stop(&self, identifier: &str) -> bool {
+        self.lock().monitors.remove(identifier).is_some()
+    }
+
+    pub fn is_monitoring(&self, identifier: &str) -> bool {
+        self.lock().monitors.contains_key(identifier)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().monitors.is_empty()
+    }
+
+    /// The presence of a monitored device.
+    pub fn presence(&self, identifier: &str) -> Option<bool> {
+        self.lock()
+            .monitors
+            .get(identifier)
+            .map(PresenceMonitor::presence)
+    }
+
+    pub fn policy(&self) -> PresencePolicy {
+        self.lock().policy
+    }
+
+    pub fn set_policy(&self, policy: PresencePolicy) {
+        self.lock().policy = policy;
+    }
+
+    /// The aggregate presence under the current policy.
+    pub fn aggregate(&self) -> AggregatePresence {
+        aggregate(&self.lock(), None)
+    }
+
+    /// The aggregate presence if it changed since the last call, `identifier` being the device
+    /// whose transition caused it.
+    pub fn update_aggregate(&self, identifier: Option<&str>) -> Option<AggregatePresence> {
+        let mut state = self.lock();
+        let aggregate = aggregate(&state, identifier);
+        if aggregate.presence == state.aggregate {
+            return None;
+        }
+        state.aggregate = aggregate.presence;
+        Some(aggregate)
+    }
+
+    /// Applies to the devices monitored later on as well.
+    pub fn set_passive_mode(&self, passive: bool) {
+        let mut state = self.lock();
+        state.passive = passive;
+        for monitor in state.monitors.values_mut() {
+            monitor.set_passive_mode(passive);
+        }
+    }
+
+    /// Record a reading taken now, readings of the devices not monitored are ignored.
+    pub fn on_rssi(&self, identifier: &str, rssi: i32) -> Option<PresenceTransition> {
+        self.lock().monitors.get_mut(identifier)?.on_rssi(rssi)
+    }
+
+    pub fn on_disconnect(&self, identifier: &str) {
+        if let Some(monitor) = self.lock().monitors.get_mut(identifier) {
+            monitor.on_disconnect();
+        }
+    }
+
+    pub fn on_power_off(&self) -> Vec<(String, PresenceTransition)> {
+        self.lock()
+            .monitors
+            .iter_mut()
+            .filter_map(|(identifier, monitor)| Some((identifier.clone(), monitor.on_power_off()?)))
+            .collect()
+    }
+
+    /// Fire the timeouts that expired, and list the devices whose RSSI read is due.
+    pub fn poll(&self) -> (Vec<(String, PresenceTransition)>, Vec<String>) {
+        let mut transitions = vec![];
+        let mut due = vec![];
+        for (identifier, monitor) in self.lock().monitors.iter_mut() {
+            if let Some(transition) = monitor.poll() {
+                transitions.push((identifier.clone(), transition));
+            }
+            if monitor.take_read_due() {
+                due.push(identifier.clone());
+            }
+        }
+        (transitions, due)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, MonitorsState<C>> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn aggregate<C: Clock>(state: &MonitorsState<C>, identifier: Option<&str>) -> AggregatePresence {
+    let mut present: Vec<String> = state
+        .monitors
+        .iter()
+        .filter(|(_, monitor)| monitor.presence())
+        .map(|(identifier, _)| identifier.clone())
+        .collect();
+    present.sort();
+    AggregatePresence {
+        presence: state.policy.is_met(present.len(), state.monitors.len()),
+        policy: state.policy,
+        present,
+        monitored: state.monitors.len(),
+        identifier: identifier.map(str::to_string),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+    use std::time::Duration;
+
+    fn monitor() -> (PresenceMonitor<ManualClock>, ManualClock) {
+        let clock = ManualClock::new();
+        let monitor = PresenceMonitor::with_clock(PresenceConfig::default(), clock.clone());
+        (monitor, clock)
+    }
+
+    fn secs(value: f64) -> Duration {
+        Duration::from_secs_f64(value)
+    }
+
+    fn reason(transition: Option<PresenceTransition>) -> Option<(bool, PresenceReason)> {
+        transition.map(|transition| (transition.presence, transition.reason))
+    }
+
+    #[test]
+    fn reasons_display_as_they_serialize() {
+        for reason in [
+            PresenceReason::Close,
+            PresenceReason::Away,
+            PresenceReason::Lost,
+            PresenceReason::Disconnected,
+            PresenceReason::PoweredOff,
+        ] {
+            assert_eq!(
+                serde_json::to_value(reason).unwrap(),
+                reason.to_string().as_str()
+            );
+        }
+    }
+
+    #[test]
+    fn readings_below_the_threshold_are_ignored_until_acquired() {
+        let (mut monitor, clock) = monitor();
+        assert_eq!(monitor.on_rssi(-90), None);
+        assert_eq!(monitor.last_read_at(), None);
+        assert_eq!(monitor.next_deadline(), Some(clock.now() + secs(60.0)));
+
+        assert_eq!(monitor.on_rssi(-70), None);
+        assert_eq!(monitor.last_read_at(), Some(clock.now()));
+        // acquired, a weak reading now counts.
+        assert_eq!(monitor.on_rssi(-90), None);
+        assert!(monitor.next_deadline().is_some());
+    }
+
+    #[test]
+    fn a_weak_signal_locks_after_the_proximity_timeout() {
+        let (mut monitor, clock) = monitor();
+        monitor.on_rssi(-50);
+        assert_eq!(monitor.on_rssi(-80), None);
+        clock.advance(secs(4.9));
+        assert_eq!(monitor.poll(), None);
+        clock.advance(secs(0.1));
+        assert_eq!(reason(monitor.poll()), Some((false, PresenceReason::Away)));
+        assert!(!monitor.presence());
+        // the proximity deadline fired once.
+        assert_eq!(monitor.poll(), None);
+    }
+
+    #[test]
+    fn a_reading_above_lock_cancels_the_proximity_timeout() {
+        let (mut monitor, clock) = monitor();
+        monitor.on_rssi(-50);
+        monitor.on_rssi(-80);
+        clock.advance(secs(3.0));
+        monitor.on_rssi(-70);
+        clock.advance(secs(3.0));
+        assert_eq!(monitor.poll(), None);
+        assert!(monitor.presence());
+    }
+
+    #[test]
+    fn only_a_reading_at_unlock_brings_the_user_back() {
+        let (mut monitor, clock) = monitor();
+        monitor.on_rssi(-50);
+        monitor.on_rssi(-80);
+        clock.advance(secs(5.0));
+        monitor.poll();
+
+        // between lock and unlock: no longer away, not close enough either.
+        assert_eq!(monitor.on_rssi(-65), None);
+        assert!(!monitor.presence());
+        let transition = monitor.on_rssi(-60).unwrap();
+        assert_eq!(
+            (transition.presence, transition.reason, transition.rssi),
+            (true, PresenceReason::Close, Some(-60))
+        );
+        assert_eq!(transition.at, clock.now());
+    }
+
+    #[test]
+    fn silence_loses_the_device_after_the_signal_timeout() {
+        let (mut monitor, clock) = monitor();
+        monitor.on_rssi(-50);
+        clock.advance(secs(59.0));
+        assert_eq!(monitor.poll(), None);
+        monitor.on_rssi(-50);
+        clock.advance(secs(59.0));
+        assert_eq!(monitor.poll(), None);
+        clock.advance(secs(1.0));
+        assert_eq!(reason(monitor.poll()), Some((false, PresenceReason::Lost)));
+    }
+
+    #[test]
+    fn a_device_never_acquired_is_lost_after_the_signal_timeout() {
+        let (mut monitor, clock) = monitor();
+        clock.advance(secs(30.0));
+        assert_eq!(monitor.on_rssi(-90), None);
+        clock.advance(secs(29.0));
+        assert_eq!(monitor.poll(), None);
+        assert!(monitor.presence());
+        clock.advance(secs(1.0));
+        assert_eq!(reason(monitor.poll()), Some((false, PresenceReason::Lost)));
+        assert!(!monitor.presence());
+    }
+
+    #[test]
+    fn a_disconnect_locks_unless_the_device_comes_back() {
+        let (mut monitor, clock) = monitor();
+        monitor.on_rssi(-50);
+        monitor.on_disconnect();
+        clock.advance(secs(2.0));
+        monitor.on_rssi(-50);
+        clock.advance(secs(5.0));
+        assert_eq!(monitor.poll(), None);
+
+        monitor.on_disconnect();
+        clock.advance(secs(5.0));
+        assert_eq!(
+            reason(monitor.poll()),
+            Some((false, PresenceReason::Disconnected))
+        );
+    }
+
+    #[test]
+    fn powering_off_locks_right_away_once() {
+        let (mut monitor, _) = monitor();
+        monitor.on_rssi(-50);
+        assert_eq!(
+            reason(monitor.on_power_off()),
+            Some((false, PresenceReason::PoweredOff))
+        );
+        assert_eq!(monitor.on_power_off(), None);
+        assert_eq!(monitor.next_deadline(), None);
+    }
+
+    #[test]
+    fn reads_are_due_every_interval_in_active_mode_only() {
+        let (mut monitor, clock) = monitor();
+        assert!(monitor.take_read_due());
+        assert!(!monitor.take_read_due());
+        clock.advance(secs(1.9));
+        assert!(!monitor.take_read_due());
+        clock.advance(secs(0.1));
+        assert!(monitor.take_read_due());
+
+        monitor.set_passive_mode(true);
+        clock.advance(secs(10.0));
+        assert!(!monitor.take_read_due());
+        monitor.set_passive_mode(false);
+        assert!(monitor.take_read_due());
+    }
+
+    #[test]
+    fn out_of_range_timeouts_are_rejected_and_never_overflow() {
+        for timeout in [f64::INFINITY, f64::NAN, -1.0, 1e300] {
+            let config = PresenceConfig {
+                proximity_timeout: timeout,
+                signal_timeout: timeout,
+                active_read_interval: timeout,
+                ..Default::default()
+            };
+            assert!(config.validate().is_err(), "{timeout} should be rejected");
+
+            let clock = ManualClock::new();
+            let mut monitor = PresenceMonitor::with_clock(config, clock.clone());
+            monitor.on_rssi(-50);
+            monitor.on_rssi(-80);
+            monitor.on_disconnect();
+            monitor.take_read_due();
+            monitor.poll();
+        }
+        assert!(PresenceConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn inconsistent_thresholds_are_rejected() {
+        let config = |lock_rssi, unlock_rssi, threshold_rssi| PresenceConfig {
+            lock_rssi,
+            unlock_rssi,
+            threshold_rssi,
+            ..Default::default()
+        };
+        assert!(config(-60, -75, -70).validate().is_err());
+        assert!(config(-200, -60, -70).validate().is_err());
+        assert!(config(-75, 100, -70).validate().is_err());
+        assert!(config(-75, -60, i32::MIN).validate().is_err());
+        assert!(config(-70, -70, -70).validate().is_ok());
+        assert!(config(LOCK_DISABLED, UNLOCK_DISABLED, -70)
+            .validate()
+            .is_ok());
+    }
+
+    #[test]
+    fn the_policy_adds_up_the_monitored_devices() {
+        let clock = ManualClock::new();
+        let monitors = Monitors::with_clock(clock.clone());
+        monitors.start("A", PresenceConfig::default());
+        monitors.start("B", PresenceConfig::default());
+        // monitoring starts with every device present.
+        assert!(monitors.update_aggregate(None).unwrap().presence);
+
+        assert_eq!(
+            reason(monitors.on_power_off().first().map(|(_, t)| *t)),
+            Some((false, PresenceReason::PoweredOff))
+        );
+        let aggregate = monitors.update_aggregate(None).unwrap();
+        assert!(!aggregate.presence);
+        assert_eq!(aggregate.monitored, 2);
+
+        monitors.on_rssi("B", -50);
+        let aggregate = monitors.update_aggregate(Some("B")).unwrap();
+        assert_eq!(
+            (aggregate.presence, aggregate.present.as_slice()),
+            (true, ["B".to_string()].as_slice())
+        );
+        assert_eq!(aggregate.identifier.as_deref(), Some("B"));
+
+        monitors.set_policy(PresencePolicy::All);
+        assert!(!monitors.update_aggregate(None).unwrap().presence);
+        monitors.set_policy(PresencePolicy::Quorum(1));
+        assert!(monitors.update_aggregate(None).unwrap().presence);
+        assert_eq!(monitors.update_aggregate(None), None);
+    }
+}
@@ -0,0 +1,915 @@
+//! Decoders of the standard GATT characteristics, following the field layouts of the Bluetooth
+//! SIG service specifications. Multi-byte fields are little endian.
+
+use crate::gatt;
+use crate::hex;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+
+/// A characteristic value decoded by [`decode`].
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", content = "value", rename_all = "camelCase")]
+pub enum ProfileValue {
+    HeartRate(HeartRateMeasurement),
+    /// Percent, 0 to 100.
+    BatteryLevel(u8),
+    Temperature(TemperatureMeasurement),
+    Weight(WeightMeasurement),
+    CyclingPower(CyclingPowerMeasurement),
+    RunningSpeedCadence(RscMeasurement),
+    CurrentTime(CurrentTime),
+}
+
+/// Heart Rate Measurement, 0x2A37.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HeartRateMeasurement {
+    /// Beats per minute.
+    pub bpm: u16,
+    /// Whether the sensor touches the skin, `None` when the sensor can't tell.
+    pub sensor_contact: Option<bool>,
+    /// Kilojoules since the last reset.
+    pub energy_expended: Option<u16>,
+    /// Milliseconds between the last beats, oldest first.
+    pub rr_intervals: Vec<f64>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TemperatureUnit {
+    Celsius,
+    Fahrenheit,
+}
+
+/// Where a temperature is taken, the Temperature Type characteristic 0x2A1D.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum TemperatureType {
+    Armpit,
+    Body,
+    Ear,
+    Finger,
+    GastroIntestinalTract,
+    Mouth,
+    Rectum,
+    Toe,
+    Tympanum,
+    Other(u8),
+}
+
+impl From<u8> for TemperatureType {
+    fn from(value: u8) -> Self {
+        match value {
+            1 => TemperatureType::Armpit,
+            2 => TemperatureType::Body,
+            3 => TemperatureType::Ear,
+            4 => TemperatureType::Finger,
+            5 => TemperatureType::GastroIntestinalTract,
+            6 => TemperatureType::Mouth,
+            7 => TemperatureType::Rectum,
+            8 => TemperatureType::Toe,
+            9 => TemperatureType::Tympanum,
+            other => TemperatureType::Other(other),
+        }
+    }
+}
+
+/// Temperature Measurement, 0x2A1C.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TemperatureMeasurement {
+    /// `None` when the thermometer reports an invalid reading.
+    pub temperature: Option<f64>,
+    pub unit: TemperatureUnit,
+    pub timestamp: Option<DateTime>,
+    pub temperature_type: Option<TemperatureType>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum WeightUnit {
+    Kilogram,
+    Pound,
+}
+
+/// Weight Measurement, 0x2A9D.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WeightMeasurement {
+    /// In `unit`, `None` when the measurement was unsuccessful.
+    pub weight: Option<f64>,
+    pub unit: WeightUnit,
+    pub timestamp: Option<DateTime>,
+    pub user_id: Option<u8>,
+    pub bmi: Option<f64>,
+    /// Meters for kilograms, inches for pounds.
+    pub height: Option<f64>,
+}
+
+/// Cumulative revolutions and the time of the last one.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RevolutionData {
+    pub revolutions: u32,
+    /// Seconds, wraps around every 32 (wheel) or 64 (crank) seconds.
+    pub last_event_time: f64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Extremes {
+    pub max: f64,
+    pub min: f64,
+}
+
+/// Cycling Power Measurement, 0x2A63.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CyclingPowerMeasurement {
+    /// Watts.
+    pub instantaneous_power: i16,
+    /// Percent of the power produced by the reference pedal.
+    pub pedal_power_balance: Option<f64>,
+    /// Whether the balance refers to the left pedal, it is unknown otherwise.
+    pub pedal_power_balance_left: bool,
+    /// Newton meters.
+    pub accumulated_torque: Option<f64>,
+    /// Whether the torque is measured at the crank, at the wheel otherwise.
+    pub torque_from_crank: bool,
+    pub wheel_revolutions: Option<RevolutionData>,
+    pub crank_revolutions: Option<RevolutionData>,
+    /// Newtons.
+    pub extreme_force: Option<Extremes>,
+    /// Newton meters.
+    pub extreme_torque: Option<Extremes>,
+    /// Degrees.
+    pub extreme_angles: Option<Extremes>,
+    /// Degrees.
+    pub top_dead_spot_angle: Option<u16>,
+    /// Degrees.
+    pub bottom_dead_spot_angle: Option<u16>,
+    /// Kilojoules.
+    pub accumulated_energy: Option<u16>,
+    pub offset_compensation: bool,
+}
+
+/// RSC Measurement, 0x2A53.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RscMeasurement {
+    /// Meters per second.
+    pub speed: f64,
+    /// Steps per minute.
+    pub cadence: u8,
+    /// Meters.
+    pub stride_length: Option<f64>,
+    /// Meters.
+    pub total_distance: Option<f64>,
+    /// Whether the user runs, walks otherwise.
+    pub running: bool,
+}
+
+/// Date Time, 0x2A08, fields are 0 when unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DateTime {
+    pub year: u16,
+    pub month: u8,
+    pub day: u8,
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+}
+
+/// Current Time, 0x2A2B.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CurrentTime {
+    pub date_time: DateTime,
+    /// 1 for Monday to 7 for Sunday, `None` when unknown.
+    pub day_of_week: Option<u8>,
+    /// Fraction of the second, in seconds.
+    pub fraction: f64,
+    pub manual_update: bool,
+    pub external_reference_update: bool,
+    pub time_zone_change: bool,
+    pub dst_change: bool,
+}
+
+/// Device Information service, 0x180A.
+pub const DEVICE_INFORMATION_SERVICE: &str = "180A";
+
+/// Where the vendor ID of a PnP ID is assigned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum VendorIdSource {
+    Bluetooth,
+    Usb,
+    Other(u8),
+}
+
+/// PnP ID, 0x2A50.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PnpId {
+    pub vendor_id_source: VendorIdSource,
+    pub vendor_id: u16,
+    pub product_id: u16,
+    /// Binary coded decimal `0xJJMN` for version JJ.M.N.
+    pub product_version: u16,
+}
+
+/// System ID, 0x2A23, both parts are hex encoded.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SystemId {
+    /// 40-bit identifier chosen by the manufacturer.
+    pub manufacturer_identifier: String,
+    /// IEEE OUI of the manufacturer.
+    pub organizationally_unique_identifier: String,
+}
+
+/// What the Device Information service reports, characteristics the device lacks are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceInformation {
+    pub manufacturer_name: Option<String>,
+    pub model_number: Option<String>,
+    pub serial_number: Option<String>,
+    pub hardware_revision: Option<String>,
+    pub firmware_revision: Option<String>,
+    pub software_revision: Option<String>,
+    pub system_id: Option<SystemId>,
+    pub pnp_id: Option<PnpId>,
+}
+
+impl DeviceInformation {
+    /// The characteristics of the service this struct covers.
+    pub const CHARACTERISTICS: [&'static str; 8] = [
+        "2A29", "2A24", "2A25", "2A27", "2A26", "2A28", "2A23", "2A50",
+    ];
+
+    /// Fill in the field of a characteristic from its value, unknown or malformed values are
+    /// ignored.
+    pub fn set(&mut self, characteristic: &str, data: &[u8]) {
+        match gatt::normalize_uuid(characteristic).as_str() {
+            "2A29" => self.manufacturer_name = decode_string(data),
+            "2A24" => self.model_number = decode_string(data),
+            "2A25" => self.serial_number = decode_string(data),
+            "2A27" => self.hardware_revision = decode_string(data),
+            "2A26" => self.firmware_revision = decode_string(data),
+            "2A28" => self.software_revision = decode_string(data),
+            "2A23" => self.system_id = decode_system_id(data),
+            "2A50" => self.pnp_id = decode_pnp_id(data),
+            _ => {}
+        }
+    }
+}
+
+/// The service a decoded characteristic belongs to, used when none is given.
+pub fn service_of(characteristic: &str) -> Option<&'static str> {
+    match gatt::normalize_uuid(characteristic).as_str() {
+        "2A37" => Some("180D"),
+        "2A19" => Some("180F"),
+        "2A1C" => Some("1809"),
+        "2A9D" => Some("181D"),
+        "2A63" => Some("1818"),
+        "2A53" => Some("1814"),
+        "2A2B" => Some("1805"),
+        _ => None,
+    }
+}
+
+/// Decode the value of a standard characteristic.
+pub fn decode(characteristic: &str, data: &[u8]) -> Result<ProfileValue> {
+    let uuid = gatt::normalize_uuid(characteristic);
+    let value = match uuid.as_str() {
+        "2A37" => decode_heart_rate(data).map(ProfileValue::HeartRate),
+        "2A19" => decode_battery_level(data).map(ProfileValue::BatteryLevel),
+        "2A1C" => decode_temperature(data).map(ProfileValue::Temperature),
+        "2A9D" => decode_weight(data).map(ProfileValue::Weight),
+        "2A63" => decode_cycling_power(data).map(ProfileValue::CyclingPower),
+        "2A53" => decode_rsc(data).map(ProfileValue::RunningSpeedCadence),
+        "2A2B" => decode_current_time(data).map(ProfileValue::CurrentTime),
+        _ => {
+            return Err(Error::InvalidValue(format!(
+                "no decoder for characteristic {uuid}"
+            )))
+        }
+    };
+    value.ok_or_else(|| {
+        let name = gatt::characteristic_name(&uuid).unwrap_or("characteristic");
+        Error::InvalidValue(format!("malformed {name} value"))
+    })
+}
+
+pub fn decode_heart_rate(data: &[u8]) -> Option<HeartRateMeasurement> {
+    let mut reader = Reader::new(data);
+    let flags = reader.u8()?;
+    let bpm = if flags & 0x01 != 0 {
+        reader.u16()?
+    } else {
+        reader.u8()? as u16
+    };
+    let sensor_contact = (flags & 0x04 != 0).then_some(flags & 0x02 != 0);
+    let energy_expended = reader.optional(flags & 0x08 != 0, Reader::u16)?;
+    let mut rr_intervals = vec![];
+    if flags & 0x10 != 0 {
+        while !reader.is_empty() {
+            rr_intervals.push(reader.u16()? as f64 * 1000.0 / 1024.0);
+        }
+    }
+    Some(HeartRateMeasurement {
+        bpm,
+        sensor_contact,
+        energy_expended,
+        rr_intervals,
+    })
+}
+
+pub fn decode_battery_level(data: &[u8]) -> Option<u8> {
+    Reader::new(data).u8().filter(|level| *level <= 100)
+}
+
+pub fn decode_temperature(data: &[u8]) -> Option<TemperatureMeasurement> {
+    let mut reader = Reader::new(data);
+    let flags = reader.u8()?;
+    let temperature = ieee11073_float(reader.u32()?);
+    let timestamp = reader.optional(flags & 0x02 != 0, Reader::date_time)?;
+    let temperature_type = reader.optional(flags & 0x04 != 0, Reader::u8)?;
+    Some(TemperatureMeasurement {
+        temperature,
+        unit: if flags & 0x01 != 0 {
+            TemperatureUnit::Fahrenheit
+        } else {
+            TemperatureUnit::Celsius
+        },
+        timestamp,
+        temperature_type: temperature_type.map(TemperatureType::from),
+    })
+}
+
+pub fn decode_weight(data: &[u8]) -> Option<WeightMeasurement> {
+    let mut reader = Reader::new(data);
+    let flags = reader.u8()?;
+    let imperial = flags & 0x01 != 0;
+    let (weight_resolution, height_resolution) = if imperial {
+        (0.01, 0.1)
+    } else {
+        (0.005, 0.001)
+    };
+    let weight = reader.u16()?;
+    let timestamp = reader.optional(flags & 0x02 != 0, Reader::date_time)?;
+    let user_id = reader.optional(flags & 0x04 != 0, Reader::u8)?;
+    let (bmi, height) = match reader.optional(flags & 0x08 != 0, |r| Some((r.u16()?, r.u16()?)))? {
+        Some((bmi, height)) => (
+            Some(bmi as f64 * 0.1),
+            Some(height as f64 * height_resolution),
+        ),
+        None => (None, None),
+    };
+    Some(WeightMeasurement {
+        // 0xFFFF reports an unsuccessful measurement.
+        weight: (weight != u16::MAX).then_some(weight as f64 * weight_resolution),
+        unit: if imperial {
+            WeightUnit::Pound
+        } else {
+            WeightUnit::Kilogram
+        },
+        timestamp,
+        // 255 is the unknown user.
+        user_id: user_id.filter(|id| *id != u8::MAX),
+        bmi,
+        height,
+    })
+}
+
+pub fn decode_cycling_power(data: &[u8]) -> Option<CyclingPowerMeasurement> {
+    let mut reader = Reader::new(data);
+    let flags = reader.u16()?;
+    let flag = |bit: u16| flags & (1 << bit) != 0;
+    let instantaneous_power = reader.i16()?;
+    let pedal_power_balance = reader.optional(flag(0), |r| Some(r.u8()? as f64 / 2.0))?;
+    let accumulated_torque = reader.optional(flag(2), |r| Some(r.u16()? as f64 / 32.0))?;
+    let wheel_revolutions = reader.optional(flag(4), |r| {
+        Some(RevolutionData {
+            revolutions: r.u32()?,
+            last_event_time: r.u16()? as f64 / 2048.0,
+        })
+    })?;
+    let crank_revolutions = reader.optional(flag(5), |r| {
+        Some(RevolutionData {
+            revolutions: r.u16()? as u32,
+            last_event_time: r.u16()? as f64 / 1024.0,
+        })
+    })?;
+    let extreme_force = reader.optional(flag(6), |r| {
+        Some(Extremes {
+            max: r.i16()? as f64,
+            min: r.i16()? as f64,
+        })
+    })?;
+    let extreme_torque = reader.optional(flag(7), |r| {
+        Some(Extremes {
+            max: r.i16()? as f64 / 32.0,
+            min: r.i16()? as f64 / 32.0,
+        })
+    })?;
+    // two 12-bit angles packed in 3 bytes, the maximum first.
+    let extreme_angles = reader.optional(flag(8), |r| {
+        let (low, middle, high) = (r.u8()? as u16, r.u8()? as u16, r.u8()? as u16);
+        Some(Extremes {
+            max: (low | (middle & 0x0F) << 8) as f64,
+            min: (middle >> 4 | high << 4) as f64,
+        })
+    })?;
+    let top_dead_spot_angle = reader.optional(flag(9), Reader::u16)?;
+    let bottom_dead_spot_angle = reader.optional(flag(10), Reader::u16)?;
+    let accumulated_energy = reader.optional(flag(11), Reader::u16)?;
+    Some(CyclingPowerMeasurement {
+        instantaneous_power,
+        pedal_power_balance,
+        pedal_power_balance_left: flag(1),
+        accumulated_torque,
+        torque_from_crank: flag(3),
+        wheel_revolutions,
+        crank_revolutions,
+        extreme_force,
+        extreme_torque,
+        extreme_angles,
+        top_dead_spot_angle,
+        bottom_dead_spot_angle,
+        accumulated_energy,
+        offset_compensation: flag(12),
+    })
+}
+
+pub fn decode_rsc(data: &[u8]) -> Option<RscMeasurement> {
+    let mut reader = Reader::new(data);
+    let flags = reader.u8()?;
+    let speed = reader.u16()? as f64 / 256.0;
+    let cadence = reader.u8()?;
+    let stride_length = reader.optional(flags & 0x01 != 0, |r| Some(r.u16()? as f64 / 100.0))?;
+    let total_distance = reader.optional(flags & 0x02 != 0, |r| Some(r.u32()? as f64 / 10.0))?;
+    Some(RscMeasurement {
+        speed,
+        cadence,
+        stride_length,
+        total_distance,
+        running: flags & 0x04 != 0,
+    })
+}
+
+pub fn decode_current_time(data: &[u8]) -> Option<CurrentTime> {
+    let mut reader = Reader::new(data);
+    let date_time = reader.date_time()?;
+    let day_of_week = reader.u8()?;
+    let fractions256 = reader.u8()?;
+    let adjust_reason = reader.u8()?;
+    Some(CurrentTime {
+        date_time,
+        day_of_week: (1..=7).contains(&day_of_week).then_some(day_of_week),
+        fraction: fractions256 as f64 / 256.0,
+        manual_update: adjust_reason & 0x01 != 0,
+        external_reference_update: adjust_reason & 0x02 != 0,
+        time_zone_change: adjust_reason & 0x04 != 0,
+        dst_change: adjust_reason & 0x08 != 0,
+    })
+}
+
+/// A UTF-8 string characteristic, devices often pad them with NULs.
+pub fn decode_string(data: &[u8]) -> Option<String> {
+    let value = String::from_utf8_lossy(data);
+    let value = value.trim_end_matches('\0').trim();
+    (!value.is_empty()).then(|| value.to_string())
+}
+
+pub fn decode_pnp_id(data: &[u8]) -> Option<PnpId> {
+    let mut reader = Reader::new(data);
+    Some(PnpId {
+        vendor_id_source: match reader.u8()? {
+            1 => VendorIdSource::Bluetooth,
+            2 => VendorIdSource::Usb,
+            other => VendorIdSource::Other(other),
+        },
+        vendor_id: reader.u16()?,
+        product_id: reader.u16()?,
+        product_version: reader.u16()?,
+    })
+}
+
+/// Both parts are little endian, they are hex encoded most significant byte first.
+pub fn decode_system_id(data: &[u8]) -> Option<SystemId> {
+    let mut reader = Reader::new(data);
+    let mut manufacturer_identifier = reader.take::<5>()?;
+    let mut organizationally_unique_identifier = reader.take::<3>()?;
+    manufacturer_identifier.reverse();
+    organizationally_unique_identifier.reverse();
+    Some(SystemId {
+        manufacturer_identifier: hex::encode(&manufacturer_identifier),
+        organizationally_unique_identifier: hex::encode(&organizationally_unique_identifier),
+    })
+}
+
+/// The 32-bit FLOAT of IEEE 11073-20601: a 24-bit signed mantissa and an 8-bit signed base 10
+/// exponent. NaN, NRes, the infinities and the reserved value give `None`.
+pub fn ieee11073_float(bits: u32) -> Option<f64> {
+    let mantissa = bits & 0x00FF_FFFF;
+    if (0x007F_FFFE..=0x0080_0002).contains(&mantissa) {
+        return None;
+    }
+    // sign extend the 24-bit mantissa.
+    let mantissa = ((mantissa << 8) as i32) >> 8;
+    let exponent = (bits >> 24) as i8 as i32;
+    // dividing keeps 3698e-2 at 36.98 where multiplying by 1e-2 would not.
+    if exponent < 0 {
+        Some(mantissa as f64 / 10f64.powi(-exponent))
+    } else {
+        Some(mantissa as f64 * 10f64.powi(exponent))
+    }
+}
+
+/// Reads the fields of a value in order, `None` once it runs out of bytes.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Reader { data }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    fn take<const N: usize>(&mut self) -> Option<[u8; N]> {
+        let (bytes, rest) = self.data.split_first_chunk::<N>()?;
+        self.data = rest;
+        Some(*bytes)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take::<1>().map(|[byte]| byte)
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take().map(u16::from_le_bytes)
+    }
+
+    fn i16(&mut self) -> Option<i16> {
+        self.take().map(i16::from_le_bytes)
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        self.take().map(u32::from_le_bytes)
+    }
+
+    fn date_time(&mut self) -> Option<DateTime> {
+        Some(DateTime {
+            year: self.u16()?,
+            month: self.u8()?,
+            day: self.u8()?,
+            hours: self.u8()?,
+            minutes: self.u8()?,
+            seconds: self.u8()?,
+        })
+    }
+
+    /// Read a field present when `present` is set, `Some(None)` when absent and `None` when
+    /// the value is truncated.
+    fn optional<T>(
+        &mut self,
+        present: bool,
+        read: impl FnOnce(&mut Self) -> Option<T>,
+    ) -> Option<Option<T>> {
+        if present {
+            read(self).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(actual: Option<f64>, expected: f64) -> bool {
+        actual.is_some_and(|actual| (actual - expected).abs() < 1e-9)
+    }
+
+    const DATE_TIME: [u8; 7] = [0xE8, 0x07, 0x05, 0x06, 0x07, 0x08, 0x09];
+
+    fn date_time() -> DateTime {
+        DateTime {
+            year: 2024,
+            month: 5,
+            day: 6,
+            hours: 7,
+            minutes: 8,
+            seconds: 9,
+        }
+    }
+
+    #[test]
+    fn heart_rate_with_an_8_bit_value() {
+        let measurement = decode_heart_rate(&[0x00, 72]).unwrap();
+        assert_eq!(
+            measurement,
+            HeartRateMeasurement {
+                bpm: 72,
+                sensor_contact: None,
+                energy_expended: None,
+                rr_intervals: vec![],
+            }
+        );
+        let measurement = decode_heart_rate(&[0x04, 60]).unwrap();
+        assert_eq!(measurement.sensor_contact, Some(false));
+    }
+
+    #[test]
+    fn heart_rate_with_every_field() {
+        // 16-bit value, contact detected, energy and two RR intervals in 1/1024 s.
+        let data = [0x1F, 0x2C, 0x01, 0x10, 0x00, 0x00, 0x04, 0x00, 0x02];
+        let measurement = decode_heart_rate(&data).unwrap();
+        assert_eq!(
+            measurement,
+            HeartRateMeasurement {
+                bpm: 300,
+                sensor_contact: Some(true),
+                energy_expended: Some(16),
+                rr_intervals: vec![1000.0, 500.0],
+            }
+        );
+    }
+
+    #[test]
+    fn truncated_heart_rates_are_malformed() {
+        assert_eq!(decode_heart_rate(&[]), None);
+        assert_eq!(decode_heart_rate(&[0x01, 72]), None);
+        assert_eq!(decode_heart_rate(&[0x08, 72, 0x10]), None);
+        assert_eq!(decode_heart_rate(&[0x10, 72, 0x00, 0x04, 0x00]), None);
+        let error = decode("2a37", &[0x01]).unwrap_err();
+        assert_eq!(error.code(), "invalidValue");
+    }
+
+    #[test]
+    fn ieee11073_floats() {
+        assert!(close(ieee11073_float(0xFE00_0E72), 36.98));
+        assert!(close(ieee11073_float(0x0200_000C), 1200.0));
+        assert!(close(ieee11073_float(0x00FF_FFFB), -5.0));
+        // NaN, NRes, +INF, -INF and the reserved value.
+        for special in [
+            0x007F_FFFF,
+            0x0080_0000,
+            0x007F_FFFE,
+            0x0080_0002,
+            0x0080_0001,
+        ] {
+            assert_eq!(ieee11073_float(special), None, "{special:08X}");
+            assert_eq!(
+                ieee11073_float(0xFE00_0000 | special),
+                None,
+                "{special:08X}"
+            );
+        }
+    }
+
+    #[test]
+    fn temperature_in_celsius_with_a_timestamp_and_a_type() {
+        let mut data = vec![0x06, 0x72, 0x0E, 0x00, 0xFE];
+        data.extend(DATE_TIME);
+        data.push(0x02);
+        let measurement = decode_temperature(&data).unwrap();
+        assert!(close(measurement.temperature, 36.98));
+        assert_eq!(measurement.unit, TemperatureUnit::Celsius);
+        assert_eq!(measurement.timestamp, Some(date_time()));
+        assert_eq!(measurement.temperature_type, Some(TemperatureType::Body));
+    }
+
+    #[test]
+    fn temperature_in_fahrenheit_and_invalid_readings() {
+        let measurement = decode_temperature(&[0x01, 0xDA, 0x03, 0x00, 0xFF]).unwrap();
+        assert!(close(measurement.temperature, 98.6));
+        assert_eq!(measurement.unit, TemperatureUnit::Fahrenheit);
+        assert_eq!(measurement.timestamp, None);
+        assert_eq!(measurement.temperature_type, None);
+
+        let nan = decode_temperature(&[0x00, 0xFF, 0xFF, 0x7F, 0x00]).unwrap();
+        assert_eq!(nan.temperature, None);
+        let minus_infinity = decode_temperature(&[0x00, 0x02, 0x00, 0x80, 0x00]).unwrap();
+        assert_eq!(minus_infinity.temperature, None);
+        assert_eq!(decode_temperature(&[0x04, 0x72, 0x0E, 0x00, 0xFE]), None);
+    }
+
+    #[test]
+    fn weight_in_si_units() {
+        let mut data = vec![0x0E, 0xB0, 0x36];
+        data.extend(DATE_TIME);
+        data.extend([0x03, 0xE5, 0x00, 0xD6, 0x06]);
+        let measurement = decode_weight(&data).unwrap();
+        assert!(close(measurement.weight, 70.0));
+        assert_eq!(measurement.unit, WeightUnit::Kilogram);
+        assert_eq!(measurement.timestamp, Some(date_time()));
+        assert_eq!(measurement.user_id, Some(3));
+        assert!(close(measurement.bmi, 22.9));
+        assert!(close(measurement.height, 1.75));
+    }
+
+    #[test]
+    fn weight_in_imperial_units() {
+        let measurement = decode_weight(&[0x0D, 0x48, 0x3C, 0xFF, 0xE5, 0x00, 0xB2, 0x02]).unwrap();
+        assert!(close(measurement.weight, 154.32));
+        assert_eq!(measurement.unit, WeightUnit::Pound);
+        assert_eq!(measurement.timestamp, None);
+        // 255 is the unknown user.
+        assert_eq!(measurement.user_id, None);
+        assert!(close(measurement.bmi, 22.9));
+        assert!(close(measurement.height, 69.0));
+
+        let unsuccessful = decode_weight(&[0x01, 0xFF, 0xFF]).unwrap();
+        assert_eq!(unsuccessful.weight, None);
+        assert_eq!(decode_weight(&[0x08, 0xB0, 0x36, 0xE5, 0x00]), None);
+    }
+
+    #[test]
+    fn cycling_power_with_every_field() {
+        let data = [
+            0xFF, 0x1F, // flags
+            0xFA, 0x00, // power
+            100,  // balance
+            0x80, 0x0C, // accumulated torque
+            0x39, 0x30, 0x00, 0x00, 0x00, 0x10, // wheel revolutions
+            0xA6, 0x02, 0x00, 0x08, // crank revolutions
+            0xF4, 0x01, 0x9C, 0xFF, // extreme forces
+            0x80, 0x02, 0xC0, 0xFE, // extreme torques
+            0x23, 0x61, 0x45, // extreme angles
+            0x0A, 0x00, // top dead spot
+            0xBE, 0x00, // bottom dead spot
+            0x2A, 0x00, // accumulated energy
+        ];
+        let measurement = decode_cycling_power(&data).unwrap();
+        assert_eq!(
+            measurement,
+            CyclingPowerMeasurement {
+                instantaneous_power: 250,
+                pedal_power_balance: Some(50.0),
+                pedal_power_balance_left: true,
+                accumulated_torque: Some(100.0),
+                torque_from_crank: true,
+                wheel_revolutions: Some(RevolutionData {
+                    revolutions: 12345,
+                    last_event_time: 2.0,
+                }),
+                crank_revolutions: Some(RevolutionData {
+                    revolutions: 678,
+                    last_event_time: 2.0,
+                }),
+                extreme_force: Some(Extremes {
+                    max: 500.0,
+                    min: -100.0,
+                }),
+                extreme_torque: Some(Extremes {
+                    max: 20.0,
+                    min: -10.0,
+                }),
+                extreme_angles: Some(Extremes {
+                    max: 291.0,
+                    min: 1110.0,
+                }),
+                top_dead_spot_angle: Some(10),
+                bottom_dead_spot_angle: Some(190),
+                accumulated_energy: Some(42),
+                offset_compensation: true,
+            }
+        );
+        assert_eq!(decode_cycling_power(&data[..data.len() - 1]), None);
+    }
+
+    #[test]
+    fn cycling_power_with_the_power_only() {
+        let measurement = decode_cycling_power(&[0x00, 0x00, 0x38, 0xFF]).unwrap();
+        assert_eq!(measurement.instantaneous_power, -200);
+        assert_eq!(measurement.pedal_power_balance, None);
+        assert!(!measurement.pedal_power_balance_left);
+        assert_eq!(measurement.wheel_revolutions, None);
+        assert_eq!(measurement.extreme_angles, None);
+        assert!(!measurement.offset_compensation);
+    }
+
+    #[test]
+    fn running_speed_and_cadence() {
+        let data = [0x07, 0x80, 0x03, 170, 0x78, 0x00, 0x39, 0x30, 0x00, 0x00];
+        assert_eq!(
+            decode_rsc(&data).unwrap(),
+            RscMeasurement {
+                speed: 3.5,
+                cadence: 170,
+                stride_length: Some(1.2),
+                total_distance: Some(1234.5),
+                running: true,
+            }
+        );
+        assert_eq!(
+            decode_rsc(&[0x00, 0x00, 0x01, 90]).unwrap(),
+            RscMeasurement {
+                speed: 1.0,
+                cadence: 90,
+                stride_length: None,
+                total_distance: None,
+                running: false,
+            }
+        );
+        assert_eq!(decode_rsc(&[0x02, 0x00, 0x01, 90, 0x00]), None);
+    }
+
+    #[test]
+    fn current_time() {
+        let data = [0xE8, 0x07, 0x0C, 0x1F, 0x17, 0x3B, 0x3B, 0x02, 0x80, 0x05];
+        assert_eq!(
+            decode_current_time(&data).unwrap(),
+            CurrentTime {
+                date_time: DateTime {
+                    year: 2024,
+                    month: 12,
+                    day: 31,
+                    hours: 23,
+                    minutes: 59,
+                    seconds: 59,
+                },
+                day_of_week: Some(2),
+                fraction: 0.5,
+                manual_update: true,
+                external_reference_update: false,
+                time_zone_change: true,
+                dst_change: false,
+            }
+        );
+        let mut unknown_day = data;
+        unknown_day[7] = 0;
+        assert_eq!(decode_current_time(&unknown_day).unwrap().day_of_week, None);
+        assert_eq!(decode_current_time(&data[..9]), None);
+    }
+
+    #[test]
+    fn battery_level_and_dispatch() {
+        assert_eq!(
+            decode("00002a19-0000-1000-8000-00805f9b34fb", &[87]).unwrap(),
+            ProfileValue::BatteryLevel(87)
+        );
+        assert!(decode("2A19", &[101]).is_err());
+        assert_eq!(decode("2A00", &[0]).unwrap_err().code(), "invalidValue");
+        assert_eq!(service_of("2a53"), Some("1814"));
+    }
+
+    #[test]
+    fn pnp_id() {
+        let data = [0x02, 0x6D, 0x04, 0x2B, 0xC5, 0x10, 0x01];
+        assert_eq!(
+            decode_pnp_id(&data).unwrap(),
+            PnpId {
+                vendor_id_source: VendorIdSource::Usb,
+                vendor_id: 0x046D,
+                product_id: 0xC52B,
+                product_version: 0x0110,
+            }
+        );
+        let bluetooth = decode_pnp_id(&[0x01, 0x0D, 0x00, 0x01, 0x00, 0x00, 0x01]).unwrap();
+        assert_eq!(bluetooth.vendor_id_source, VendorIdSource::Bluetooth);
+        assert_eq!(bluetooth.vendor_id, 0x000D);
+        let other = decode_pnp_id(&[0x07, 0, 0, 0, 0, 0, 0]).unwrap();
+        assert_eq!(other.vendor_id_source, VendorIdSource::Other(7));
+        assert_eq!(decode_pnp_id(&data[..6]), None);
+    }
+
+    #[test]
+    fn system_id() {
+        let data = [0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+        assert_eq!(
+            decode_system_id(&data).unwrap(),
+            SystemId {
+                manufacturer_identifier: "0504030201".to_string(),
+                organizationally_unique_identifier: "080706".to_string(),
+            }
+        );
+        assert_eq!(decode_system_id(&data[..7]), None);
+    }
+
+    #[test]
+    fn device_information_fields() {
+        let mut info = DeviceInformation::default();
+        info.set("2a29", b"Acme\0\0");
+        info.set("2A24", b"  ");
+        info.set("2A50", &[0x01, 0x0D, 0x00]);
+        info.set("2A23", &[0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08]);
+        assert_eq!(info.manufacturer_name.as_deref(), Some("Acme"));
+        assert_eq!(info.model_number, None);
+        assert_eq!(info.pnp_id, None);
+        assert_eq!(
+            info.system_id
+                .map(|id| id.organizationally_unique_identifier),
+            Some("080706".to_string())
+        );
+    }
+}
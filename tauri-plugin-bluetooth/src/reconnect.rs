@@ -0,0 +1,511 @@
+use crate::clock::{check_seconds, seconds, Clock, SystemClock};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How a lost connection is retried, delays grow from `initial_delay` by `multiplier` up to
+/// `max_delay`.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ReconnectPolicy {
+    /// Seconds between the loss of the connection and the first attempt.
+    pub initial_delay: f64,
+    pub multiplier: f64,
+    /// Seconds, also how long an attempt may take before it counts as failed.
+    pub max_delay: f64,
+    /// Attempts before giving up, unlimited if omitted.
+    pub max_attempts: Option<u32>,
+    /// Fraction of each delay added or removed at random, between 0 and 1.
+    pub jitter: f64,
+}
+
+impl Default for ReconnectPolicy {
+    fn default() -> Self {
+        ReconnectPolicy {
+            initial_delay: 1.0,
+            multiplier: 2.0,
+            max_delay: 60.0,
+            max_attempts: None,
+            jitter: 0.1,
+        }
+    }
+}
+
+impl ReconnectPolicy {
+    /// Check that the delays are positive durations, the multiplier at least 1 and the jitter
+    /// between 0 and 1.
+    pub fn validate(&self) -> crate::Result<()> {
+        for (name, delay) in [
+            ("initialDelay", self.initial_delay),
+            ("maxDelay", self.max_delay),
+        ] {
+            check_seconds(name, delay)?;
+            if delay == 0.0 {
+                return Err(crate::Error::InvalidValue(format!(
+                    "{name} must be positive"
+                )));
+            }
+        }
+        if !(1.0..=f64::MAX).contains(&self.multiplier) {
+            return Err(crate::Error::InvalidValue(format!(
+                "multiplier must be at least 1, got {}",
+                self.multiplier
+            )));
+        }
+        if !(0.0..=1.0).contains(&self.jitter) {
+            return Err(crate::Error::InvalidValue(format!(
+                "jitter must be between 0 and 1, got {}",
+                self.jitter
+            )));
+        }
+        Ok(())
+    }
+
+    /// Delay before the attempt following `attempt`, 0 being the loss of the connection.
+    ///
+    /// The jitter is derived from the identifier and the attempt, so a device always gets the
+    /// same delays while devices lost together are not retried in lockstep.
+    pub fn delay(&self, identifier: &str, attempt: u32) -> Duration {
+        let base = self.initial_delay * self.multiplier.max(1.0).powi(attempt as i32);
+        let mut hasher = DefaultHasher::new();
+        (identifier, attempt).hash(&mut hasher);
+        let unit = (hasher.finish() >> 11) as f64 / (1u64 << 53) as f64;
+        let jitter = self.jitter.clamp(0.0, 1.0) * (2.0 * unit - 1.0);
+        // `min` also caps a multiplier that overflowed to infinity.
+        seconds((base * (1.0 + jitter)).min(self.max_delay))
+    }
+}
+
+/// Where a reconnection stands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "status", rename_all = "camelCase")]
+pub enum ReconnectStatus {
+    /// The connection was lost, the next attempt comes in `delay` milliseconds.
+    Scheduled {
+        delay: u64,
+    },
+    Attempting,
+    /// The attempt did not connect in time.
+    Failed,
+    Connected,
+    /// `max_attempts` failed, nothing is tried until the device connects again.
+    GaveUp,
+    /// The adapter is powered off, attempts resume once it is back on.
+    Paused,
+}
+
+/// Payload of the reconnect event.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReconnectUpdate {
+    pub identifier: String,
+    /// The attempt the update is about, 0 before the first one.
+    pub attempt: u32,
+    #[serde(flatten)]
+    pub status: ReconnectStatus,
+}
+
+struct Entry {
+    policy: ReconnectPolicy,
+    connected: bool,
+    reconnecting: bool,
+    attempt: u32,
+    next_at: Option<Instant>,
+}
+
+struct State {
+    powered: bool,
+    devices: HashMap<String, Entry>,
+}
+
+/// Reconnects the devices with a [`ReconnectPolicy`] when their connection drops.
+///
+/// Feed it the connection states and the adapter power, and call [`poll`](Self::poll) regularly:
+/// it returns the devices to connect to. A device disconnected on purpose is not reconnected.
+pub struct Reconnects<C: Clock = SystemClock> {
+    clock: C,
+    state: Mutex<State>,
+}
+
+impl Default for Reconnects<SystemClock> {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> Reconnects<C> {
+    pub fn with_clock(clock: C) -> Self {
+        Reconnects {
+            clock,
+            state: Mutex::new(State {
+                powered: true,
+                devices: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Set the policy of a device, or stop reconnecting it when `None`. `connected` is its
+    /// current state, a device already disconnected is not reconnected until it drops again.
+    pub fn set_policy(&self, identifier: &str, policy: Option<ReconnectPolicy>, connected: bool) {
+        let mut state = self.lock();
+        match policy {
+            Some(policy) => match state.devices.get_mut(identifier) {
+                Some(entry) => entry.policy = policy,
+                None => {
+                    state.devices.insert(
+                        identifier.to_string(),
+                        Entry {
+                            policy,
+                            connected,
+                            reconnecting: false,
+                            attempt: 0,
+                            next_at: None,
+                        },
+                    );
+                }
+            },
+            None => {
+                state.devices.remove(identifier);
+            }
+        }
+    }
+
+    pub fn policy(&self, identifier: &str) -> Option<ReconnectPolicy> {
+        self.lock()
+            .devices
+            .get(identifier)
+            .map(|entry| entry.policy.clone())
+    }
+
+    /// Record the connection state of a device, a drop schedules the first attempt.
+    pub fn observe(&self, identifier: &str, connected: bool) -> Vec<ReconnectUpdate> {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        let powered = state.powered;
+        let Some(entry) = state.devices.get_mut(identifier) else {
+            return vec![];
+        };
+        let was_connected = std::mem::replace(&mut entry.connected, connected);
+        if connected {
+            if !entry.reconnecting {
+                return vec![];
+            }
+            let attempt = entry.attempt;
+            entry.reconnecting = false;
+            entry.attempt = 0;
+            entry.next_at = None;
+            return vec![update(identifier, attempt, ReconnectStatus::Connected)];
+        }
+        if !was_connected || entry.reconnecting {
+            return vec![];
+        }
+        entry.reconnecting = true;
+        entry.attempt = 0;
+        if !powered {
+            return vec![update(identifier, 0, ReconnectStatus::Paused)];
+        }
+        vec![schedule(identifier, entry, now)]
+    }
+
+    /// A device is about to be disconnected on purpose, cancel its reconnection.
+    pub fn disconnecting(&self, identifier: &str) {
+        if let Some(entry) = self.lock().devices.get_mut(identifier) {
+            entry.connected = false;
+            entry.reconnecting = false;
+            entry.attempt = 0;
+            entry.next_at = None;
+        }
+    }
+
+    /// Pause the reconnections while the adapter is off, start them over once it is back on.
+    pub fn set_powered(&self, powered: bool) -> Vec<ReconnectUpdate> {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        if state.powered == powered {
+            return vec![];
+        }
+        state.powered = powered;
+        let mut updates = vec![];
+        for (identifier, entry) in state.devices.iter_mut() {
+            if !entry.reconnecting {
+                continue;
+            }
+            entry.attempt = 0;
+            if powered {
+                updates.push(schedule(identifier, entry, now));
+            } else {
+                entry.next_at = None;
+                updates.push(update(identifier, 0, ReconnectStatus::Paused));
+            }
+        }
+        updates
+    }
+
+    /// Settle the attempts that timed out and start the ones due, returns the updates and the
+    /// devices to connect to.
+    pub fn poll(&self) -> (Vec<ReconnectUpdate>, Vec<String>) {
+        let now = self.clock.now();
+        let mut state = self.lock();
+        let mut updates = vec![];
+        let mut due = vec![];
+        if !state.powered {
+            return (updates, due);
+        }
+        for (identifier, entry) in state.devices.iter_mut() {
+            if !entry.reconnecting || entry.next_at.map_or(true, |next_at| next_at > now) {
+                continue;
+            }
+            if entry.attempt > 0 {
+                updates.push(update(identifier, entry.attempt, ReconnectStatus::Failed));
+            }
+            if entry
+                .policy
+                .max_attempts
+                .is_some_and(|max_attempts| entry.attempt >= max_attempts)
+            {
+                entry.reconnecting = false;
+                entry.next_at = None;
+                updates.push(update(identifier, entry.attempt, ReconnectStatus::GaveUp));
+                continue;
+            }
+            entry.attempt += 1;
+            entry.next_at = Some(now + entry.policy.delay(identifier, entry.attempt));
+            updates.push(update(
+                identifier,
+                entry.attempt,
+                ReconnectStatus::Attempting,
+            ));
+            due.push(identifier.clone());
+        }
+        (updates, due)
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn update(identifier: &str, attempt: u32, status: ReconnectStatus) -> ReconnectUpdate {
+    ReconnectUpdate {
+        identifier: identifier.to_string(),
+        attempt,
+        status,
+    }
+}
+
+fn schedule(identifier: &str, entry: &mut Entry, now: Instant) -> ReconnectUpdate {
+    let delay = entry.policy.delay(identifier, 0);
+    entry.next_at = Some(now + delay);
+    update(
+        identifier,
+        0,
+        ReconnectStatus::Scheduled {
+            delay: delay.as_millis() as u64,
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::clock::ManualClock;
+
+    fn policy(max_attempts: Option<u32>) -> ReconnectPolicy {
+        ReconnectPolicy {
+            initial_delay: 1.0,
+            multiplier: 2.0,
+            max_delay: 10.0,
+            max_attempts,
+            jitter: 0.0,
+        }
+    }
+
+    fn statuses(updates: &[ReconnectUpdate]) -> Vec<(u32, ReconnectStatus)> {
+        updates
+            .iter()
+            .map(|update| (update.attempt, update.status))
+            .collect()
+    }
+
+    fn dropped(max_attempts: Option<u32>) -> (ManualClock, Reconnects<ManualClock>) {
+        let clock = ManualClock::new();
+        let reconnects = Reconnects::with_clock(clock.clone());
+        reconnects.set_policy("AA", Some(policy(max_attempts)), true);
+        let updates = reconnects.observe("AA", false);
+        assert_eq!(
+            statuses(&updates),
+            [(0, ReconnectStatus::Scheduled { delay: 1000 })]
+        );
+        (clock, reconnects)
+    }
+
+    #[test]
+    fn delays_grow_up_to_the_maximum() {
+        let (clock, reconnects) = dropped(None);
+        clock.advance(Duration::from_millis(999));
+        assert_eq!(reconnects.poll(), (vec![], vec![]));
+        clock.advance(Duration::from_millis(1));
+        let (updates, due) = reconnects.poll();
+        assert_eq!(statuses(&updates), [(1, ReconnectStatus::Attempting)]);
+        assert_eq!(due, ["AA"]);
+
+        // each attempt times out after the next delay: 2, 4, 8 then 10 seconds.
+        for (attempt, delay) in [(1, 2), (2, 4), (3, 8), (4, 10), (5, 10)] {
+            clock.advance(Duration::from_secs(delay - 1));
+            assert_eq!(reconnects.poll(), (vec![], vec![]));
+            clock.advance(Duration::from_secs(1));
+            let (updates, due) = reconnects.poll();
+            assert_eq!(
+                statuses(&updates),
+                [
+                    (attempt, ReconnectStatus::Failed),
+                    (attempt + 1, ReconnectStatus::Attempting)
+                ]
+            );
+            assert_eq!(due, ["AA"]);
+        }
+
+        let updates = reconnects.observe("AA", true);
+        assert_eq!(statuses(&updates), [(6, ReconnectStatus::Connected)]);
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(reconnects.poll(), (vec![], vec![]));
+    }
+
+    #[test]
+    fn reconnection_gives_up_after_the_maximum_attempts() {
+        let (clock, reconnects) = dropped(Some(2));
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(reconnects.poll().1, ["AA"]);
+        clock.advance(Duration::from_secs(2));
+        assert_eq!(reconnects.poll().1, ["AA"]);
+        clock.advance(Duration::from_secs(4));
+        let (updates, due) = reconnects.poll();
+        assert_eq!(
+            statuses(&updates),
+            [(2, ReconnectStatus::Failed), (2, ReconnectStatus::GaveUp)]
+        );
+        assert!(due.is_empty());
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(reconnects.poll(), (vec![], vec![]));
+
+        // a new drop starts over.
+        assert!(reconnects.observe("AA", true).is_empty());
+        let updates = reconnects.observe("AA", false);
+        assert_eq!(
+            statuses(&updates),
+            [(0, ReconnectStatus::Scheduled { delay: 1000 })]
+        );
+    }
+
+    #[test]
+    fn reconnection_pauses_while_the_adapter_is_off() {
+        let (clock, reconnects) = dropped(None);
+        clock.advance(Duration::from_secs(1));
+        assert_eq!(reconnects.poll().1, ["AA"]);
+
+        let updates = reconnects.set_powered(false);
+        assert_eq!(statuses(&updates), [(0, ReconnectStatus::Paused)]);
+        assert!(reconnects.set_powered(false).is_empty());
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(reconnects.poll(), (vec![], vec![]));
+
+        let updates = reconnects.set_powered(true);
+        assert_eq!(
+            statuses(&updates),
+            [(0, ReconnectStatus::Scheduled { delay: 1000 })]
+        );
+        clock.advance(Duration::from_secs(1));
+        let (updates, due) = reconnects.poll();
+        assert_eq!(statuses(&updates), [(1, ReconnectStatus::Attempting)]);
+        assert_eq!(due, ["AA"]);
+    }
+
+    #[test]
+    fn a_drop_while_the_adapter_is_off_waits_for_it() {
+        let clock = ManualClock::new();
+        let reconnects = Reconnects::with_clock(clock.clone());
+        reconnects.set_policy("AA", Some(policy(None)), true);
+        assert!(reconnects.set_powered(false).is_empty());
+        let updates = reconnects.observe("AA", false);
+        assert_eq!(statuses(&updates), [(0, ReconnectStatus::Paused)]);
+        let updates = reconnects.set_powered(true);
+        assert_eq!(
+            statuses(&updates),
+            [(0, ReconnectStatus::Scheduled { delay: 1000 })]
+        );
+    }
+
+    #[test]
+    fn devices_disconnected_on_purpose_are_not_reconnected() {
+        let (clock, reconnects) = dropped(None);
+        reconnects.disconnecting("AA");
+        clock.advance(Duration::from_secs(60));
+        assert_eq!(reconnects.poll(), (vec![], vec![]));
+        assert!(reconnects.observe("AA", false).is_empty());
+
+        reconnects.set_policy("BB", Some(policy(None)), false);
+        assert!(reconnects.observe("BB", false).is_empty());
+        reconnects.set_policy("BB", None, false);
+        assert_eq!(reconnects.policy("BB"), None);
+    }
+
+    #[test]
+    fn the_jitter_stays_within_bounds() {
+        let policy = ReconnectPolicy {
+            jitter: 0.5,
+            ..policy(None)
+        };
+        for attempt in 0..4 {
+            let base = 2f64.powi(attempt as i32);
+            let delay = policy.delay("AA", attempt).as_secs_f64();
+            assert!(delay >= base * 0.5 && delay <= (base * 1.5).min(10.0));
+            assert_eq!(policy.delay("AA", attempt), policy.delay("AA", attempt));
+        }
+    }
+
+    #[test]
+    fn invalid_policies_are_rejected() {
+        assert!(ReconnectPolicy::default().validate().is_ok());
+        let invalid = [
+            ReconnectPolicy {
+                initial_delay: 0.0,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                initial_delay: f64::NAN,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                max_delay: -1.0,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                max_delay: f64::INFINITY,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                multiplier: 0.5,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                multiplier: f64::INFINITY,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                jitter: 1.5,
+                ..Default::default()
+            },
+            ReconnectPolicy {
+                jitter: f64::NAN,
+                ..Default::default()
+            },
+        ];
+        for policy in invalid {
+            let error = policy.validate().unwrap_err();
+            assert_eq!(error.code(), "invalidValue", "{policy:?}");
+        }
+    }
+}
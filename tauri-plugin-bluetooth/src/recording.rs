@@ -0,0 +1,319 @@
+use crate::adapter::AdapterState;
+use crate::bridge::Device;
+use crate::desktop::State;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, BufWriter, ErrorKind, Write};
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// What a backend hands to the plugin, one variant per `dispatch_*` function of the desktop
+/// module.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "event", content = "data", rename_all = "camelCase")]
+pub enum RecordedEvent {
+    NewDevice(Device),
+    UpdateDevice(Device),
+    RemoveDevice(Device),
+    #[serde(rename_all = "camelCase")]
+    Rssi {
+        identifier: String,
+        rssi: i32,
+        estimated_rssi: i32,
+        active: bool,
+    },
+    Presence {
+        presence: bool,
+        reason: String,
+    },
+    PowerWarning,
+    AdapterState(AdapterState),
+    ConnectionState {
+        identifier: String,
+        connected: bool,
+        error: Option<String>,
+    },
+    Notification {
+        identifier: String,
+        service: String,
+        characteristic: String,
+        value: Vec<u8>,
+    },
+}
+
+impl RecordedEvent {
+    /// Hand the event to the plugin as if the backend reported it.
+    pub(crate) fn dispatch(self, state: &State) {
+        match self {
+            RecordedEvent::NewDevice(device) => state.dispatch_new_device(device),
+            RecordedEvent::UpdateDevice(device) => state.dispatch_update_device(device),
+            RecordedEvent::RemoveDevice(device) => state.dispatch_remove_device(device),
+            RecordedEvent::Rssi {
+                identifier,
+                rssi,
+                estimated_rssi,
+                active,
+            } => state.dispatch_rssi(identifier, rssi, estimated_rssi, active),
+            RecordedEvent::Presence { presence, reason } => {
+                state.dispatch_presence(presence, reason)
+            }
+            RecordedEvent::PowerWarning => state.dispatch_power_warn(),
+            RecordedEvent::AdapterState(adapter) => state.dispatch_adapter_state(adapter),
+            RecordedEvent::ConnectionState {
+                identifier,
+                connected,
+                error,
+            } => state.dispatch_connection_state(&identifier, connected, error),
+            RecordedEvent::Notification {
+                identifier,
+                service,
+                characteristic,
+                value,
+            } => state.dispatch_notification(identifier, service, characteristic, value),
+        }
+    }
+}
+
+/// A line of a recording.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct RecordedLine {
+    /// Seconds since the recording started, on the monotonic clock.
+    pub at: f64,
+    #[serde(flatten)]
+    pub event: RecordedEvent,
+}
+
+/// What a finished recording holds.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecordingSummary {
+    pub path: PathBuf,
+    pub events: usize,
+    /// Seconds between the start and the end of the recording.
+    pub duration: f64,
+}
+
+struct Recording {
+    path: PathBuf,
+    file: BufWriter<File>,
+    started: Instant,
+    events: usize,
+}
+
+/// Writes the events reaching the plugin to a JSONL file, one [`RecordedLine`] each.
+#[derive(Default)]
+pub struct Recorder {
+    recording: Mutex<Option<Recording>>,
+}
+
+impl Recorder {
+    /// Record to `path` from now on, replacing the recording already running. That one ends
+    /// before `path` is created, even if the new one fails to start.
+    pub fn start(&self, path: PathBuf) -> Result<()> {
+        let mut recording = self.lock();
+        // closed first, `path` may be the file it writes to.
+        if let Some(mut current) = recording.take() {
+            let _ = current.file.flush();
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = BufWriter::new(File::create(&path)?);
+        *recording = Some(Recording {
+            path,
+            file,
+            started: Instant::now(),
+            events: 0,
+        });
+        Ok(())
+    }
+
+    pub fn stop(&self) -> Option<RecordingSummary> {
+        let mut recording = self.lock().take()?;
+        let _ = recording.file.flush();
+        Some(RecordingSummary {
+            path: recording.path,
+            events: recording.events,
+            duration: recording.started.elapsed().as_secs_f64(),
+        })
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.lock().is_some()
+    }
+
+    /// Write an event if recording, `event` is only built then.
+    pub fn record(&self, event: impl FnOnce() -> RecordedEvent) {
+        let mut guard = self.lock();
+        let Some(recording) = guard.as_mut() else {
+            return;
+        };
+        let line = RecordedLine {
+            at: recording.started.elapsed().as_secs_f64(),
+            event: event(),
+        };
+        match write_line(&mut recording.file, &line) {
+            Ok(()) => recording.events += 1,
+            // a full disk or a removed file ends the recording.
+            Err(_) => *guard = None,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Option<Recording>> {
+        self.recording.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Flushed line by line, so a crash keeps what led to it.
+fn write_line(file: &mut BufWriter<File>, line: &RecordedLine) -> Result<()> {
+    serde_json::to_writer(&mut *file, line)?;
+    file.write_all(b"\n")?;
+    file.flush()?;
+    Ok(())
+}
+
+/// Read a recording, blank lines are skipped.
+pub fn read_recording(path: &Path) -> Result<Vec<RecordedLine>> {
+    let mut lines = vec![];
+    for (index, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let line = serde_json::from_str(&line).map_err(|e| {
+            Error::InvalidValue(format!("{} line {}: {e}", path.display(), index + 1))
+        })?;
+        lines.push(line);
+    }
+    Ok(lines)
+}
+
+/// The recording `name` of `dir`, which must be a plain file name: no separator, no `..`, and
+/// no link leading out of `dir`.
+pub(crate) fn recording_path(dir: &Path, name: &str) -> Result<PathBuf> {
+    let mut components = Path::new(name).components();
+    let plain = matches!(
+        (components.next(), components.next()),
+        (Some(Component::Normal(_)), None)
+    );
+    if !plain || name.contains(['/', '\\']) {
+        return Err(Error::InvalidValue(format!(
+            "{name:?} is not the file name of a recording"
+        )));
+    }
+    fs::create_dir_all(dir)?;
+    let dir = dir.canonicalize()?;
+    let path = dir.join(name);
+    let resolved = match fs::symlink_metadata(&path) {
+        // a dangling link fails here rather than being created through.
+        Ok(_) => path.canonicalize()?,
+        Err(e) if e.kind() == ErrorKind::NotFound => path,
+        Err(e) => return Err(e.into()),
+    };
+    if resolved.parent() != Some(dir.as_path()) {
+        return Err(Error::InvalidValue(format!(
+            "{name:?} leads out of the recordings directory"
+        )));
+    }
+    Ok(resolved)
+}
+
+/// Dispatch the lines of a recording with their original spacing divided by `speed`, all at once
+/// when `speed` is 0. Blocks until the last one is dispatched.
+///
+/// The timeouts of the plugin run on the system clock: only a replay at real speed reproduces
+/// them, an accelerated one suits what follows from the events alone, like the RSSI filters.
+pub(crate) fn replay(lines: Vec<RecordedLine>, speed: f64, state: &State) -> usize {
+    let started = Instant::now();
+    let count = lines.len();
+    for line in lines {
+        if speed > 0.0 {
+            // a line out of order or out of range is dispatched right away.
+            let due = Duration::try_from_secs_f64(line.at / speed).unwrap_or_default();
+            if let Some(wait) = due.checked_sub(started.elapsed()) {
+                std::thread::sleep(wait);
+            }
+        }
+        line.event.dispatch(state);
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "tauri-plugin-bluetooth-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn recordings_stay_in_their_directory() {
+        let root = temp_dir("recordings");
+        let dir = root.join("recordings");
+        let path = recording_path(&dir, "walk.jsonl").unwrap();
+        assert_eq!(path, dir.canonicalize().unwrap().join("walk.jsonl"));
+
+        for name in [
+            "",
+            ".",
+            "..",
+            "../walk.jsonl",
+            "nested/walk.jsonl",
+            "nested\\walk.jsonl",
+            "/tmp/walk.jsonl",
+        ] {
+            assert!(
+                matches!(recording_path(&dir, name), Err(Error::InvalidValue(_))),
+                "{name:?}"
+            );
+        }
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn restarting_a_recording_starts_its_file_over() {
+        let dir = temp_dir("recording-restart");
+        let path = dir.join("walk.jsonl");
+        let recorder = Recorder::default();
+        recorder.start(path.clone()).unwrap();
+        recorder.record(|| RecordedEvent::PowerWarning);
+        recorder.start(path.clone()).unwrap();
+        recorder.record(|| RecordedEvent::AdapterState(AdapterState::PoweredOff));
+        let summary = recorder.stop().unwrap();
+        assert_eq!(summary.events, 1);
+        assert!(!recorder.is_recording());
+
+        let lines = read_recording(&path).unwrap();
+        assert_eq!(lines.len(), 1);
+        assert!(matches!(
+            lines[0].event,
+            RecordedEvent::AdapterState(AdapterState::PoweredOff)
+        ));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn links_out_of_the_directory_are_rejected() {
+        let root = temp_dir("recording-links");
+        let dir = root.join("recordings");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(root.join("secret"), "").unwrap();
+        std::os::unix::fs::symlink(root.join("secret"), dir.join("secret.jsonl")).unwrap();
+        std::os::unix::fs::symlink(root.join("missing"), dir.join("missing.jsonl")).unwrap();
+        assert!(matches!(
+            recording_path(&dir, "secret.jsonl"),
+            Err(Error::InvalidValue(_))
+        ));
+        assert!(recording_path(&dir, "missing.jsonl").is_err());
+        fs::remove_dir_all(root).unwrap();
+    }
+}
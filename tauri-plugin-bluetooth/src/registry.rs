@@ -0,0 +1,360 @@
+use crate::beacon;
+use crate::bridge::Device;
+use crate::clock::{unix_millis, Clock, SystemClock};
+use crate::profiles::DeviceInformation;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Devices not heard from for this long are evicted, unless configured otherwise.
+pub const DEFAULT_DEVICE_TTL: Duration = Duration::from_secs(60);
+/// Number of readings kept per device.
+const RSSI_HISTORY_LEN: usize = 32;
+
+/// A reading in the history of a device, `at` is in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RssiPoint {
+    pub at: u64,
+    pub rssi: i32,
+}
+
+/// What the registry knows about a device, times are in milliseconds since the Unix epoch.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceSnapshot {
+    pub device: Device,
+    pub first_seen: u64,
+    pub last_seen: u64,
+    pub rssi_history: Vec<RssiPoint>,
+    /// Last result of `read_device_info`.
+    pub device_info: Option<DeviceInformation>,
+}
+
+/// Criteria of `list_devices`, every field that is set must match.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DeviceFilter {
+    /// Case insensitive substring of the name or the bluetooth name.
+    pub name: Option<String>,
+    pub min_rssi: Option<i32>,
+    /// Peripheral state, e.g. `connected`.
+    pub state: Option<String>,
+}
+
+impl DeviceFilter {
+    pub fn matches(&self, device: &Device) -> bool {
+        if let Some(pattern) = &self.name {
+            let pattern = pattern.to_lowercase();
+            let found = [&device.name, &device.bl_name]
+                .into_iter()
+                .flatten()
+                .any(|name| name.to_lowercase().contains(&pattern));
+            if !found {
+                return false;
+            }
+        }
+        if self.min_rssi.is_some_and(|min_rssi| device.rssi < min_rssi) {
+            return false;
+        }
+        if let Some(state) = &self.state {
+            if device.state.as_ref() != Some(state) {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+struct Entry {
+    device: Device,
+    first_seen: u64,
+    last_seen: u64,
+    last_seen_at: Instant,
+    rssi_history: VecDeque<RssiPoint>,
+    device_info: Option<DeviceInformation>,
+}
+
+impl Entry {
+    fn snapshot(&self) -> DeviceSnapshot {
+        DeviceSnapshot {
+            device: self.device.clone(),
+            first_seen: self.first_seen,
+            last_seen: self.last_seen,
+            rssi_history: self.rssi_history.iter().copied().collect(),
+            device_info: self.device_info.clone(),
+        }
+    }
+
+    fn push_rssi(&mut self, at: u64, rssi: i32) {
+        if self.rssi_history.len() >= RSSI_HISTORY_LEN {
+            self.rssi_history.pop_front();
+        }
+        self.rssi_history.push_back(RssiPoint { at, rssi });
+    }
+}
+
+/// Thread-safe view of the devices around, fed by the delegate callbacks.
+pub struct DeviceRegistry<C: Clock = SystemClock> {
+    clock: C,
+    ttl: Mutex<Duration>,
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl Default for DeviceRegistry<SystemClock> {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> DeviceRegistry<C> {
+    pub fn with_clock(clock: C) -> Self {
+        DeviceRegistry {
+            clock,
+            ttl: Mutex::new(DEFAULT_DEVICE_TTL),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn ttl(&self) -> Duration {
+        *self.ttl.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn set_ttl(&self, ttl: Duration) {
+        *self.ttl.lock().unwrap_or_else(|e| e.into_inner()) = ttl;
+    }
+
+    /// Record a sighting, fields missing from `device` keep their previous value.
+    pub fn record(&self, device: &Device) -> DeviceSnapshot {
+        let now = self.clock.now();
+        let timestamp = unix_millis();
+        let mut entries = self.lock();
+        let entry = entries
+            .entry(device.uuid.clone())
+            .and_modify(|entry| merge(&mut entry.device, device))
+            .or_insert_with(|| Entry {
+                device: device.clone(),
+                first_seen: timestamp,
+                last_seen: timestamp,
+                last_seen_at: now,
+                rssi_history: VecDeque::new(),
+                device_info: None,
+            });
+        entry.last_seen = timestamp;
+        entry.last_seen_at = now;
+        entry.push_rssi(timestamp, device.rssi);
+        entry.snapshot()
+    }
+
+    /// Record a reading of a known device.
+    pub fn record_rssi(&self, identifier: &str, rssi: i32) {
+        let now = self.clock.now();
+        let timestamp = unix_millis();
+        if let Some(entry) = self.lock().get_mut(identifier) {
+            entry.device.rssi = rssi;
+            entry.last_seen = timestamp;
+            entry.last_seen_at = now;
+            entry.push_rssi(timestamp, rssi);
+        }
+    }
+
+    /// Cache the Device Information of a known device, its manufacturer and model replace
+    /// those of the device.
+    pub fn record_device_info(&self, identifier: &str, info: &DeviceInformation) {
+        if let Some(entry) = self.lock().get_mut(identifier) {
+            if info.manufacturer_name.is_some() {
+                entry.device.manufacture.clone_from(&info.manufacturer_name);
+            }
+            if info.model_number.is_some() {
+                entry.device.model.clone_from(&info.model_number);
+            }
+            entry.device_info = Some(info.clone());
+        }
+    }
+
+    pub fn remove(&self, identifier: &str) -> Option<DeviceSnapshot> {
+        self.lock().remove(identifier).map(|entry| entry.snapshot())
+    }
+
+    /// Drop the devices not seen within the TTL and return them.
+    pub fn evict_stale(&self) -> Vec<Device> {
+        let now = self.clock.now();
+        let ttl = self.ttl();
+        let mut evicted = vec![];
+        self.lock().retain(|_, entry| {
+            let fresh = now.saturating_duration_since(entry.last_seen_at) < ttl;
+            if !fresh {
+                evicted.push(entry.device.clone());
+            }
+            fresh
+        });
+        evicted
+    }
+
+    pub fn get(&self, identifier: &str) -> Option<DeviceSnapshot> {
+        self.lock().get(identifier).map(Entry::snapshot)
+    }
+
+    /// Snapshots of the matching devices, most recently seen first.
+    pub fn list(&self, filter: &DeviceFilter) -> Vec<DeviceSnapshot> {
+        let mut devices: Vec<DeviceSnapshot> = self
+            .lock()
+            .values()
+            .filter(|entry| filter.matches(&entry.device))
+            .map(Entry::snapshot)
+            .collect();
+        devices.sort_by_key(|snapshot| std::cmp::Reverse(snapshot.last_seen));
+        devices
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, Entry>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn merge(current: &mut Device, update: &Device) {
+    fn keep(current: &mut Option<String>, update: &Option<String>) {
+        if update.is_some() {
+            current.clone_from(update);
+        }
+    }
+    keep(&mut current.manufacture, &update.manufacture);
+    keep(&mut current.model, &update.model);
+    keep(&mut current.mac_addr, &update.mac_addr);
+    keep(&mut current.bl_name, &update.bl_name);
+    keep(&mut current.name, &update.name);
+    keep(&mut current.state, &update.state);
+    current.advertisement.merge(&update.advertisement);
+    beacon::merge(&mut current.beacons, &update.beacons);
+    current.rssi = update.rssi;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advertisement::Advertisement;
+    use crate::clock::ManualClock;
+
+    fn device(uuid: &str, name: Option<&str>, rssi: i32) -> Device {
+        Device {
+            uuid: uuid.to_string(),
+            manufacture: None,
+            model: None,
+            advertisement: Advertisement::default(),
+            rssi,
+            mac_addr: None,
+            bl_name: None,
+            name: name.map(str::to_string),
+            state: None,
+            beacons: vec![],
+        }
+    }
+
+    #[test]
+    fn devices_not_heard_within_the_ttl_are_evicted() {
+        let clock = ManualClock::new();
+        let registry = DeviceRegistry::with_clock(clock.clone());
+        registry.set_ttl(Duration::from_secs(10));
+        registry.record(&device("AA", None, -60));
+        clock.advance(Duration::from_secs(6));
+        registry.record(&device("BB", None, -70));
+        clock.advance(Duration::from_secs(3));
+        registry.record_rssi("AA", -61);
+
+        clock.advance(Duration::from_secs(6));
+        assert!(registry.evict_stale().is_empty());
+        clock.advance(Duration::from_secs(1));
+        let evicted = registry.evict_stale();
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].uuid(), "BB");
+        assert!(registry.get("BB").is_none());
+
+        clock.advance(Duration::from_secs(3));
+        assert_eq!(registry.evict_stale()[0].uuid(), "AA");
+        assert!(registry.list(&DeviceFilter::default()).is_empty());
+    }
+
+    #[test]
+    fn sightings_keep_what_the_update_does_not_carry() {
+        let registry = DeviceRegistry::with_clock(ManualClock::new());
+        let mut first = device("AA", Some("Sensor"), -60);
+        first.manufacture = Some("Acme".to_string());
+        first.advertisement.service_uuids = vec!["180D".to_string()];
+        registry.record(&first);
+        let mut second = device("AA", None, -55);
+        second.model = Some("HR-1".to_string());
+        second.advertisement.tx_power_level = Some(-4);
+        let snapshot = registry.record(&second);
+
+        let merged = &snapshot.device;
+        assert_eq!(merged.name.as_deref(), Some("Sensor"));
+        assert_eq!(merged.manufacture.as_deref(), Some("Acme"));
+        assert_eq!(merged.model.as_deref(), Some("HR-1"));
+        assert_eq!(merged.rssi, -55);
+        assert_eq!(merged.advertisement.service_uuids, ["180D"]);
+        assert_eq!(merged.advertisement.tx_power_level, Some(-4));
+        let history: Vec<_> = snapshot.rssi_history.iter().map(|p| p.rssi).collect();
+        assert_eq!(history, [-60, -55]);
+    }
+
+    #[test]
+    fn the_rssi_history_is_bounded() {
+        let registry = DeviceRegistry::with_clock(ManualClock::new());
+        registry.record(&device("AA", None, 0));
+        for rssi in 1..=RSSI_HISTORY_LEN as i32 {
+            registry.record_rssi("AA", -rssi);
+        }
+        let history = registry.get("AA").unwrap().rssi_history;
+        assert_eq!(history.len(), RSSI_HISTORY_LEN);
+        assert_eq!(history[0].rssi, -1);
+        assert_eq!(
+            history[RSSI_HISTORY_LEN - 1].rssi,
+            -(RSSI_HISTORY_LEN as i32)
+        );
+    }
+
+    #[test]
+    fn the_filter_matches_every_criterion_that_is_set() {
+        let registry = DeviceRegistry::with_clock(ManualClock::new());
+        registry.record(&device("AA", Some("Heart Rate"), -50));
+        let mut connected = device("BB", Some("Scale"), -80);
+        connected.bl_name = Some("HRM scale".to_string());
+        connected.state = Some("connected".to_string());
+        registry.record(&connected);
+        registry.record(&device("CC", None, -40));
+
+        let list = |filter: DeviceFilter| {
+            let mut uuids: Vec<_> = registry
+                .list(&filter)
+                .into_iter()
+                .map(|snapshot| snapshot.device.uuid)
+                .collect();
+            uuids.sort();
+            uuids
+        };
+        assert_eq!(list(DeviceFilter::default()), ["AA", "BB", "CC"]);
+        let name = |name: &str| DeviceFilter {
+            name: Some(name.to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list(name("heart")), ["AA"]);
+        assert_eq!(list(name("HRM")), ["BB"]);
+        assert!(list(name("watch")).is_empty());
+        let min_rssi = DeviceFilter {
+            min_rssi: Some(-50),
+            ..Default::default()
+        };
+        assert_eq!(list(min_rssi), ["AA", "CC"]);
+        let state = DeviceFilter {
+            state: Some("connected".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(list(state), ["BB"]);
+        let both = DeviceFilter {
+            min_rssi: Some(-60),
+            ..name("scale")
+        };
+        assert!(list(both).is_empty());
+    }
+}
@@ -0,0 +1,569 @@
+use crate::calibration::DistanceEstimate;
+use crate::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+/// Number of outputs used to estimate the variance of filters that don't track their own.
+const VARIANCE_WINDOW: usize = 10;
+
+/// A single stage of an RSSI pipeline.
+pub trait RssiFilter: Send {
+    /// Feed a value, `None` drops it from the rest of the pipeline.
+    fn apply(&mut self, value: f64) -> Option<f64>;
+
+    /// Variance of the current estimate, for filters that track it.
+    fn variance(&self) -> Option<f64> {
+        None
+    }
+
+    fn reset(&mut self);
+}
+
+/// Exponential moving average, the smoothing the Swift side applies.
+#[derive(Debug, Clone)]
+pub struct Ema {
+    alpha: f64,
+    value: Option<f64>,
+}
+
+impl Ema {
+    /// `alpha` is clamped to `0..=1`, [`FilterConfig::validate`] rejects it instead.
+    pub fn new(alpha: f64) -> Self {
+        Ema {
+            alpha: alpha.clamp(0.0, 1.0),
+            value: None,
+        }
+    }
+}
+
+impl RssiFilter for Ema {
+    fn apply(&mut self, value: f64) -> Option<f64> {
+        let next = match self.value {
+            Some(prev) => self.alpha * value + (1.0 - self.alpha) * prev,
+            None => value,
+        };
+        self.value = Some(next);
+        Some(next)
+    }
+
+    fn reset(&mut self) {
+        self.value = None;
+    }
+}
+
+/// One dimensional Kalman filter with a constant state model.
+#[derive(Debug, Clone)]
+pub struct Kalman {
+    process_noise: f64,
+    measurement_noise: f64,
+    estimate: Option<f64>,
+    error: f64,
+}
+
+impl Kalman {
+    pub fn new(process_noise: f64, measurement_noise: f64) -> Self {
+        Kalman {
+            process_noise,
+            measurement_noise,
+            estimate: None,
+            error: measurement_noise,
+        }
+    }
+}
+
+impl RssiFilter for Kalman {
+    fn apply(&mut self, value: f64) -> Option<f64> {
+        let Some(estimate) = self.estimate else {
+            self.estimate = Some(value);
+            self.error = self.measurement_noise;
+            return Some(value);
+        };
+        let error = self.error + self.process_noise;
+        let gain = error / (error + self.measurement_noise);
+        let next = estimate + gain * (value - estimate);
+        self.error = (1.0 - gain) * error;
+        self.estimate = Some(next);
+        Some(next)
+    }
+
+    fn variance(&self) -> Option<f64> {
+        self.estimate.map(|_| self.error)
+    }
+
+    fn reset(&mut self) {
+        self.estimate = None;
+        self.error = self.measurement_noise;
+    }
+}
+
+/// Median of the last `window` values.
+#[derive(Debug, Clone)]
+pub struct Median {
+    window: usize,
+    values: VecDeque<f64>,
+}
+
+impl Median {
+    /// A `window` of 0 counts as 1.
+    pub fn new(window: usize) -> Self {
+        Median {
+            window: window.max(1),
+            values: VecDeque::new(),
+        }
+    }
+}
+
+impl RssiFilter for Median {
+    fn apply(&mut self, value: f64) -> Option<f64> {
+        push_window(&mut self.values, value, self.window);
+        let mut sorted: Vec<f64> = self.values.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let mid = sorted.len() / 2;
+        if sorted.len() % 2 == 0 {
+            Some((sorted[mid - 1] + sorted[mid]) / 2.0)
+        } else {
+            Some(sorted[mid])
+        }
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Drops values whose z-score over the last `window` values exceeds `threshold`, the windowed
+/// normalization the Swift side does with `vDSP_normalizeD`.
+///
+/// Rejected values still enter the window, so a lasting change of level gets through after a
+/// few samples.
+#[derive(Debug, Clone)]
+pub struct ZScore {
+    window: usize,
+    threshold: f64,
+    values: VecDeque<f64>,
+}
+
+impl ZScore {
+    /// A `window` of 0 counts as 1.
+    pub fn new(window: usize, threshold: f64) -> Self {
+        ZScore {
+            window: window.max(1),
+            threshold,
+            values: VecDeque::new(),
+        }
+    }
+}
+
+impl RssiFilter for ZScore {
+    fn apply(&mut self, value: f64) -> Option<f64> {
+        let outlier = match mean_variance(self.values.iter().copied()) {
+            Some((mean, variance)) if self.values.len() >= 3 && variance > 0.0 => {
+                ((value - mean) / variance.sqrt()).abs() > self.threshold
+            }
+            _ => false,
+        };
+        push_window(&mut self.values, value, self.window);
+        (!outlier).then_some(value)
+    }
+
+    fn reset(&mut self) {
+        self.values.clear();
+    }
+}
+
+/// Serializable description of a filter stage.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum FilterConfig {
+    Ema {
+        alpha: f64,
+    },
+    #[serde(rename_all = "camelCase")]
+    Kalman {
+        process_noise: f64,
+        measurement_noise: f64,
+    },
+    Median {
+        window: usize,
+    },
+    ZScore {
+        window: usize,
+        threshold: f64,
+    },
+}
+
+impl FilterConfig {
+    /// Check the parameters give a filter producing finite values.
+    pub fn validate(&self) -> Result<()> {
+        let valid = match *self {
+            FilterConfig::Ema { alpha } => alpha > 0.0 && alpha <= 1.0,
+            // the gain is 0/0 without any noise.
+            FilterConfig::Kalman {
+                process_noise,
+                measurement_noise,
+            } => {
+                (0.0..=f64::MAX).contains(&process_noise)
+                    && measurement_noise > 0.0
+                    && measurement_noise.is_finite()
+            }
+            FilterConfig::Median { window } => window > 0,
+            FilterConfig::ZScore { window, threshold } => {
+                window > 0 && threshold > 0.0 && threshold.is_finite()
+            }
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(Error::InvalidValue(format!("invalid RSSI filter {self:?}")))
+        }
+    }
+
+    pub fn build(&self) -> Box<dyn RssiFilter> {
+        match *self {
+            FilterConfig::Ema { alpha } => Box::new(Ema::new(alpha)),
+            FilterConfig::Kalman {
+                process_noise,
+                measurement_noise,
+            } => Box::new(Kalman::new(process_noise, measurement_noise)),
+            FilterConfig::Median { window } => Box::new(Median::new(window)),
+            FilterConfig::ZScore { window, threshold } => Box::new(ZScore::new(window, threshold)),
+        }
+    }
+}
+
+/// The stages applied, in order, to the readings of a device.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RssiFilterConfig {
+    pub stages: Vec<FilterConfig>,
+}
+
+impl RssiFilterConfig {
+    pub fn validate(&self) -> Result<()> {
+        self.stages.iter().try_for_each(FilterConfig::validate)
+    }
+}
+
+impl Default for RssiFilterConfig {
+    /// Same smoothing as the Swift side: an EMA with `alpha = 0.15`.
+    fn default() -> Self {
+        RssiFilterConfig {
+            stages: vec![FilterConfig::Ema { alpha: 0.15 }],
+        }
+    }
+}
+
+/// Output of a pipeline for one reading.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FilteredRssi {
+    pub value: f64,
+    pub variance: f64,
+    /// The reading was dropped by a stage, `value` is the previous estimate.
+    pub rejected: bool,
+}
+
+/// A chain of [`RssiFilter`]s.
+pub struct RssiPipeline {
+    stages: Vec<Box<dyn RssiFilter>>,
+    outputs: VecDeque<f64>,
+}
+
+impl RssiPipeline {
+    pub fn new(stages: Vec<Box<dyn RssiFilter>>) -> Self {
+        RssiPipeline {
+            stages,
+            outputs: VecDeque::new(),
+        }
+    }
+
+    pub fn from_config(config: &RssiFilterConfig) -> Self {
+        Self::new(config.stages.iter().map(FilterConfig::build).collect())
+    }
+
+    pub fn apply(&mut self, rssi: f64) -> FilteredRssi {
+        let mut value = Some(rssi);
+        for stage in self.stages.iter_mut() {
+            value = value.and_then(|value| stage.apply(value));
+        }
+        let rejected = value.is_none();
+        if let Some(value) = value {
+            push_window(&mut self.outputs, value, VARIANCE_WINDOW);
+        }
+        let variance = self
+            .stages
+            .iter()
+            .rev()
+            .find_map(|stage| stage.variance())
+            .or_else(|| mean_variance(self.outputs.iter().copied()).map(|(_, v)| v))
+            .unwrap_or_default();
+        FilteredRssi {
+            value: self.outputs.back().copied().unwrap_or(rssi),
+            variance,
+            rejected,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.stages.iter_mut().for_each(|stage| stage.reset());
+        self.outputs.clear();
+    }
+}
+
+/// Pipelines per device, built from a default configuration unless the device has its own.
+#[derive(Default)]
+pub struct RssiFilters {
+    inner: Mutex<RssiFiltersInner>,
+}
+
+#[derive(Default)]
+struct RssiFiltersInner {
+    default_config: RssiFilterConfig,
+    configs: HashMap<String, RssiFilterConfig>,
+    pipelines: HashMap<String, RssiPipeline>,
+}
+
+impl RssiFilters {
+    /// Configure one device, or every device without a configuration of its own when
+    /// `identifier` is `None`. The affected pipelines start over.
+    pub fn configure(&self, identifier: Option<&str>, config: RssiFilterConfig) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        match identifier {
+            Some(identifier) => {
+                inner.pipelines.remove(identifier);
+                inner.configs.insert(identifier.to_string(), config);
+            }
+            None => {
+                let RssiFiltersInner {
+                    configs, pipelines, ..
+                } = &mut *inner;
+                pipelines.retain(|identifier, _| configs.contains_key(identifier));
+                inner.default_config = config;
+            }
+        }
+    }
+
+    pub fn config(&self, identifier: &str) -> RssiFilterConfig {
+        let inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner
+            .configs
+            .get(identifier)
+            .unwrap_or(&inner.default_config)
+            .clone()
+    }
+
+    pub fn apply(&self, identifier: &str, rssi: i32) -> FilteredRssi {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        let RssiFiltersInner {
+            default_config,
+            configs,
+            pipelines,
+        } = &mut *inner;
+        pipelines
+            .entry(identifier.to_string())
+            .or_insert_with(|| {
+                RssiPipeline::from_config(configs.get(identifier).unwrap_or(default_config))
+            })
+            .apply(rssi as f64)
+    }
+
+    /// Forget the filter state of a device, e.g. after a disconnect.
+    pub fn reset(&self, identifier: &str) {
+        let mut inner = self.inner.lock().unwrap_or_else(|e| e.into_inner());
+        inner.pipelines.remove(identifier);
+    }
+}
+
+/// A filtered RSSI reading, as handed to [`BLEDelegate::update_filtered_rssi`].
+///
+/// [`BLEDelegate::update_filtered_rssi`]: crate::bridge::BLEDelegate::update_filtered_rssi
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RssiUpdate {
+    pub identifier: String,
+    pub rssi: i32,
+    pub estimated_rssi: i32,
+    pub filtered_rssi: f64,
+    pub variance: f64,
+    pub rejected: bool,
+    pub active: bool,
+    /// Distance derived from the filtered RSSI with the calibration of the device.
+    pub distance: DistanceEstimate,
+}
+
+fn push_window(values: &mut VecDeque<f64>, value: f64, window: usize) {
+    if values.len() >= window {
+        values.pop_front();
+    }
+    values.push_back(value);
+}
+
+fn mean_variance(values: impl Iterator<Item = f64> + Clone) -> Option<(f64, f64)> {
+    let count = values.clone().count();
+    if count == 0 {
+        return None;
+    }
+    let mean = values.clone().sum::<f64>() / count as f64;
+    let variance = values.map(|v| (v - mean).powi(2)).sum::<f64>() / count as f64;
+    Some((mean, variance))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(filter: &mut dyn RssiFilter, values: &[f64]) -> Vec<Option<f64>> {
+        values.iter().map(|value| filter.apply(*value)).collect()
+    }
+
+    #[test]
+    fn the_ema_starts_at_the_first_value() {
+        let mut ema = Ema::new(0.5);
+        assert_eq!(
+            run(&mut ema, &[-60.0, -70.0, -70.0]),
+            [Some(-60.0), Some(-65.0), Some(-67.5)]
+        );
+        ema.reset();
+        assert_eq!(ema.apply(-40.0), Some(-40.0));
+    }
+
+    #[test]
+    fn the_kalman_filter_converges_and_narrows_its_variance() {
+        let mut kalman = Kalman::new(0.01, 4.0);
+        assert_eq!(kalman.variance(), None);
+        assert_eq!(kalman.apply(-50.0), Some(-50.0));
+        assert_eq!(kalman.variance(), Some(4.0));
+
+        let mut last = (-50.0, 4.0);
+        for _ in 0..50 {
+            let value = kalman.apply(-70.0).unwrap();
+            let variance = kalman.variance().unwrap();
+            assert!(value < last.0 && value > -70.0);
+            assert!(variance < last.1);
+            last = (value, variance);
+        }
+        assert!((last.0 + 70.0).abs() < 2.0, "{}", last.0);
+
+        kalman.reset();
+        assert_eq!(kalman.variance(), None);
+        assert_eq!(kalman.apply(-40.0), Some(-40.0));
+    }
+
+    #[test]
+    fn the_median_of_an_even_window_averages_the_middle_values() {
+        let mut median = Median::new(4);
+        assert_eq!(
+            run(&mut median, &[-50.0, -90.0, -60.0, -55.0, -40.0]),
+            [
+                Some(-50.0),
+                Some(-70.0),
+                Some(-60.0),
+                Some(-57.5),
+                // the window dropped the -50.
+                Some(-57.5),
+            ]
+        );
+    }
+
+    #[test]
+    fn the_z_score_drops_spikes_but_lets_a_level_shift_through() {
+        let steady = [-60.0, -61.0, -59.0, -60.0];
+        let mut zscore = ZScore::new(5, 2.0);
+        assert!(run(&mut zscore, &steady).iter().all(Option::is_some));
+        assert_eq!(zscore.apply(-90.0), None);
+        assert_eq!(zscore.apply(-60.0), Some(-60.0));
+
+        // the device moved away: rejected once, then the window holds the new level.
+        let mut zscore = ZScore::new(5, 2.0);
+        run(&mut zscore, &steady);
+        assert_eq!(
+            run(&mut zscore, &[-61.0, -59.0, -80.0, -80.0, -80.0]),
+            [Some(-61.0), Some(-59.0), None, Some(-80.0), Some(-80.0)]
+        );
+    }
+
+    #[test]
+    fn pipelines_apply_their_stages_in_order() {
+        let pipeline = |stages| {
+            let mut pipeline = RssiPipeline::from_config(&RssiFilterConfig { stages });
+            [-60.0, -100.0, -60.0].map(|rssi| pipeline.apply(rssi).value)
+        };
+        let median = FilterConfig::Median { window: 3 };
+        let ema = FilterConfig::Ema { alpha: 0.5 };
+        assert_eq!(
+            pipeline(vec![median.clone(), ema.clone()]),
+            [-60.0, -70.0, -65.0]
+        );
+        assert_eq!(pipeline(vec![ema, median]), [-60.0, -70.0, -70.0]);
+
+        // a rejected reading never reaches the later stages.
+        let mut pipeline = RssiPipeline::from_config(&RssiFilterConfig {
+            stages: vec![
+                FilterConfig::ZScore {
+                    window: 5,
+                    threshold: 2.0,
+                },
+                FilterConfig::Ema { alpha: 0.5 },
+            ],
+        });
+        for rssi in [-60.0, -61.0, -59.0, -60.0] {
+            assert!(!pipeline.apply(rssi).rejected);
+        }
+        let spike = pipeline.apply(-90.0);
+        assert!(spike.rejected);
+        assert_eq!(spike.value, -59.875);
+    }
+
+    #[test]
+    fn invalid_filters_are_rejected() {
+        assert!(RssiFilterConfig::default().validate().is_ok());
+        let invalid = [
+            FilterConfig::Ema { alpha: 0.0 },
+            FilterConfig::Ema { alpha: 1.5 },
+            FilterConfig::Ema { alpha: f64::NAN },
+            FilterConfig::Kalman {
+                process_noise: 0.0,
+                measurement_noise: 0.0,
+            },
+            FilterConfig::Kalman {
+                process_noise: -1.0,
+                measurement_noise: 4.0,
+            },
+            FilterConfig::Kalman {
+                process_noise: 0.1,
+                measurement_noise: f64::INFINITY,
+            },
+            FilterConfig::Median { window: 0 },
+            FilterConfig::ZScore {
+                window: 5,
+                threshold: -1.0,
+            },
+            FilterConfig::ZScore {
+                window: 5,
+                threshold: f64::NAN,
+            },
+        ];
+        for stage in invalid {
+            let config = RssiFilterConfig {
+                stages: vec![FilterConfig::Median { window: 3 }, stage.clone()],
+            };
+            assert!(config.validate().is_err(), "{stage:?}");
+        }
+    }
+
+    #[test]
+    fn resetting_a_device_starts_its_pipeline_over() {
+        let filters = RssiFilters::default();
+        filters.configure(
+            None,
+            RssiFilterConfig {
+                stages: vec![FilterConfig::Ema { alpha: 0.5 }],
+            },
+        );
+        filters.apply("AA", -40);
+        assert_eq!(filters.apply("AA", -60).value, -50.0);
+        filters.reset("AA");
+        assert_eq!(filters.apply("AA", -60).value, -60.0);
+    }
+}
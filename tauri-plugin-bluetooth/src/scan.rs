@@ -0,0 +1,560 @@
+use crate::bridge::Device;
+use crate::clock::{unix_millis, Clock, SystemClock};
+use crate::gatt;
+use crate::{Error, Result, ScanOptions};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// The criteria of [`ScanOptions`] ready to be checked against every report of a scan.
+///
+/// The RSSI floor applies to every report, the other criteria only need to be met once: a
+/// device that matched keeps being reported even if a later packet lacks e.g. its name.
+#[derive(Debug, Clone, Default)]
+pub struct ScanFilter {
+    services: Vec<String>,
+    name_prefix: Option<String>,
+    name_pattern: Option<Regex>,
+    company_ids: Vec<u16>,
+    min_rssi: Option<i32>,
+}
+
+impl ScanFilter {
+    pub fn new(options: &ScanOptions) -> Result<Self> {
+        if let Some(uuid) = options
+            .services
+            .iter()
+            .find(|uuid| !gatt::is_valid_uuid(uuid))
+        {
+            return Err(Error::InvalidValue(format!("invalid service uuid {uuid}")));
+        }
+        let name_pattern = match &options.name_pattern {
+            Some(pattern) => Some(
+                Regex::new(pattern)
+                    .map_err(|e| Error::InvalidValue(format!("invalid name pattern: {e}")))?,
+            ),
+            None => None,
+        };
+        Ok(ScanFilter {
+            services: options.services.clone(),
+            name_prefix: options
+                .name_prefix
+                .as_ref()
+                .map(|prefix| prefix.to_lowercase()),
+            name_pattern,
+            company_ids: options.company_ids.clone(),
+            min_rssi: options.min_rssi,
+        })
+    }
+
+    /// Whether to report `device`, `known` is what is already known about it.
+    pub fn matches(&self, device: &Device, known: Option<&Device>) -> bool {
+        if self.min_rssi.is_some_and(|min_rssi| device.rssi < min_rssi) {
+            return false;
+        }
+        self.identifies(device) || known.is_some_and(|known| self.identifies(known))
+    }
+
+    fn identifies(&self, device: &Device) -> bool {
+        let advertisement = &device.advertisement;
+        if !self.services.is_empty()
+            && !advertisement
+                .service_uuids
+                .iter()
+                .chain(&advertisement.overflow_service_uuids)
+                .chain(advertisement.service_data.keys())
+                .any(|uuid| {
+                    self.services
+                        .iter()
+                        .any(|wanted| gatt::same_uuid(uuid, wanted))
+                })
+        {
+            return false;
+        }
+        if !self.company_ids.is_empty()
+            && !advertisement
+                .manufacturer_data
+                .keys()
+                .any(|company_id| self.company_ids.contains(company_id))
+        {
+            return false;
+        }
+        let mut names = [&advertisement.local_name, &device.name, &device.bl_name]
+            .into_iter()
+            .flatten();
+        match (&self.name_prefix, &self.name_pattern) {
+            (None, None) => true,
+            (prefix, pattern) => names.any(|name| {
+                prefix
+                    .as_ref()
+                    .map_or(true, |prefix| name.to_lowercase().starts_with(prefix))
+                    && pattern
+                        .as_ref()
+                        .map_or(true, |pattern| pattern.is_match(name))
+            }),
+        }
+    }
+}
+
+/// Session of `start_scanning` and `stop_scanning`, timed sessions get IDs from 1.
+pub const MANUAL_SESSION: u32 = 0;
+
+/// What a session saw of a device.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeviceScanSummary {
+    pub identifier: String,
+    pub name: Option<String>,
+    /// Number of reports.
+    pub sightings: u32,
+    pub max_rssi: i32,
+    pub average_rssi: f64,
+    /// Milliseconds from the start of the session to the first report.
+    pub time_to_first_sighting: u64,
+}
+
+/// Emitted when a session ends, devices are sorted by first sighting.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanSummary {
+    pub session_id: u32,
+    /// Milliseconds since the Unix epoch.
+    pub started_at: u64,
+    /// Milliseconds.
+    pub duration: u64,
+    pub devices: Vec<DeviceScanSummary>,
+}
+
+struct DeviceStats {
+    name: Option<String>,
+    first_sighting: Duration,
+    sightings: u32,
+    rssi_sum: i64,
+    max_rssi: i32,
+}
+
+struct Session {
+    options: ScanOptions,
+    filter: ScanFilter,
+    started_at: u64,
+    started: Instant,
+    /// When a timed session ends, `None` for the sessions ended on request.
+    deadline: Option<Instant>,
+    devices: HashMap<String, DeviceStats>,
+}
+
+impl Session {
+    fn summary(self, session_id: u32, now: Instant) -> ScanSummary {
+        let mut devices: Vec<DeviceScanSummary> = self
+            .devices
+            .into_iter()
+            .map(|(identifier, stats)| DeviceScanSummary {
+                identifier,
+                name: stats.name,
+                sightings: stats.sightings,
+                max_rssi: stats.max_rssi,
+                average_rssi: stats.rssi_sum as f64 / stats.sightings as f64,
+                time_to_first_sighting: stats.first_sighting.as_millis() as u64,
+            })
+            .collect();
+        devices.sort_by_key(|device| device.time_to_first_sighting);
+        ScanSummary {
+            session_id,
+            started_at: self.started_at,
+            duration: now.saturating_duration_since(self.started).as_millis() as u64,
+            devices,
+        }
+    }
+}
+
+/// The scan sessions running at once, the native scan runs as long as one of them does.
+///
+/// A report is dispatched when it passes the filter of any session, and counted in the
+/// statistics of each session it passes. The native scan reports duplicates if one session wants
+/// them, the other sessions only get the first report of each device.
+pub struct ScanSessions<C: Clock = SystemClock> {
+    clock: C,
+    next_id: Mutex<u32>,
+    sessions: Mutex<HashMap<u32, Session>>,
+}
+
+impl Default for ScanSessions<SystemClock> {
+    fn default() -> Self {
+        Self::with_clock(SystemClock)
+    }
+}
+
+impl<C: Clock> ScanSessions<C> {
+    pub fn with_clock(clock: C) -> Self {
+        ScanSessions {
+            clock,
+            next_id: Mutex::new(MANUAL_SESSION),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Open a session and return its ID, it is due to end after `duration` if set.
+    pub fn start(&self, options: ScanOptions, duration: Option<Duration>) -> Result<u32> {
+        let mut session = self.session(options)?;
+        session.deadline = duration.and_then(|duration| session.started.checked_add(duration));
+        let mut next_id = self.next_id.lock().unwrap_or_else(|e| e.into_inner());
+        *next_id += 1;
+        self.lock().insert(*next_id, session);
+        Ok(*next_id)
+    }
+
+    /// Open the manual session, or change its options if it is already open.
+    pub fn start_manual(&self, options: ScanOptions) -> Result<()> {
+        let filter = ScanFilter::new(&options)?;
+        let mut sessions = self.lock();
+        match sessions.get_mut(&MANUAL_SESSION) {
+            Some(session) => {
+                session.options = options;
+                session.filter = filter;
+            }
+            None => {
+                sessions.insert(MANUAL_SESSION, self.session(options)?);
+            }
+        }
+        Ok(())
+    }
+
+    /// Close a session, returns its summary if it was open.
+    pub fn end(&self, session_id: u32) -> Option<ScanSummary> {
+        let session = self.lock().remove(&session_id)?;
+        Some(session.summary(session_id, self.clock.now()))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// IDs of the timed sessions past their deadline, they stay open until ended.
+    pub fn expired(&self) -> Vec<u32> {
+        let now = self.clock.now();
+        self.lock()
+            .iter()
+            .filter(|(_, session)| session.deadline.is_some_and(|deadline| deadline <= now))
+            .map(|(session_id, _)| *session_id)
+            .collect()
+    }
+
+    /// Options for the native scanner covering every session: no service filter if one
+    /// session has none, duplicates if one session wants them.
+    pub fn native_options(&self) -> ScanOptions {
+        let sessions = self.lock();
+        let mut services: Vec<String> = vec![];
+        let mut unfiltered = false;
+        for session in sessions.values() {
+            unfiltered |= session.options.services.is_empty();
+            for uuid in &session.options.services {
+                if !services.iter().any(|known| gatt::same_uuid(known, uuid)) {
+                    services.push(uuid.clone());
+                }
+            }
+        }
+        ScanOptions {
+            services: if unfiltered { vec![] } else { services },
+            allow_duplicates: sessions
+                .values()
+                .any(|session| session.options.allow_duplicates),
+            ..ScanOptions::default()
+        }
+    }
+
+    /// Count a report in the sessions it passes, returns their IDs, none while no session is
+    /// open.
+    pub fn record(&self, device: &Device, known: Option<&Device>) -> Vec<u32> {
+        let now = self.clock.now();
+        let mut wanted = vec![];
+        for (session_id, session) in self.lock().iter_mut() {
+            if !session.filter.matches(device, known)
+                || (!session.options.allow_duplicates && session.devices.contains_key(&device.uuid))
+            {
+                continue;
+            }
+            wanted.push(*session_id);
+            let name = device
+                .name
+                .clone()
+                .or_else(|| device.advertisement.local_name.clone())
+                .or_else(|| device.bl_name.clone());
+            let stats = session
+                .devices
+                .entry(device.uuid.clone())
+                .or_insert_with(|| DeviceStats {
+                    name: None,
+                    first_sighting: now.saturating_duration_since(session.started),
+                    sightings: 0,
+                    rssi_sum: 0,
+                    max_rssi: device.rssi,
+                });
+            if name.is_some() {
+                stats.name = name;
+            }
+            stats.sightings += 1;
+            stats.rssi_sum += device.rssi as i64;
+            stats.max_rssi = stats.max_rssi.max(device.rssi);
+        }
+        wanted
+    }
+
+    fn session(&self, options: ScanOptions) -> Result<Session> {
+        Ok(Session {
+            filter: ScanFilter::new(&options)?,
+            options,
+            started_at: unix_millis(),
+            started: self.clock.now(),
+            deadline: None,
+            devices: HashMap::new(),
+        })
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<u32, Session>> {
+        self.sessions.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::advertisement::Advertisement;
+    use crate::clock::ManualClock;
+
+    fn device(rssi: i32, advertisement: Advertisement) -> Device {
+        Device {
+            uuid: "AA".to_string(),
+            manufacture: None,
+            model: None,
+            advertisement,
+            rssi,
+            mac_addr: None,
+            bl_name: None,
+            name: None,
+            state: None,
+            beacons: vec![],
+        }
+    }
+
+    fn named(local_name: Option<&str>, name: Option<&str>, bl_name: Option<&str>) -> Device {
+        let advertisement = Advertisement {
+            local_name: local_name.map(str::to_string),
+            ..Default::default()
+        };
+        Device {
+            name: name.map(str::to_string),
+            bl_name: bl_name.map(str::to_string),
+            ..device(-50, advertisement)
+        }
+    }
+
+    fn filter(options: ScanOptions) -> ScanFilter {
+        ScanFilter::new(&options).unwrap()
+    }
+
+    #[test]
+    fn name_prefixes_ignore_the_case() {
+        let filter = filter(ScanOptions {
+            name_prefix: Some("Hr".to_string()),
+            ..Default::default()
+        });
+        assert!(filter.matches(&named(Some("HRM Pro"), None, None), None));
+        assert!(filter.matches(&named(None, Some("hrm"), None), None));
+        assert!(filter.matches(&named(None, None, Some("hR-strap")), None));
+        assert!(!filter.matches(&named(Some("Lamp HR"), None, None), None));
+        assert!(!filter.matches(&named(None, None, None), None));
+    }
+
+    #[test]
+    fn name_patterns_match_any_of_the_names() {
+        let filter = filter(ScanOptions {
+            name_pattern: Some("^Watch [0-9]+$".to_string()),
+            ..Default::default()
+        });
+        assert!(filter.matches(&named(Some("Watch 7"), None, None), None));
+        assert!(filter.matches(&named(Some("Lamp"), Some("Watch 8"), None), None));
+        assert!(filter.matches(&named(None, Some("Lamp"), Some("Watch 9")), None));
+        assert!(!filter.matches(&named(Some("watch 7"), Some("Watch X"), None), None));
+
+        // both criteria must hold for the same name.
+        let both = ScanFilter::new(&ScanOptions {
+            name_prefix: Some("watch".to_string()),
+            name_pattern: Some("[0-9]$".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(both.matches(&named(Some("Watch 7"), None, None), None));
+        assert!(!both.matches(&named(Some("Watch"), Some("Lamp 7"), None), None));
+
+        let invalid = ScanFilter::new(&ScanOptions {
+            name_pattern: Some("(".to_string()),
+            ..Default::default()
+        });
+        assert!(matches!(invalid, Err(Error::InvalidValue(_))));
+    }
+
+    #[test]
+    fn company_ids_match_the_manufacturer_data() {
+        let filter = filter(ScanOptions {
+            company_ids: vec![0x004C, 0x0118],
+            ..Default::default()
+        });
+        let advertisement = |company_id: u16| Advertisement {
+            manufacturer_data: [(company_id, vec![0x02, 0x15])].into(),
+            ..Default::default()
+        };
+        assert!(filter.matches(&device(-50, advertisement(0x0118)), None));
+        assert!(!filter.matches(&device(-50, advertisement(0x0006)), None));
+        assert!(!filter.matches(&device(-50, Advertisement::default()), None));
+    }
+
+    #[test]
+    fn short_and_long_service_uuids_are_the_same() {
+        let filter = filter(ScanOptions {
+            services: vec!["180D".to_string()],
+            ..Default::default()
+        });
+        let long = "0000180d-0000-1000-8000-00805f9b34fb".to_string();
+        let listed = Advertisement {
+            service_uuids: vec![long.clone()],
+            ..Default::default()
+        };
+        let overflow = Advertisement {
+            overflow_service_uuids: vec!["180d".to_string()],
+            ..Default::default()
+        };
+        let data = Advertisement {
+            service_data: [(long, vec![0x01])].into(),
+            ..Default::default()
+        };
+        for advertisement in [listed, overflow, data] {
+            assert!(filter.matches(&device(-50, advertisement), None));
+        }
+        let other = Advertisement {
+            service_uuids: vec!["180F".to_string()],
+            ..Default::default()
+        };
+        assert!(!filter.matches(&device(-50, other), None));
+        assert!(ScanFilter::new(&ScanOptions {
+            services: vec!["not a uuid".to_string()],
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[test]
+    fn the_rssi_floor_applies_to_every_report() {
+        let filter = filter(ScanOptions {
+            name_prefix: Some("hrm".to_string()),
+            min_rssi: Some(-70),
+            ..Default::default()
+        });
+        let known = named(Some("HRM"), None, None);
+        let weak = Device {
+            rssi: -71,
+            ..known.clone()
+        };
+        assert!(filter.matches(&known, None));
+        assert!(!filter.matches(&weak, None));
+        // being known doesn't lift the floor.
+        assert!(!filter.matches(&weak, Some(&known)));
+        let at_floor = Device { rssi: -70, ..weak };
+        assert!(filter.matches(&at_floor, Some(&known)));
+    }
+
+    #[test]
+    fn a_matched_device_stays_matched_through_what_is_known() {
+        let filter = filter(ScanOptions {
+            name_prefix: Some("hrm".to_string()),
+            ..Default::default()
+        });
+        let known = named(Some("HRM"), None, None);
+        // a later packet without the name.
+        let nameless = named(None, None, None);
+        assert!(!filter.matches(&nameless, None));
+        assert!(filter.matches(&nameless, Some(&known)));
+        assert!(!filter.matches(&nameless, Some(&named(Some("Lamp"), None, None))));
+    }
+
+    #[test]
+    fn reports_are_recorded_in_the_sessions_they_pass() {
+        let sessions = ScanSessions::default();
+        let hrm = named(Some("HRM"), None, None);
+        let lamp = Device {
+            uuid: "BB".to_string(),
+            ..named(Some("Lamp"), None, None)
+        };
+        assert!(sessions.record(&hrm, None).is_empty(), "no session is open");
+
+        sessions.start_manual(ScanOptions::default()).unwrap();
+        let hrms = sessions
+            .start(
+                ScanOptions {
+                    name_prefix: Some("hrm".to_string()),
+                    allow_duplicates: false,
+                    ..Default::default()
+                },
+                None,
+            )
+            .unwrap();
+        let mut recorded = sessions.record(&hrm, None);
+        recorded.sort();
+        assert_eq!(recorded, [MANUAL_SESSION, hrms]);
+        assert_eq!(sessions.record(&lamp, None), [MANUAL_SESSION]);
+        // a session without duplicates only gets the first report.
+        assert_eq!(sessions.record(&hrm, Some(&hrm)), [MANUAL_SESSION]);
+
+        let summary = sessions.end(hrms).unwrap();
+        assert_eq!(summary.devices.len(), 1);
+        assert_eq!(summary.devices[0].sightings, 1);
+        assert_eq!(sessions.end(MANUAL_SESSION).unwrap().devices.len(), 2);
+        assert!(sessions.record(&hrm, None).is_empty());
+    }
+
+    #[test]
+    fn timed_sessions_expire_until_ended() {
+        let clock = ManualClock::new();
+        let sessions = ScanSessions::with_clock(clock.clone());
+        sessions.start_manual(ScanOptions::default()).unwrap();
+        let untimed = sessions.start(ScanOptions::default(), None).unwrap();
+        let short = sessions
+            .start(ScanOptions::default(), Some(Duration::from_secs(5)))
+            .unwrap();
+        let long = sessions
+            .start(ScanOptions::default(), Some(Duration::from_secs(10)))
+            .unwrap();
+        assert!(sessions.expired().is_empty());
+
+        clock.advance(Duration::from_secs(5));
+        assert_eq!(sessions.expired(), [short]);
+        assert_eq!(sessions.expired(), [short]);
+        let summary = sessions.end(short).unwrap();
+        assert_eq!(summary.session_id, short);
+        assert_eq!(summary.duration, 5000);
+        // an expired session ends once.
+        assert!(sessions.expired().is_empty());
+        assert!(sessions.end(short).is_none());
+
+        // a session ended early never expires.
+        assert!(sessions.end(long).is_some());
+        clock.advance(Duration::from_secs(10));
+        assert!(sessions.expired().is_empty());
+        assert!(sessions.end(untimed).is_some());
+        assert!(sessions.end(MANUAL_SESSION).is_some());
+        assert!(sessions.is_empty());
+    }
+
+    #[test]
+    fn out_of_range_durations_never_expire() {
+        let clock = ManualClock::new();
+        let sessions = ScanSessions::with_clock(clock.clone());
+        let session_id = sessions
+            .start(ScanOptions::default(), Some(Duration::MAX))
+            .unwrap();
+        clock.advance(Duration::from_secs(1_000_000));
+        assert!(sessions.expired().is_empty());
+        assert!(sessions.end(session_id).is_some());
+    }
+}
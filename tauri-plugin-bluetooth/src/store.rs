@@ -0,0 +1,91 @@
+//! The JSON files the plugin keeps in the app data dir.
+
+use crate::Result;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Read the value stored at `path`, the default one if there is no file yet.
+///
+/// A file that doesn't parse is moved to `<path>.bak` and the default value is returned, so a
+/// corrupted file doesn't keep the plugin from starting.
+pub(crate) fn read<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let content = match fs::read(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(e) => return Err(e.into()),
+    };
+    match serde_json::from_slice(&content) {
+        Ok(value) => Ok(value),
+        Err(e) => {
+            let backup = backup_path(path);
+            log::error!(
+                "{} is not valid: {e}, moved to {} and starting empty",
+                path.display(),
+                backup.display()
+            );
+            fs::rename(path, backup)?;
+            Ok(T::default())
+        }
+    }
+}
+
+/// Store `value` at `path`, creating the missing directories.
+pub(crate) fn write<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
+
+fn backup_path(path: &Path) -> PathBuf {
+    let mut backup = OsString::from(path);
+    backup.push(".bak");
+    backup.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "tauri-plugin-bluetooth-{name}-{}",
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn values_round_trip() {
+        let dir = temp_dir("round-trip");
+        let path = dir.join("store.json");
+        let missing: HashMap<String, u32> = read(&path).unwrap();
+        assert!(missing.is_empty());
+        let value = HashMap::from([("AA".to_string(), 1)]);
+        write(&path, &value).unwrap();
+        assert_eq!(read::<HashMap<String, u32>>(&path).unwrap(), value);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn a_corrupted_file_is_set_aside() {
+        let dir = temp_dir("corrupted");
+        let path = dir.join("store.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "{\"AA\": ").unwrap();
+        let value: HashMap<String, u32> = read(&path).unwrap();
+        assert!(value.is_empty());
+        assert!(!path.exists());
+        assert_eq!(
+            fs::read_to_string(dir.join("store.json.bak")).unwrap(),
+            "{\"AA\": "
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,198 @@
+use crate::calibration::PathLossModel;
+use crate::presence::PresenceConfig;
+use crate::{store, Result};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// A device whose presence is monitored, kept across restarts.
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TrustedDevice {
+    pub identifier: String,
+    /// Name given by the user.
+    #[serde(default)]
+    pub alias: Option<String>,
+    #[serde(default)]
+    pub mac_addr: Option<String>,
+    /// Path loss model of the device, its current calibration when omitted.
+    #[serde(default)]
+    pub calibration: Option<PathLossModel>,
+    /// Thresholds of the presence monitoring.
+    #[serde(default)]
+    pub presence: PresenceConfig,
+}
+
+#[derive(Default)]
+struct TrustedDevicesInner {
+    path: Option<PathBuf>,
+    devices: Vec<TrustedDevice>,
+}
+
+/// The trusted devices, saved to a JSON file on every change.
+#[derive(Default)]
+pub struct TrustedDevices {
+    inner: Mutex<TrustedDevicesInner>,
+}
+
+impl TrustedDevices {
+    /// Use `path` for persistence and load the devices stored there, see [`store::read`].
+    pub fn load(&self, path: PathBuf) -> Result<()> {
+        let devices = store::read(&path)?;
+        let mut inner = self.lock();
+        inner.path = Some(path);
+        inner.devices = devices;
+        Ok(())
+    }
+
+    /// Add a device, or replace the one with the same identifier. The list is left as it was
+    /// when the save fails.
+    pub fn add(&self, device: TrustedDevice) -> Result<()> {
+        let mut inner = self.lock();
+        let previous = match inner
+            .devices
+            .iter()
+            .position(|known| known.identifier == device.identifier)
+        {
+            Some(index) => Some((index, std::mem::replace(&mut inner.devices[index], device))),
+            None => {
+                inner.devices.push(device);
+                None
+            }
+        };
+        save(&inner).inspect_err(|_| match previous {
+            Some((index, known)) => inner.devices[index] = known,
+            None => {
+                inner.devices.pop();
+            }
+        })
+    }
+
+    /// Returns whether the device was trusted, it stays so when the save fails.
+    pub fn remove(&self, identifier: &str) -> Result<bool> {
+        let mut inner = self.lock();
+        let Some(index) = inner
+            .devices
+            .iter()
+            .position(|device| device.identifier == identifier)
+        else {
+            return Ok(false);
+        };
+        let removed = inner.devices.remove(index);
+        save(&inner).inspect_err(|_| inner.devices.insert(index, removed))?;
+        Ok(true)
+    }
+
+    pub fn get(&self, identifier: &str) -> Option<TrustedDevice> {
+        self.lock()
+            .devices
+            .iter()
+            .find(|device| device.identifier == identifier)
+            .cloned()
+    }
+
+    /// The devices in the order they were added.
+    pub fn list(&self) -> Vec<TrustedDevice> {
+        self.lock().devices.clone()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TrustedDevicesInner> {
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+fn save(inner: &TrustedDevicesInner) -> Result<()> {
+    match &inner.path {
+        Some(path) => store::write(path, &inner.devices),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trusted(identifier: &str, alias: &str) -> TrustedDevice {
+        TrustedDevice {
+            identifier: identifier.to_string(),
+            alias: Some(alias.to_string()),
+            mac_addr: None,
+            calibration: None,
+            presence: PresenceConfig::default(),
+        }
+    }
+
+    #[test]
+    fn adding_a_known_identifier_replaces_the_device() {
+        let devices = TrustedDevices::default();
+        devices.add(trusted("AA", "phone")).unwrap();
+        devices.add(trusted("BB", "watch")).unwrap();
+        devices.add(trusted("AA", "work phone")).unwrap();
+        let aliases: Vec<_> = devices
+            .list()
+            .into_iter()
+            .filter_map(|device| device.alias)
+            .collect();
+        assert_eq!(aliases, ["work phone", "watch"]);
+        assert_eq!(devices.get("AA"), Some(trusted("AA", "work phone")));
+
+        assert!(devices.remove("AA").unwrap());
+        assert!(!devices.remove("AA").unwrap());
+        assert_eq!(devices.get("AA"), None);
+        assert_eq!(devices.list(), [trusted("BB", "watch")]);
+    }
+
+    #[test]
+    fn devices_are_saved_on_every_change() {
+        let dir = std::env::temp_dir().join(format!(
+            "tauri-plugin-bluetooth-trusted-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("trusted.json");
+        let devices = TrustedDevices::default();
+        devices.load(path.clone()).unwrap();
+        assert!(devices.list().is_empty());
+        let mut watch = trusted("BB", "watch");
+        watch.calibration = Some(PathLossModel::default());
+        watch.presence.lock_rssi = -80;
+        devices.add(trusted("AA", "phone")).unwrap();
+        devices.add(watch.clone()).unwrap();
+
+        let reloaded = TrustedDevices::default();
+        reloaded.load(path.clone()).unwrap();
+        assert_eq!(reloaded.list(), [trusted("AA", "phone"), watch.clone()]);
+
+        devices.remove("AA").unwrap();
+        let reloaded = TrustedDevices::default();
+        reloaded.load(path).unwrap();
+        assert_eq!(reloaded.list(), [watch]);
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn a_change_that_cant_be_saved_is_undone() {
+        let dir = std::env::temp_dir().join(format!(
+            "tauri-plugin-bluetooth-trusted-unsaved-{}",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        let path = dir.join("trusted.json");
+        let devices = TrustedDevices::default();
+        devices.load(path.clone()).unwrap();
+        devices.add(trusted("AA", "phone")).unwrap();
+        devices.add(trusted("BB", "watch")).unwrap();
+        // a directory in the way of the file makes the save fail.
+        std::fs::remove_file(&path).unwrap();
+        std::fs::create_dir_all(&path).unwrap();
+
+        assert!(devices.add(trusted("CC", "tablet")).is_err());
+        assert!(devices.add(trusted("AA", "work phone")).is_err());
+        assert!(devices.remove("AA").is_err());
+        assert_eq!(
+            devices.list(),
+            [trusted("AA", "phone"), trusted("BB", "watch")]
+        );
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -19,12 +19,16 @@ static GLOBAL_IAP_HANDLER: OnceCell<Option<CalendarEventHandler>> = OnceCell::ne
 
 pub type IapCallback = extern "C" fn(callback: *const c_char);
 
+#[cfg(native_bridge)]
 extern "C" {
   fn native_purchase(account_token: *const c_char, product_id: *const c_char) -> *const c_char;
   fn native_restore_purchase();
   fn native_register_iap_callback(callback: IapCallback);
 }
 
+#[cfg(not(native_bridge))]
+use crate::stub::{native_purchase, native_register_iap_callback, native_restore_purchase};
+
 pub fn init<R: Runtime, C: DeserializeOwned>(
   app: &AppHandle<R>,
   _api: PluginApi<R, C>,
@@ -11,6 +11,8 @@ mod mobile;
 mod commands;
 mod error;
 mod models;
+#[cfg(not(native_bridge))]
+mod stub;
 
 pub use error::{Error, Result};
 
@@ -0,0 +1,30 @@
+//! Stand-in for the Swift bridge, used when the crate is built without the native library
+//! (`native-macos` disabled or a target other than macOS). StoreKit is unavailable, so every
+//! call answers with an `UNSUPPORTED` event.
+
+use crate::desktop::IapCallback;
+use std::ffi::c_char;
+use std::sync::Mutex;
+
+const UNSUPPORTED: &std::ffi::CStr =
+    c"{\"type\": \"UNSUPPORTED\", \"error\": \"StoreKit is not available in this build\"}";
+
+static IAP_CALLBACK: Mutex<Option<IapCallback>> = Mutex::new(None);
+
+pub(crate) unsafe fn native_purchase(
+    _account_token: *const c_char,
+    _product_id: *const c_char,
+) -> *const c_char {
+    UNSUPPORTED.as_ptr()
+}
+
+pub(crate) unsafe fn native_restore_purchase() {
+    let callback = *IAP_CALLBACK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(callback) = callback {
+        callback(UNSUPPORTED.as_ptr());
+    }
+}
+
+pub(crate) unsafe fn native_register_iap_callback(callback: IapCallback) {
+    *IAP_CALLBACK.lock().unwrap_or_else(|e| e.into_inner()) = Some(callback);
+}